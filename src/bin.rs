@@ -17,7 +17,10 @@ pub fn main() -> Result<()> {
         Command::Expand => issue!(9),
         Command::Highlight(cmd) => cmd.execute()?,
         Command::Format(cmd) => cmd.execute()?,
+        Command::Outline(cmd) => cmd.execute()?,
         Command::Parse => issue!(11),
+        Command::Query(cmd) => cmd.execute()?,
+        Command::Unicode(cmd) => cmd.execute()?,
         #[cfg(feature = "cli-complete")]
         Command::Completions(cmd) => cmd.execute()?,
     }
@@ -1,12 +1,13 @@
 //! (La)TeX code pretty formatting with [`latex::format`](crate::latex::format).
 
-use crate::cli::io::{InputArgs, OutputArgs};
+use crate::cli::io::{lex_chunks, InputArgs, OutputArgs, Stdin};
 use crate::cli::traits::Execute;
 use crate::error::Error;
+use crate::latex::edit::EditSet;
 use crate::latex::format::*;
-use crate::latex::token::Token;
+use crate::latex::parse::lex;
 use clap::Parser;
-use logos::Logos;
+use std::io::Write;
 
 /// Command structure to pretty format TeX documents.
 #[derive(Debug, Parser)]
@@ -18,19 +19,143 @@ pub struct FormatCommand {
     #[command(flatten)]
     #[allow(missing_docs)]
     pub output_args: OutputArgs,
+    /// Print a unified diff between each document and its formatted version,
+    /// instead of the formatted document.
+    #[arg(long, conflicts_with = "inplace")]
+    pub diff: bool,
 }
 
 impl Execute for FormatCommand {
     type Error = Error;
-    fn execute(self) -> Result<(), Self::Error> {
+    fn execute(mut self) -> Result<(), Self::Error> {
+        // Each file is formatted on its own, unless told otherwise
+        self.input_args.disable_auto_includes();
+        if self.diff && self.input_args.follows_includes() {
+            return Err(Error::IncompatibleOptions(
+                "--diff and --follow-includes=always".to_string(),
+            ));
+        }
         let mut stdout = self.output_args.stdout();
-        let sources = self.input_args.read_sources().unwrap();
 
-        for source in sources.iter() {
-            let iter = Token::lexer(source.as_str()).spanned();
+        if self.output_args.writes_inplace(&self.input_args)? {
+            // Files are written back in their original encoding
+            let files = self.input_args.read_files()?;
+
+            for (path, file) in self.input_args.filenames.iter().zip(files.iter()) {
+                let formatted = edits(file.as_str())?.apply(file.as_str());
+                std::fs::write(path, file.encode(&formatted)?)?;
+            }
+            return Ok(());
+        }
+
+        let sources = if self.input_args.streams_stdin() && !self.diff {
+            match self.input_args.stream_stdin(&mut stdout)? {
+                Stdin::Stream(stream) => {
+                    let mut formatter = AutoIndentFormatter::new(std::iter::empty());
+
+                    lex_chunks(stream, |source, tokens| {
+                        let empty = AutoIndentFormatter::new(std::iter::empty());
+                        let mut chunk_formatter =
+                            std::mem::replace(&mut formatter, empty).resume(tokens.into_iter());
+                        chunk_formatter.write_formatted(source, &mut stdout)?;
+                        formatter = chunk_formatter.resume(std::iter::empty());
+                        Ok(())
+                    })?;
+                    return Ok(());
+                }
+                Stdin::Buffered(source) => vec![source],
+            }
+        } else {
+            self.input_args.read_sources()?
+        };
+
+        let filenames = self.input_args.filenames_str();
+
+        for (i, source) in sources.iter().enumerate() {
+            let source = source.as_str();
+            let edits = edits(source)?;
 
-            AutoIndentFormatter::new(iter).write_formatted(source.as_str(), &mut stdout)?;
+            if self.diff {
+                let path = filenames.get(i).copied().unwrap_or("stdin");
+                stdout.write_all(edits.unified_diff(source, path).as_bytes())?;
+            } else {
+                stdout.write_all(edits.apply(source).as_bytes())?;
+            }
         }
         Ok(())
     }
 }
+
+/// Return the edits formatting a whole source, lexed as with
+/// [`SyntaxTree::parse`](crate::latex::parse::SyntaxTree::parse).
+fn edits(source: &str) -> Result<EditSet, Error> {
+    let (tokens, _) = lex(source);
+
+    AutoIndentFormatter::new(tokens.into_iter()).edit_set(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    #[test]
+    fn test_format() {
+        FormatCommand::command().debug_assert();
+    }
+    #[test]
+    fn test_inplace_and_follow_includes() {
+        let m = FormatCommand::try_parse_from(vec!["", "--inplace=always", "README.md"]).unwrap();
+        assert!(m.output_args.writes_inplace(&m.input_args).unwrap());
+
+        let m = FormatCommand::try_parse_from(vec![
+            "",
+            "--inplace=always",
+            "--follow-includes",
+            "README.md",
+        ])
+        .unwrap();
+        assert!(m.output_args.writes_inplace(&m.input_args).is_err());
+    }
+    #[test]
+    fn test_follow_includes_auto() {
+        let m = FormatCommand::try_parse_from(vec!["", "README.md"]).unwrap();
+        assert!(m.input_args.follows_includes());
+
+        let m = FormatCommand::try_parse_from(vec!["", "README.md", "LICENSE.md"]).unwrap();
+        assert!(!m.input_args.follows_includes());
+
+        let m = FormatCommand::try_parse_from(vec![""]).unwrap();
+        assert!(!m.input_args.follows_includes());
+
+        let m = FormatCommand::try_parse_from(vec!["", "--follow-includes"]).unwrap();
+        assert!(m.input_args.follows_includes());
+    }
+    #[test]
+    fn test_includes_not_followed() {
+        let path = "tests/data/include/main.tex";
+        let mut m = FormatCommand::try_parse_from(vec!["", "--diff", path]).unwrap();
+        assert!(m.input_args.follows_includes());
+
+        m.input_args.disable_auto_includes();
+        let sources = m.input_args.read_sources().unwrap();
+        assert_eq!(
+            sources[0].as_str(),
+            include_str!("../../../tests/data/include/main.tex")
+        );
+
+        let m =
+            FormatCommand::try_parse_from(vec!["", "--diff", "--follow-includes", path]).unwrap();
+        assert!(m.execute().is_err());
+    }
+    #[test]
+    fn test_streams_stdin_unless_legacy_encoding() {
+        let m = FormatCommand::try_parse_from(vec![""]).unwrap();
+        assert!(m.input_args.streams_stdin());
+
+        let m = FormatCommand::try_parse_from(vec!["", "--encoding", "utf8"]).unwrap();
+        assert!(m.input_args.streams_stdin());
+
+        let m = FormatCommand::try_parse_from(vec!["", "--encoding", "latin1"]).unwrap();
+        assert!(!m.input_args.streams_stdin());
+    }
+}
@@ -1,12 +1,14 @@
 //! (La)TeX code highlighting with [`latex::highlight`](crate::latex::highlight).
 
-use crate::cli::io::{InputArgs, OutputArgs};
+use crate::cli::io::{lex_chunks, InputArgs, OutputArgs, OutputFormat, Stdin};
 use crate::cli::traits::Execute;
 use crate::error::Error;
 use crate::latex::highlight::*;
+use crate::latex::parse::{lex, SyntaxTree};
+use crate::latex::serialize::locate;
 use crate::latex::token::{Token, TokenDiscriminants};
 use clap::{Parser, ValueEnum};
-use logos::Logos;
+use std::io::Write;
 
 /// Define the part of TeX code to be highlighted.
 #[derive(Clone, Debug, ValueEnum)]
@@ -17,6 +19,7 @@ enum HighlightedPart {
     Document,
     InlineMath,
     DisplayMath,
+    Arguments,
 }
 
 /// Command structure to highlight parts of TeX codes.
@@ -53,39 +56,99 @@ pub struct HighlightCommand {
     pub output_args: OutputArgs,
 }
 
+/// Return a function that updates the state of a highlighter with the next token,
+/// and returns whether this token should be highlighted.
+fn highlight_fn(
+    token: Option<TokenDiscriminants>,
+    part: &HighlightedPart,
+) -> Box<dyn FnMut(&Token<'_>) -> bool> {
+    let iter = std::iter::empty();
+    match (token, part) {
+        (Some(token), _) => {
+            let mut highlighter = TokenHighlighter::new(iter, token);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::Math) => {
+            let mut highlighter = MathHighlighter::new(iter);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::Preamble) => {
+            let mut highlighter = PreambleHighlighter::new(iter);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::Document) => {
+            let mut highlighter = DocumentHighlighter::new(iter);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::InlineMath) => {
+            let mut highlighter = InlineMathHighlighter::new(iter);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::DisplayMath) => {
+            let mut highlighter = DisplayMathHighlighter::new(iter);
+            Box::new(move |token| highlighter.highlight(token))
+        }
+        (None, HighlightedPart::Arguments) => {
+            unreachable!("arguments are only highlighted in whole sources")
+        }
+    }
+}
+
 impl Execute for HighlightCommand {
     type Error = Error;
     fn execute(self) -> Result<(), Self::Error> {
         let mut stdout = self.output_args.stdout();
-        let sources = self.input_args.read_sources().unwrap();
-
         let color = self.output_args.color_args.into();
 
+        let sources = if self.input_args.streams_stdin()
+            && !matches!(self.output_args.output_format, OutputFormat::Json)
+            && (self.token.is_some() || !matches!(self.part, HighlightedPart::Arguments))
+        {
+            match self.input_args.stream_stdin(&mut stdout)? {
+                Stdin::Stream(stream) => {
+                    let mut highlight = highlight_fn(self.token, &self.part);
+
+                    lex_chunks(stream, |source, tokens| {
+                        tokens
+                            .into_iter()
+                            .map(|(token, span)| (highlight(&token), (token, span)))
+                            .write_colorized(source, &mut stdout, &color)?;
+                        Ok(())
+                    })?;
+                    return Ok(());
+                }
+                Stdin::Buffered(source) => vec![source],
+            }
+        } else {
+            self.input_args.read_sources()?
+        };
+
         for source in sources.iter() {
-            let iter = Token::lexer(source.as_str()).spanned();
-            if let Some(token) = self.token {
-                TokenHighlighter::new(iter, token)
-                    .write_colorized(source.as_str(), &mut stdout, &color)
-                    .unwrap();
-            } else {
-                match self.part {
-                    HighlightedPart::Math => MathHighlighter::new(iter)
-                        .write_colorized(source.as_str(), &mut stdout, &color)
-                        .unwrap(),
-                    HighlightedPart::Preamble => PreambleHighlighter::new(iter)
-                        .write_colorized(source.as_str(), &mut stdout, &color)
-                        .unwrap(),
-                    HighlightedPart::Document => DocumentHighlighter::new(iter)
-                        .write_colorized(source.as_str(), &mut stdout, &color)
-                        .unwrap(),
-                    HighlightedPart::InlineMath => InlineMathHighlighter::new(iter)
-                        .write_colorized(source.as_str(), &mut stdout, &color)
-                        .unwrap(),
-                    HighlightedPart::DisplayMath => DisplayMathHighlighter::new(iter)
-                        .write_colorized(source.as_str(), &mut stdout, &color)
-                        .unwrap(),
+            let source = source.as_str();
+            let (tokens, _) = lex(source);
+            let iter = tokens.into_iter();
+            let mut highlighter: Box<dyn Iterator<Item = _>> = match (self.token, &self.part) {
+                (Some(token), _) => Box::new(TokenHighlighter::new(iter, token)),
+                (None, HighlightedPart::Math) => Box::new(MathHighlighter::new(iter)),
+                (None, HighlightedPart::Preamble) => Box::new(PreambleHighlighter::new(iter)),
+                (None, HighlightedPart::Document) => Box::new(DocumentHighlighter::new(iter)),
+                (None, HighlightedPart::InlineMath) => Box::new(InlineMathHighlighter::new(iter)),
+                (None, HighlightedPart::DisplayMath) => Box::new(DisplayMathHighlighter::new(iter)),
+                (None, HighlightedPart::Arguments) => {
+                    let tree = SyntaxTree::parse(source);
+                    Box::new(ArgumentHighlighter::new(iter, &tree))
                 }
             };
+
+            match self.output_args.output_format {
+                OutputFormat::Json => {
+                    let tokens: Vec<_> =
+                        locate(source, highlighter.highlight_spanned_tokens()).collect();
+                    serde_json::to_writer(&mut stdout, &tokens).map_err(std::io::Error::from)?;
+                    stdout.write_all(b"\n")?;
+                }
+                _ => highlighter.write_colorized(source, &mut stdout, &color)?,
+            }
         }
         Ok(())
     }
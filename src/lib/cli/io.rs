@@ -1,10 +1,18 @@
 //! Input and Output command-line tools.
 
 use crate::error::{Error, Result};
+use crate::latex::catcode::CatcodeLexer;
+use crate::latex::encoding::{decode, Decoded, Encoding};
+use crate::latex::lines::LineIndex;
+use crate::latex::project::{Expanded, FileSpan, Project, SourceFile};
+use crate::latex::serialize::Location;
+use crate::latex::stream::{StreamLexer, DEFAULT_CAPACITY};
+use crate::latex::token::{Span, SpannedToken};
+use crate::latex::verbatim::{VerbatimConfig, VerbatimLexer};
 use clap::{Args, ValueEnum};
 use is_terminal::IsTerminal;
-use std::io;
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use termcolor::{ColorChoice, StandardStream};
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -49,13 +57,24 @@ pub struct InputArgs {
     #[arg(num_args(0..), value_parser = parse_filename)]
     pub filenames: Vec<PathBuf>,
 
-    /// If set, read files from calls to `\input{...}` and `\include{...}`.
+    /// If set, read files from calls to `\input{...}`, `\include{...}`, `\subfile{...}`,
+    /// `\import{...}{...}` and `\subimport{...}{...}`, and process them as one document.
+    ///
+    /// By default (`auto`), included files are only followed when reading a single file,
+    /// and not by commands whose output replaces each file, e.g., `format` and `unicode`.
     #[arg(short, long, value_name("WHEN"), value_enum, default_value = "auto", default_missing_value = "always", num_args(0..=1), require_equals(true))]
     pub follow_includes: Choice,
 
-    /// Directoy used for relative paths, if standard input is used.
+    /// Directoy used for relative paths, if standard input is used and included files are followed.
     #[arg(short, long, value_parser = parse_directory, default_value = ".")]
     pub directory: PathBuf,
+
+    /// Input encoding (utf8, latin1, latin9 or cp1252).
+    /// If not present, input that is not valid UTF-8 is read with the encoding
+    /// declared with `\usepackage[...]{inputenc}`.
+    /// Standard input is streamed when possible, so only its beginning is checked.
+    #[arg(long)]
+    pub encoding: Option<Encoding>,
 }
 
 impl InputArgs {
@@ -64,25 +83,205 @@ impl InputArgs {
     pub fn filenames_str(&self) -> Vec<&'_ str> {
         self.filenames.iter().map(|p| p.to_str().unwrap()).collect()
     }
+    /// Return whether included files should be followed, see [`Project`].
+    ///
+    /// With [`Choice::Auto`], they are only followed when reading a single file,
+    /// as standard input has no directory to resolve them from.
+    #[must_use]
+    pub fn follows_includes(&self) -> bool {
+        match self.follow_includes {
+            Choice::Always => self.filenames.len() <= 1,
+            Choice::Auto => self.filenames.len() == 1,
+            Choice::Never => false,
+        }
+    }
+    /// Only follow included files with `--follow-includes=always`, for commands
+    /// whose output replaces each file, e.g., `format` and `unicode`.
+    pub fn disable_auto_includes(&mut self) {
+        if matches!(self.follow_includes, Choice::Auto) {
+            self.follow_includes = Choice::Never;
+        }
+    }
+    /// Return whether standard input may be read chunk by chunk, see [`InputArgs::stream_stdin`].
+    ///
+    /// This is the case when no filename is provided, included files are not followed,
+    /// and `--encoding`, if present, is UTF-8.
+    #[must_use]
+    pub fn streams_stdin(&self) -> bool {
+        self.filenames.is_empty()
+            && !self.follows_includes()
+            && matches!(self.encoding, None | Some(Encoding::Utf8))
+    }
+    /// Read standard input chunk by chunk, see [`StreamLexer`].
+    ///
+    /// If `--encoding` is not present and the first chunk is not valid UTF-8,
+    /// the whole input is read instead, so that its encoding can be inferred, see [`decode`].
+    ///
+    /// Standard output is used when waiting for user to input text.
+    pub fn stream_stdin<W>(&self, stdout: &mut W) -> Result<Stdin>
+    where
+        W: io::Write,
+    {
+        prompt_stdin(stdout)?;
+        let mut stdin = io::stdin().lock();
+        let mut bytes = Vec::new();
+        stdin
+            .by_ref()
+            .take(DEFAULT_CAPACITY as u64)
+            .read_to_end(&mut bytes)?;
+
+        let utf8 = match std::str::from_utf8(&bytes) {
+            Ok(_) => true,
+            // The first chunk may end in the middle of a character
+            Err(error) => error.error_len().is_none(),
+        };
+        if utf8 || self.encoding.is_some() {
+            return Ok(Stdin::Stream(StreamLexer::new(
+                io::Cursor::new(bytes).chain(stdin),
+            )));
+        }
+        stdin.read_to_end(&mut bytes)?;
+        let source = decode(&bytes, self.encoding)?.into_string();
+        Ok(Stdin::Buffered(Source::Text(source)))
+    }
     /// Read one or more sources, either from filenames or frind standard input.
-    pub fn read_sources(&self) -> Result<Vec<String>> {
-        let sources: Vec<String> = if self.filenames.is_empty() {
-            let mut source = String::new();
-            read_from_stdin(&mut io::stdout(), &mut source)?;
-            vec![source]
+    ///
+    /// If included files are followed, the only source is the expanded project,
+    /// see [`Project::expand`].
+    ///
+    /// Sources are transcoded into UTF-8, see [`decode`].
+    pub fn read_sources(&self) -> Result<Vec<Source>> {
+        let sources: Vec<Source> = if self.filenames.is_empty() {
+            let mut bytes = Vec::new();
+            prompt_stdin(&mut io::stdout())?;
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            let source = decode(&bytes, self.encoding)?.into_string();
+            if self.follows_includes() {
+                let project =
+                    Project::from_source(self.directory.join("stdin.tex"), source, |path| {
+                        self.read_file(path).map(Decoded::into_string)
+                    });
+                vec![Source::project(project)]
+            } else {
+                vec![Source::Text(source)]
+            }
+        } else if self.follows_includes() {
+            let project = Project::load_with(&self.filenames[0], |path| {
+                self.read_file(path).map(Decoded::into_string)
+            })?;
+            vec![Source::project(project)]
         } else {
-            let sources: std::result::Result<Vec<String>, _> =
-                self.filenames.iter().map(std::fs::read_to_string).collect();
-            sources?
+            self.read_files()?
+                .into_iter()
+                .map(|file| Source::Text(file.into_string()))
+                .collect()
         };
         Ok(sources)
     }
+    /// Read and transcode each file from filenames, keeping track of its original encoding.
+    pub fn read_files(&self) -> Result<Vec<Decoded>> {
+        let files: io::Result<Vec<Decoded>> = self
+            .filenames
+            .iter()
+            .map(|path| self.read_file(path))
+            .collect();
+        Ok(files?)
+    }
+    /// Read and transcode one file.
+    fn read_file(&self, path: &Path) -> io::Result<Decoded> {
+        decode(&std::fs::read(path)?, self.encoding)
+    }
 }
 
-/// Read lines from standard input and write to buffer string.
-///
-/// Standard output is used when waiting for user to input text.
-pub fn read_from_stdin<W>(stdout: &mut W, buffer: &mut String) -> Result<()>
+/// A source read with [`InputArgs::read_sources`].
+#[derive(Clone, Debug)]
+pub enum Source {
+    /// Content of one file, or of standard input.
+    Text(String),
+    /// A project, whose included files are followed, and its expanded content,
+    /// see [`Project::expand`].
+    Expanded(Project, Expanded),
+}
+
+impl Source {
+    /// Create a new source from a project, expanding it.
+    #[must_use]
+    pub fn project(project: Project) -> Self {
+        let expanded = project.expand();
+        Self::Expanded(project, expanded)
+    }
+
+    /// Return the source text.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Text(source) => source,
+            Self::Expanded(_, expanded) => expanded.source(),
+        }
+    }
+
+    /// Return the files the source is made of, which are empty if included files
+    /// are not followed.
+    #[must_use]
+    pub fn files(&self) -> &[SourceFile] {
+        match self {
+            Self::Text(_) => &[],
+            Self::Expanded(project, _) => project.files(),
+        }
+    }
+
+    /// Return the file, and the span in that file, of a span of the source,
+    /// see [`Expanded::locate`].
+    ///
+    /// Return [`None`] if included files are not followed.
+    #[must_use]
+    pub fn locate(&self, span: Span) -> Option<FileSpan> {
+        match self {
+            Self::Text(_) => None,
+            Self::Expanded(_, expanded) => expanded.locate(span),
+        }
+    }
+}
+
+/// Line indices of a [`Source`], and of the files it is made of.
+#[derive(Debug)]
+pub struct SourceIndex<'source> {
+    source: &'source Source,
+    index: LineIndex<'source>,
+    files: Vec<LineIndex<'source>>,
+}
+
+impl<'source> SourceIndex<'source> {
+    /// Index the lines of a source, and of the files it is made of.
+    #[must_use]
+    pub fn new(source: &'source Source) -> Self {
+        Self {
+            source,
+            index: LineIndex::new(source.as_str()),
+            files: source
+                .files()
+                .iter()
+                .map(|file| LineIndex::new(&file.source))
+                .collect(),
+        }
+    }
+
+    /// Return the path of the file containing a span of the source, if included
+    /// files are followed, and the location of the span in that file.
+    #[must_use]
+    pub fn locate(&self, span: Span) -> (Option<&'source Path>, Location) {
+        match self.source.locate(span.clone()) {
+            Some(FileSpan { file, span }) => (
+                Some(&self.source.files()[file].path),
+                Location::new(&self.files[file], span),
+            ),
+            None => (None, Location::new(&self.index, span)),
+        }
+    }
+}
+
+/// Tell the user how to end the input, if standard input is a terminal.
+fn prompt_stdin<W>(stdout: &mut W) -> Result<()>
 where
     W: io::Write,
 {
@@ -99,12 +298,49 @@ where
             "Reading from STDIN, press [CTRL+D] when you're done."
         )?;
     }
-    let stdin = std::io::stdin();
+    Ok(())
+}
+
+/// Lex each chunk of a stream, and call `f` with its text and tokens.
+///
+/// As in [`SyntaxTree::parse`](crate::latex::parse::SyntaxTree::parse), verbatim regions
+/// are lexed with [`VerbatimLexer`], and command names with [`CatcodeLexer`], whose state
+/// carries over from one chunk to the next. Verbatim environments defined in the input
+/// are known from the chunk defining them onwards, see [`StreamLexer::with_verbatim`].
+pub fn lex_chunks<R, F>(stream: StreamLexer<R>, mut f: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(&str, Vec<SpannedToken<'_>>) -> Result<()>,
+{
+    let mut stream = stream.with_verbatim(VerbatimConfig::default());
+    let (mut at_letter, mut expl_syntax) = (false, false);
 
-    while stdin.read_line(buffer)? > 0 {}
+    while let Some(chunk) = stream.next() {
+        let chunk = chunk?;
+        let source = chunk.as_str();
+        let config = stream
+            .verbatim()
+            .expect("verbatim environments should be set");
+        let mut iter = CatcodeLexer::new(source, VerbatimLexer::new(source, config))
+            .with_at_letter(at_letter)
+            .with_expl_syntax(expl_syntax);
+        let tokens: Vec<_> = iter.by_ref().collect();
+        at_letter = iter.at_letter();
+        expl_syntax = iter.expl_syntax();
+        f(source, tokens)?;
+    }
     Ok(())
 }
 
+/// Standard input, read with [`InputArgs::stream_stdin`].
+#[derive(Debug)]
+pub enum Stdin {
+    /// A stream over the input, starting with valid UTF-8.
+    Stream(StreamLexer<io::Chain<io::Cursor<Vec<u8>>, io::StdinLock<'static>>>),
+    /// The whole input, transcoded into UTF-8.
+    Buffered(Source),
+}
+
 #[derive(Args, Debug)]
 #[allow(missing_docs)]
 pub struct OutputArgs {
@@ -123,6 +359,23 @@ pub struct OutputArgs {
 }
 
 impl OutputArgs {
+    /// Return whether output should be written in place, i.e., back to the files
+    /// given as input, instead of to standard output.
+    ///
+    /// Included files cannot be written back, so writing in place while always following
+    /// them is an error. Otherwise, included files are not followed.
+    pub fn writes_inplace(&self, input_args: &InputArgs) -> Result<bool> {
+        if !matches!(self.inplace, Choice::Always) || input_args.filenames.is_empty() {
+            return Ok(false);
+        }
+        if matches!(input_args.follow_includes, Choice::Always) && input_args.follows_includes() {
+            return Err(Error::IncompatibleOptions(
+                "--inplace=always and --follow-includes".to_string(),
+            ));
+        }
+        Ok(true)
+    }
+
     /// Return a standard output stream that optionally supports color.
     #[must_use]
     pub fn stdout(&self) -> StandardStream {
@@ -10,7 +10,10 @@ pub mod color;
 pub mod format;
 pub mod highlight;
 pub mod io;
+pub mod outline;
+pub mod query;
 pub mod traits;
+pub mod unicode;
 use clap::{CommandFactory, Parser, Subcommand};
 pub use traits::*;
 #[cfg(feature = "cli-complete")]
@@ -44,7 +47,10 @@ pub enum Command {
     Highlight(highlight::HighlightCommand),
     #[clap(visible_alias = "fmt")]
     Format(format::FormatCommand),
+    Outline(outline::OutlineCommand),
     Parse,
+    Query(query::QueryCommand),
+    Unicode(unicode::UnicodeCommand),
     #[cfg(feature = "cli-complete")]
     Completions(complete::CompleteCommand),
 }
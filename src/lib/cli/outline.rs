@@ -0,0 +1,145 @@
+//! Document outline extraction with [`latex::outline`](crate::latex::outline).
+
+use crate::cli::io::{InputArgs, OutputArgs, OutputFormat, SourceIndex};
+use crate::cli::traits::Execute;
+use crate::error::Error;
+use crate::latex::outline::{Level, Outline, Section};
+use crate::latex::parse::SyntaxTree;
+use crate::latex::serialize::Location;
+use crate::latex::token::Span;
+use clap::Parser;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// A span, rendered as `{"path": ..., "start": ..., ...}` in JSON.
+#[derive(Debug, Serialize)]
+struct FileLocation<'source> {
+    /// Path of the file containing the span, if included files are followed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'source Path>,
+    /// Location of the span, in the file containing it.
+    #[serde(flatten)]
+    location: Location,
+}
+
+impl<'source> FileLocation<'source> {
+    fn new(index: &SourceIndex<'source>, span: Span) -> Self {
+        let (path, location) = index.locate(span);
+        Self { path, location }
+    }
+}
+
+/// A [`Section`] whose spans are located in the files they originate from.
+#[derive(Debug, Serialize)]
+struct Entry<'source> {
+    level: Level,
+    starred: bool,
+    short_title: Option<&'source str>,
+    title: &'source str,
+    heading: FileLocation<'source>,
+    body: FileLocation<'source>,
+    children: Vec<Entry<'source>>,
+}
+
+impl<'source> Entry<'source> {
+    fn new(index: &SourceIndex<'source>, section: &Section<'source>) -> Self {
+        Self {
+            level: section.level,
+            starred: section.starred,
+            short_title: section.short_title,
+            title: section.title,
+            heading: FileLocation::new(index, section.heading.clone()),
+            body: FileLocation::new(index, section.body.clone()),
+            children: section
+                .children
+                .iter()
+                .map(|child| Self::new(index, child))
+                .collect(),
+        }
+    }
+}
+
+/// Command structure to print the outline of TeX documents.
+#[derive(Debug, Parser)]
+#[command(about = "Print the outline (parts, chapters, sections, ...) of TeX document(s).")]
+pub struct OutlineCommand {
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub input_args: InputArgs,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub output_args: OutputArgs,
+}
+
+impl Execute for OutlineCommand {
+    type Error = Error;
+    fn execute(self) -> Result<(), Self::Error> {
+        let mut stdout = self.output_args.stdout();
+        let sources = self.input_args.read_sources()?;
+
+        for source in sources.iter() {
+            let tree = SyntaxTree::parse(source.as_str());
+            let outline = Outline::from_tree(&tree);
+
+            match self.output_args.output_format {
+                OutputFormat::Json => {
+                    let index = SourceIndex::new(source);
+                    let entries: Vec<_> = outline
+                        .sections()
+                        .iter()
+                        .map(|section| Entry::new(&index, section))
+                        .collect();
+                    serde_json::to_writer(&mut stdout, &entries).map_err(std::io::Error::from)?;
+                    stdout.write_all(b"\n")?;
+                }
+                _ => stdout.write_all(outline.to_string().as_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::io::Source;
+    use crate::latex::project::Project;
+    use clap::CommandFactory;
+    #[test]
+    fn test_outline() {
+        OutlineCommand::command().debug_assert();
+    }
+    #[test]
+    fn test_json_and_one_file() {
+        let m = OutlineCommand::try_parse_from(vec!["", "--output-format", "json", "README.md"]);
+        assert!(m.is_ok(), "{}", m.unwrap_err());
+        let m = m.unwrap();
+        assert!(matches!(m.output_args.output_format, OutputFormat::Json));
+        assert_eq!(m.input_args.filenames_str(), vec!["README.md"]);
+    }
+    #[test]
+    fn test_entry_in_included_file() {
+        let project =
+            Project::from_source("main.tex", "\\section{A}\n\\input{b}\n".to_string(), |_| {
+                Ok("x\n\\section{B}\ny".to_string())
+            });
+        let source = Source::project(project);
+        assert_eq!(source.as_str(), "\\section{A}\nx\n\\section{B}\ny\n");
+
+        let tree = SyntaxTree::parse(source.as_str());
+        let outline = Outline::from_tree(&tree);
+        let index = SourceIndex::new(&source);
+        let entry = Entry::new(&index, &outline.sections()[1]);
+        assert_eq!(
+            serde_json::to_string(&entry.heading).unwrap(),
+            r#"{"path":"b.tex","start":2,"end":13,"line":2,"column":1}"#
+        );
+        assert_eq!(entry.body.path, Some(Path::new("b.tex")));
+        assert_eq!(entry.body.location.span(), 13..15);
+
+        let entry = Entry::new(&index, &outline.sections()[0]);
+        assert_eq!(entry.heading.path, Some(Path::new("main.tex")));
+        assert_eq!(entry.heading.location.span(), 0..11);
+    }
+}
@@ -0,0 +1,147 @@
+//! Structural search in TeX documents with [`latex::query`](crate::latex::query).
+
+use crate::cli::io::{InputArgs, OutputArgs, OutputFormat, Source, SourceIndex};
+use crate::cli::traits::Execute;
+use crate::error::Error;
+use crate::latex::parse::SyntaxTree;
+use crate::latex::project::FileSpan;
+use crate::latex::query::Query;
+use crate::latex::serialize::Location;
+use crate::latex::token::Span;
+use clap::Parser;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+/// A match, rendered as `{"text": ..., "path": ..., "span": ...}` in JSON.
+#[derive(Debug, Serialize)]
+struct Match<'source> {
+    /// Source text of the match.
+    text: &'source str,
+    /// Path of the file containing the match, if included files are followed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'source Path>,
+    /// Location of the match, in the file containing it.
+    span: Location,
+}
+
+impl<'source> Match<'source> {
+    /// Create a match from a span of a source, located in the file it originates from.
+    fn new(source: &'source Source, index: &SourceIndex<'source>, span: Span) -> Self {
+        let (path, location) = index.locate(span.clone());
+
+        Self {
+            text: &source.as_str()[span],
+            path,
+            span: location,
+        }
+    }
+}
+
+/// Command structure to find nodes matching a selector in TeX documents.
+#[derive(Debug, Parser)]
+#[command(
+    about = "Print the nodes of TeX document(s) that match a selector.",
+    override_usage = "untex query [OPTIONS] <SELECTOR> [FILENAMES]...\n
+    command | untex query [OPTIONS] <SELECTOR>"
+)]
+pub struct QueryCommand {
+    /// Selector, e.g., `env[name=figure] > cmd[name=caption]`.
+    pub selector: String,
+    /// Print the spans (byte ranges) of the matches instead of their source text.
+    ///
+    /// If included files are followed, spans are prefixed by the path of the file
+    /// containing them, and relative to that file.
+    #[arg(short, long)]
+    pub spans: bool,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub input_args: InputArgs,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub output_args: OutputArgs,
+}
+
+impl Execute for QueryCommand {
+    type Error = Error;
+    fn execute(self) -> Result<(), Self::Error> {
+        let query: Query = self.selector.parse()?;
+        let mut stdout = self.output_args.stdout();
+        let sources = self.input_args.read_sources()?;
+
+        for source in sources.iter() {
+            let tree = SyntaxTree::parse(source.as_str());
+            let matches = query.select(&tree);
+
+            match self.output_args.output_format {
+                OutputFormat::Json => {
+                    let index = SourceIndex::new(source);
+                    let matches: Vec<_> = matches
+                        .iter()
+                        .map(|m| Match::new(source, &index, m.span()))
+                        .collect();
+                    serde_json::to_writer(&mut stdout, &matches).map_err(std::io::Error::from)?;
+                    writeln!(stdout)?;
+                }
+                _ => {
+                    for m in matches {
+                        let span = m.span();
+                        if !self.spans {
+                            writeln!(stdout, "{}", &source.as_str()[span])?;
+                        } else if let Some(FileSpan { file, span }) = source.locate(span.clone()) {
+                            let path = source.files()[file].path.display();
+                            writeln!(stdout, "{}:{}..{}", path, span.start, span.end)?;
+                        } else {
+                            writeln!(stdout, "{}..{}", span.start, span.end)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::project::Project;
+    use clap::CommandFactory;
+    #[test]
+    fn test_query() {
+        QueryCommand::command().debug_assert();
+    }
+    #[test]
+    fn test_selector_and_one_file() {
+        let m =
+            QueryCommand::try_parse_from(vec!["", "--spans", "cmd[name=cite] arg(1)", "README.md"]);
+        assert!(m.is_ok(), "{}", m.unwrap_err());
+        let m = m.unwrap();
+        assert!(m.spans);
+        assert_eq!(m.selector, "cmd[name=cite] arg(1)");
+        assert_eq!(m.input_args.filenames_str(), vec!["README.md"]);
+    }
+    #[test]
+    fn test_match_json() {
+        let source = Source::Text("a\n\\cite{b}".to_string());
+        let m = Match::new(&source, &SourceIndex::new(&source), 2..10);
+        assert_eq!(
+            serde_json::to_string(&m).unwrap(),
+            r#"{"text":"\\cite{b}","span":{"start":2,"end":10,"line":2,"column":1}}"#
+        );
+    }
+    #[test]
+    fn test_match_in_included_file() {
+        let project = Project::from_source("main.tex", "a\n\\input{b}\n".to_string(), |_| {
+            Ok("x\n\\cite{c}".to_string())
+        });
+        let source = Source::project(project);
+        assert_eq!(source.as_str(), "a\nx\n\\cite{c}\n");
+
+        let m = Match::new(&source, &SourceIndex::new(&source), 4..12);
+        assert_eq!(
+            serde_json::to_string(&m).unwrap(),
+            r#"{"text":"\\cite{c}","path":"b.tex","span":{"start":2,"end":10,"line":2,"column":1}}"#
+        );
+    }
+}
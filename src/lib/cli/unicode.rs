@@ -0,0 +1,117 @@
+//! Conversion between accent macros and Unicode characters with [`latex::accent`](crate::latex::accent).
+
+use crate::cli::io::{InputArgs, OutputArgs};
+use crate::cli::traits::Execute;
+use crate::error::Error;
+use crate::latex::accent::{to_ascii, to_utf8};
+use crate::latex::encoding::{encode, infer, Decoded, Encoding};
+use clap::Parser;
+use std::io::Write;
+
+/// Command structure to convert accent macros, e.g., `\'e`, into Unicode characters, and back.
+#[derive(Debug, Parser)]
+#[command(about = "Convert accent macros of TeX document(s) into Unicode characters, and back.")]
+pub struct UnicodeCommand {
+    /// Replace accent and special-character macros, e.g., `\'e` or `\ss`,
+    /// by their Unicode character.
+    ///
+    /// With `--inplace`, files keep their original encoding, and none is written
+    /// if a character cannot be represented in it.
+    #[arg(
+        long,
+        conflicts_with = "to_ascii",
+        required_unless_present = "to_ascii"
+    )]
+    pub to_utf8: bool,
+    /// Replace accented and special characters by their LaTeX macro.
+    #[arg(long)]
+    pub to_ascii: bool,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub input_args: InputArgs,
+    #[command(flatten)]
+    #[allow(missing_docs)]
+    pub output_args: OutputArgs,
+}
+
+impl UnicodeCommand {
+    /// Return the encoding a converted file is written in.
+    ///
+    /// This is its original encoding, except for files read as UTF-8 that declare another
+    /// one with `\usepackage[...]{inputenc}`, e.g., ASCII files, which get the declared one.
+    fn encoding(&self, file: &Decoded) -> Encoding {
+        match (self.input_args.encoding, file.encoding()) {
+            (None, Encoding::Utf8) => infer(file.as_str().as_bytes()).unwrap_or(Encoding::Utf8),
+            (_, encoding) => encoding,
+        }
+    }
+}
+
+impl Execute for UnicodeCommand {
+    type Error = Error;
+    fn execute(mut self) -> Result<(), Self::Error> {
+        // Each file is converted on its own, unless told otherwise
+        self.input_args.disable_auto_includes();
+        let convert = if self.to_utf8 { to_utf8 } else { to_ascii };
+
+        if self.output_args.writes_inplace(&self.input_args)? {
+            // Files are written back in their original encoding, as declared with `inputenc`
+            let files = self.input_args.read_files()?;
+            let encoded = files
+                .iter()
+                .map(|file| encode(&convert(file.as_str()), self.encoding(file)))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (path, bytes) in self.input_args.filenames.iter().zip(encoded) {
+                std::fs::write(path, bytes)?;
+            }
+            return Ok(());
+        }
+
+        let mut stdout = self.output_args.stdout();
+        let sources = self.input_args.read_sources()?;
+
+        for source in sources.iter() {
+            stdout.write_all(convert(source.as_str()).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::encoding::decode;
+    use clap::CommandFactory;
+    #[test]
+    fn test_unicode() {
+        UnicodeCommand::command().debug_assert();
+    }
+    #[test]
+    fn test_direction() {
+        assert!(UnicodeCommand::try_parse_from(vec!["", "--to-utf8", "README.md"]).is_ok());
+        assert!(UnicodeCommand::try_parse_from(vec!["", "--to-ascii", "README.md"]).is_ok());
+        assert!(UnicodeCommand::try_parse_from(vec!["", "README.md"]).is_err());
+        assert!(
+            UnicodeCommand::try_parse_from(vec!["", "--to-utf8", "--to-ascii", "README.md"])
+                .is_err()
+        );
+    }
+    #[test]
+    fn test_original_encoding() {
+        let m = UnicodeCommand::try_parse_from(vec!["", "--to-utf8", "README.md"]).unwrap();
+
+        let file = decode(b"\\usepackage[latin1]{inputenc}\n\\'e\\\"{o}", None).unwrap();
+        assert_eq!(m.encoding(&file), Encoding::Latin1);
+        assert_eq!(
+            encode(&to_utf8(file.as_str()), m.encoding(&file)).unwrap(),
+            b"\\usepackage[latin1]{inputenc}\n\xe9\xf6"
+        );
+
+        let file = decode(b"\\usepackage[latin1]{inputenc}\n\\H{o}", None).unwrap();
+        assert!(encode(&to_utf8(file.as_str()), m.encoding(&file)).is_err());
+
+        let file = decode(b"\\H{o}", None).unwrap();
+        assert_eq!(m.encoding(&file), Encoding::Utf8);
+    }
+}
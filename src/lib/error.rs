@@ -7,6 +7,14 @@ pub enum Error {
     #[error(transparent)]
     IO(#[from] std::io::Error),
 
+    /// Error from using command-line options that cannot be used together.
+    #[error("incompatible options (got {0})")]
+    IncompatibleOptions(String),
+
+    /// Error from parsing an argument specification (see [`Signature`](crate::latex::signature::Signature)).
+    #[error("invalid argument specification (got '{0}')")]
+    InvalidArgSpec(String),
+
     /// Error from parsing category code.
     #[error("invalid category code (got '{0}', must be between 0 and 15 included)")]
     InvalidCategoryCode(String),
@@ -15,9 +23,21 @@ pub enum Error {
     #[error("invalid directory (got '{0}', does not exist or is not a directory)")]
     InvalidDirectory(String),
 
+    /// Error from parsing an input encoding (see [`Encoding`](crate::latex::encoding::Encoding)).
+    #[error("invalid encoding (got '{0}', must be one of utf8, latin1, latin9 or cp1252)")]
+    InvalidEncoding(String),
+
     /// Error from checking if `filename` exists and is a actualla a file.
     #[error("invalid filename (got '{0}', does not exist or is not a file)")]
     InvalidFilename(String),
+
+    /// Error from parsing a query selector (see [`Query`](crate::latex::query::Query)).
+    #[error("invalid query (got '{0}')")]
+    InvalidQuery(String),
+
+    /// Error from adding overlapping edits to an [`EditSet`](crate::latex::edit::EditSet).
+    #[error("overlapping edits (got {0:?} and {1:?})")]
+    OverlappingEdits(crate::latex::token::Span, crate::latex::token::Span),
 }
 
 /// Result type alias with error type defined above (see [`Error`]).
@@ -0,0 +1,502 @@
+//! Accent and special-character macros, e.g., `\'e`, `\c{c}` or `\ss`, as Unicode characters.
+//!
+//! [`AccentLexer`] wraps an iterator of spanned tokens, and merges the tokens of an accent
+//! macro and its letter, e.g., `\"{o}` or `\^{\i}`, or of a special-character macro, e.g.,
+//! `\ae`, into a single [`Token::Accented`], carrying the resulting character.
+//!
+//! The reverse table is available with [`to_latex`], and whole strings can be converted
+//! with [`to_utf8`] and [`to_ascii`], e.g., to normalise author names between
+//! bibliography files and manuscripts.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::accent::{to_ascii, to_utf8, AccentLexer};
+//! use untex::prelude::*;
+//!
+//! let source = r#"G\"{o}del and Erd\H{o}s"#;
+//! let accented: Vec<_> = AccentLexer::new(source, Token::lexer(source).spanned())
+//!     .filter_map(|(token, span)| match token {
+//!         Token::Accented(c) => Some((c, &source[span])),
+//!         _ => None,
+//!     })
+//!     .collect();
+//!
+//! assert_eq!(accented, vec![('ö', r#"\"{o}"#), ('ő', r"\H{o}")]);
+//! assert_eq!(to_utf8(r"Stra\ss e, Fran\c cois"), "Straße, François");
+//! assert_eq!(to_ascii("Łukasiewicz, Straße"), r"\L{}ukasiewicz, Stra\ss{}e");
+//! ```
+
+use crate::latex::parse::lex;
+use crate::latex::token::{SpannedToken, Token};
+use logos::Logos;
+use std::collections::VecDeque;
+
+/// Accent macros, without the leading backslash, with the letters they apply to,
+/// each followed by the resulting character.
+static ACCENTS: [(&str, &str); 13] = [
+    ("'", "AÁEÉIÍOÓUÚYÝaáeéiíoóuúyýCĆcćNŃnńSŚsśZŹzźLĹlĺRŔrŕGǴgǵ"),
+    ("`", "AÀEÈIÌOÒUÙaàeèiìoòuùNǸnǹ"),
+    ("^", "AÂEÊIÎOÔUÛaâeêiîoôuûCĈcĉGĜgĝHĤhĥJĴjĵSŜsŝWŴwŵYŶyŷ"),
+    ("\"", "AÄEËIÏOÖUÜYŸaäeëiïoöuüyÿ"),
+    ("~", "AÃNÑOÕaãnñoõIĨiĩUŨuũ"),
+    ("=", "AĀaāEĒeēIĪiīOŌoōUŪuū"),
+    (".", "CĊcċEĖeėGĠgġIİZŻzż"),
+    ("u", "AĂaăEĔeĕGĞgğIĬiĭOŎoŏUŬuŭ"),
+    ("v", "CČcčDĎdďEĚeěNŇnňRŘrřSŠsšTŤtťZŽzžAǍaǎIǏiǐOǑoǒUǓuǔ"),
+    ("H", "OŐoőUŰuű"),
+    ("c", "CÇcçGĢgģKĶkķLĻlļNŅnņRŖrŗSŞsşTŢtţ"),
+    ("k", "AĄaąEĘeęIĮiįUŲuų"),
+    ("r", "AÅaåUŮuů"),
+];
+
+/// Special-character macros, without the leading backslash, with their character.
+static SPECIALS: [(&str, char); 21] = [
+    ("aa", 'å'),
+    ("AA", 'Å'),
+    ("ae", 'æ'),
+    ("AE", 'Æ'),
+    ("dh", 'ð'),
+    ("DH", 'Ð'),
+    ("dj", 'đ'),
+    ("DJ", 'Đ'),
+    ("i", 'ı'),
+    ("j", 'ȷ'),
+    ("l", 'ł'),
+    ("L", 'Ł'),
+    ("ng", 'ŋ'),
+    ("NG", 'Ŋ'),
+    ("o", 'ø'),
+    ("O", 'Ø'),
+    ("oe", 'œ'),
+    ("OE", 'Œ'),
+    ("ss", 'ß'),
+    ("th", 'þ'),
+    ("TH", 'Þ'),
+];
+
+/// Return the character of a special-character macro, without the leading backslash.
+///
+/// ```
+/// use untex::latex::accent::special;
+///
+/// assert_eq!(special("ss"), Some('ß'));
+/// assert_eq!(special("section"), None);
+/// ```
+#[must_use]
+pub fn special(name: &str) -> Option<char> {
+    SPECIALS
+        .iter()
+        .find(|(special, _)| *special == name)
+        .map(|(_, c)| *c)
+}
+
+/// Return the character of an accent macro, without the leading backslash, applied to a letter.
+///
+/// ```
+/// use untex::latex::accent::accent;
+///
+/// assert_eq!(accent("v", 'c'), Some('č'));
+/// assert_eq!(accent("v", 'x'), None);
+/// ```
+#[must_use]
+pub fn accent(name: &str, letter: char) -> Option<char> {
+    let (_, letters) = ACCENTS.iter().find(|(accent, _)| *accent == name)?;
+    let mut chars = letters.chars();
+
+    while let (Some(base), Some(accented)) = (chars.next(), chars.next()) {
+        if base == letter {
+            return Some(accented);
+        }
+    }
+    None
+}
+
+/// Return the LaTeX macro producing a character, if any, e.g., `\'e` for `'é'`.
+///
+/// Accents on `i` and `j` apply to their dotless version, e.g., `\^{\i}` for `'î'`.
+///
+/// ```
+/// use untex::latex::accent::to_latex;
+///
+/// assert_eq!(to_latex('é').unwrap(), r"\'e");
+/// assert_eq!(to_latex('ç').unwrap(), r"\c{c}");
+/// assert_eq!(to_latex('î').unwrap(), r"\^{\i}");
+/// assert_eq!(to_latex('ß').unwrap(), r"\ss");
+/// assert_eq!(to_latex('e'), None);
+/// ```
+#[must_use]
+pub fn to_latex(c: char) -> Option<String> {
+    if let Some((name, _)) = SPECIALS.iter().find(|(_, special)| *special == c) {
+        return Some(format!("\\{name}"));
+    }
+
+    for (name, letters) in ACCENTS.iter() {
+        let mut chars = letters.chars();
+        while let (Some(base), Some(accented)) = (chars.next(), chars.next()) {
+            if accented != c {
+                continue;
+            }
+            let base = match base {
+                'i' | 'j' => format!("{{\\{base}}}"),
+                _ if name.chars().all(|c| c.is_ascii_alphabetic()) => format!("{{{base}}}"),
+                _ => base.to_string(),
+            };
+            return Some(format!("\\{name}{base}"));
+        }
+    }
+    None
+}
+
+/// Lex a source, and split its tokens into runs that are either converted by
+/// [`to_utf8`] and [`to_ascii`], or kept as is.
+///
+/// Comments, verbatim content and the bodies of `tabbing` environments, where `\=`,
+/// `\'` and `` \` `` are tabbing commands, are kept as is.
+fn runs(source: &str) -> Vec<(bool, Vec<SpannedToken<'_>>)> {
+    let (tokens, _) = lex(source);
+    let mut runs: Vec<(bool, Vec<SpannedToken<'_>>)> = Vec::new();
+    let mut tabbing = 0_usize;
+
+    for token in tokens {
+        let converted = match token.0 {
+            Token::Comment | Token::Verbatim => false,
+            Token::EnvironmentBegin("tabbing") => {
+                tabbing += 1;
+                false
+            }
+            Token::EnvironmentEnd("tabbing") => {
+                tabbing = tabbing.saturating_sub(1);
+                false
+            }
+            _ => tabbing == 0,
+        };
+        match runs.last_mut() {
+            Some((last, tokens)) if *last == converted => tokens.push(token),
+            _ => runs.push((converted, vec![token])),
+        }
+    }
+    runs
+}
+
+/// Return a copy of the source, with accent and special-character macros replaced
+/// by their Unicode character, see [`AccentLexer`].
+///
+/// Comments, verbatim content and `tabbing` bodies are left unchanged.
+#[must_use]
+pub fn to_utf8(source: &str) -> String {
+    let mut string = String::with_capacity(source.len());
+
+    for (converted, tokens) in runs(source) {
+        if !converted {
+            let span = tokens[0].1.start..tokens[tokens.len() - 1].1.end;
+            string.push_str(&source[span]);
+            continue;
+        }
+        for (token, span) in AccentLexer::new(source, tokens) {
+            match token {
+                Token::Accented(c) => string.push(c),
+                _ => string.push_str(&source[span]),
+            }
+        }
+    }
+    string
+}
+
+/// Return a copy of the source, with characters replaced by their LaTeX macro, see [`to_latex`].
+///
+/// Special-character macros are followed by `{}` when the next character is a letter
+/// or a whitespace, which would otherwise be read as part of the macro or skipped.
+/// Characters without a macro, and comments, verbatim content and `tabbing` bodies,
+/// are left unchanged.
+#[must_use]
+pub fn to_ascii(source: &str) -> String {
+    let mut string = String::with_capacity(source.len());
+
+    for (converted, tokens) in runs(source) {
+        let span = tokens[0].1.start..tokens[tokens.len() - 1].1.end;
+        if !converted {
+            string.push_str(&source[span]);
+            continue;
+        }
+        for (i, c) in source[span.clone()].char_indices() {
+            match to_latex(c) {
+                Some(latex) => {
+                    string.push_str(&latex);
+                    let next = source[span.start + i + c.len_utf8()..].chars().next();
+                    let is_control_word = latex[1..].chars().all(|c| c.is_ascii_alphabetic());
+                    if is_control_word
+                        && matches!(next, Some(next) if next.is_alphabetic() || next.is_whitespace())
+                    {
+                        string.push_str("{}");
+                    }
+                }
+                None => string.push(c),
+            }
+        }
+    }
+    string
+}
+
+/// Iterator merging the tokens of accent and special-character macros into
+/// [`Token::Accented`], see the [module documentation](self).
+///
+/// The following forms are recognized:
+/// - `\'e`, `\' e`, `\'{e}` and `\'{\i}`, for all accents;
+/// - `\ss`, `\ss{}` and `\ss ` (the spaces are ignored, as in TeX), for all special characters.
+///
+/// Only the first letter of a word is accented, e.g., `\'etat` gives `é` and `tat`.
+#[derive(Debug)]
+pub struct AccentLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    source: &'source str,
+    iter: I,
+    buffer: VecDeque<SpannedToken<'source>>,
+}
+
+impl<'source, I> AccentLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    /// Create a new lexer over the spanned tokens of a source.
+    pub fn new<T>(source: &'source str, iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            source,
+            iter: iter.into_iter(),
+            buffer: VecDeque::new(),
+        }
+    }
+
+    /// Return the `i`-th next token, if any.
+    fn get(&mut self, i: usize) -> Option<SpannedToken<'source>> {
+        while self.buffer.len() <= i {
+            let token = self.iter.next()?;
+            self.buffer.push_back(token);
+        }
+        self.buffer.get(i).cloned()
+    }
+
+    /// Return whether the `i`-th next token is `token`.
+    fn is(&mut self, i: usize, token: Token<'_>) -> bool {
+        matches!(self.get(i), Some((next, _)) if next == token)
+    }
+
+    /// Return the name of a command, without the leading backslash.
+    fn name(&self, token: &SpannedToken<'source>) -> Option<&'source str> {
+        match token {
+            (Token::CommandName | Token::InvalidCommand, span) => {
+                self.source.get(span.start + 1..span.end)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the letter an accent applies to, i.e., a single character,
+    /// or a dotless `\i` or `\j`.
+    fn letter(&self, token: &SpannedToken<'source>) -> Option<char> {
+        match token {
+            (Token::Word, span) => self.source[span.clone()].chars().next(),
+            _ => match self.name(token)? {
+                "i" => Some('i'),
+                "j" => Some('j'),
+                _ => None,
+            },
+        }
+    }
+
+    /// Recognize an accented character starting at the next token, and return it
+    /// with the number of tokens it spans and its end.
+    fn recognize(&mut self) -> Option<(char, usize, usize)> {
+        let first = self.get(0)?;
+        let name = self.name(&first)?;
+
+        if let Some(c) = special(name) {
+            return Some(
+                if self.is(1, Token::BraceOpen) && self.is(2, Token::BraceClose) {
+                    (c, 3, self.buffer[2].1.end)
+                } else if self.is(1, Token::TabsOrSpaces) {
+                    (c, 2, self.buffer[1].1.end)
+                } else {
+                    (c, 1, first.1.end)
+                },
+            );
+        }
+
+        let mut i = 1;
+        if self.is(i, Token::TabsOrSpaces) {
+            i += 1;
+        }
+        let (letter, len, end) = match self.get(i)? {
+            (Token::BraceOpen, _) => {
+                let token = self.get(i + 1)?;
+                if matches!(token, (Token::Word, ref span) if self.source[span.clone()].chars().count() > 1)
+                {
+                    return None;
+                }
+                let letter = self.letter(&token)?;
+                match self.get(i + 2)? {
+                    (Token::BraceClose, span) => (letter, i + 3, span.end),
+                    _ => return None,
+                }
+            }
+            token @ (Token::Word, _) => {
+                let letter = self.letter(&token)?;
+                (letter, i + 1, token.1.start + letter.len_utf8())
+            }
+            token => (self.letter(&token)?, i + 1, token.1.end),
+        };
+        Some((accent(name, letter)?, len, end))
+    }
+}
+
+impl<'source, I> Iterator for AccentLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    type Item = SpannedToken<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (c, len, end) = match self.recognize() {
+            Some(accented) => accented,
+            None => return self.buffer.pop_front(),
+        };
+        let start = self.buffer[0].1.start;
+        let (_, last) = self.buffer.drain(..len).next_back().unwrap();
+
+        if end < last.end {
+            // Only the first letter of a word is accented
+            let rest = end..last.end;
+            let tokens: Vec<_> = Token::lexer(&self.source[rest.clone()])
+                .spanned()
+                .map(|(token, range)| (token, rest.start + range.start..rest.start + range.end))
+                .collect();
+            for token in tokens.into_iter().rev() {
+                self.buffer.push_front(token);
+            }
+        }
+        Some((Token::Accented(c), start..end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<(Token<'_>, &str)> {
+        AccentLexer::new(source, Token::lexer(source).spanned())
+            .map(|(token, span)| (token, &source[span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_accents() {
+        assert_eq!(
+            lex(r#"\'e\"{o}\c{c}\c c\^{\i}\u\i\' E"#),
+            vec![
+                (Token::Accented('é'), r"\'e"),
+                (Token::Accented('ö'), r#"\"{o}"#),
+                (Token::Accented('ç'), r"\c{c}"),
+                (Token::Accented('ç'), r"\c c"),
+                (Token::Accented('î'), r"\^{\i}"),
+                (Token::Accented('ĭ'), r"\u\i"),
+                (Token::Accented('É'), r"\' E"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_specials() {
+        assert_eq!(
+            lex(r"\ss\ae{}\o \l{\L}"),
+            vec![
+                (Token::Accented('ß'), r"\ss"),
+                (Token::Accented('æ'), r"\ae{}"),
+                (Token::Accented('ø'), r"\o "),
+                (Token::Accented('ł'), r"\l"),
+                (Token::BraceOpen, "{"),
+                (Token::Accented('Ł'), r"\L"),
+                (Token::BraceClose, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_word() {
+        assert_eq!(
+            lex(r"\'etat"),
+            vec![(Token::Accented('é'), r"\'e"), (Token::Word, "tat")]
+        );
+    }
+
+    #[test]
+    fn test_not_accents() {
+        assert_eq!(
+            lex(r"\'{ab}\'x\c{}\section\'"),
+            vec![
+                (Token::InvalidCommand, r"\'"),
+                (Token::BraceOpen, "{"),
+                (Token::Word, "ab"),
+                (Token::BraceClose, "}"),
+                (Token::InvalidCommand, r"\'"),
+                (Token::Word, "x"),
+                (Token::CommandName, r"\c"),
+                (Token::BraceOpen, "{"),
+                (Token::BraceClose, "}"),
+                (Token::CommandName, r"\section"),
+                (Token::InvalidCommand, r"\'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (_, letters) in ACCENTS.iter() {
+            for c in letters.chars().skip(1).step_by(2) {
+                let latex = to_latex(c).unwrap();
+                assert_eq!(to_utf8(&latex), c.to_string(), "{latex}");
+            }
+        }
+        for (_, c) in SPECIALS.iter() {
+            assert_eq!(to_utf8(&to_latex(*c).unwrap()), c.to_string());
+        }
+
+        let names = "Ångström, Gödel, Łukasiewicz, Nguyễn, Øre, Straße";
+        assert_eq!(to_utf8(&to_ascii(names)), names);
+    }
+
+    #[test]
+    fn test_kept() {
+        let source = r"\'e % \'e é
+\verb|\'e é| \begin{verbatim}
+\'e é
+\end{verbatim}
+\begin{tabbing}
+\=a \'e \> é
+\end{tabbing} \'e é";
+        assert_eq!(
+            to_utf8(source),
+            r"é % \'e é
+\verb|\'e é| \begin{verbatim}
+\'e é
+\end{verbatim}
+\begin{tabbing}
+\=a \'e \> é
+\end{tabbing} é é"
+        );
+        assert_eq!(
+            to_ascii(source),
+            r"\'e % \'e é
+\verb|\'e é| \begin{verbatim}
+\'e é
+\end{verbatim}
+\begin{tabbing}
+\=a \'e \> é
+\end{tabbing} \'e \'e"
+        );
+    }
+}
@@ -0,0 +1,281 @@
+//! Splitting aligned content into rows and cells.
+//!
+//! Tables (see [`latex::table`](crate::latex::table)) and math content (see
+//! [`latex::math`](crate::latex::math)) share the same structure: their content is
+//! split into [`Row`]s on `\\` and into cells on `&`. Horizontal rules (e.g., `\hline`
+//! or `\midrule`) are attached to the row they precede, and `\multicolumn` commands
+//! are recorded with the index of the cell they span.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::align::Splitter;
+//! use untex::latex::parse::SyntaxTree;
+//!
+//! let source = r"\hline a & b \\ c";
+//! let tree = SyntaxTree::parse(source);
+//! let (rows, end_rules) =
+//!     Splitter::new(source, 0).split(tree.nodes(), source.len(), |_, _, content| content);
+//!
+//! assert_eq!(rows.len(), 2);
+//! assert_eq!(&source[rows[0].rules[0].clone()], r"\hline");
+//! assert_eq!(&source[rows[0].cells[1].clone().unwrap()], "b");
+//! assert_eq!(rows[1].cells, vec![Some(16..17)]);
+//! assert!(end_rules.is_empty());
+//! ```
+
+use crate::latex::parse::{Argument, Command, Node};
+use crate::latex::token::{Span, Token};
+use logos::Logos;
+
+/// Commands drawing horizontal rules between rows.
+static RULE_COMMANDS: &[&str] = &[
+    "hline",
+    "cline",
+    "toprule",
+    "midrule",
+    "bottomrule",
+    "cmidrule",
+    "specialrule",
+    "addlinespace",
+    "morecmidrules",
+];
+
+/// Commands ending a row.
+static ROW_COMMANDS: &[&str] = &["\\", "tabularnewline"];
+
+/// A `\multicolumn{columns}{spec}{content}` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Multicolumn {
+    /// Index of the cell, in [`Row::cells`], containing the command.
+    pub cell: usize,
+    /// Number of spanned columns, if it is a valid integer.
+    pub columns: Option<usize>,
+    /// Span of the column specification.
+    pub spec: Option<Span>,
+    /// Span of the content.
+    pub content: Option<Span>,
+    /// Span of the whole command.
+    pub span: Span,
+}
+
+impl Multicolumn {
+    /// Build a [`Multicolumn`] from a `\multicolumn` command, in the given cell.
+    #[must_use]
+    pub fn from_command(command: &Command<'_>, cell: usize, source: &str) -> Self {
+        let mut groups = command.args.iter().filter_map(|arg| match arg {
+            Argument::Required(group) => Some(group.inner_span()),
+            _ => None,
+        });
+        let columns = groups.next();
+
+        Self {
+            cell,
+            columns: columns.and_then(|span| source[span].trim().parse().ok()),
+            spec: groups.next(),
+            content: groups.next(),
+            span: command.span.clone(),
+        }
+    }
+}
+
+/// A row of aligned content, with cells of type `C`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row<C> {
+    /// Cells of the row. There is always at least one cell.
+    pub cells: Vec<C>,
+    /// Spans of the rules (e.g., `\hline`) preceding the row.
+    pub rules: Vec<Span>,
+    /// `\multicolumn` commands in the row.
+    pub multicolumns: Vec<Multicolumn>,
+    /// Span of the command ending the row (e.g., `\\[2pt]`), if any.
+    pub separator: Option<Span>,
+    /// Span of the row, rules and separator included.
+    pub span: Span,
+}
+
+/// A unit of aligned content, obtained by splitting text nodes into tokens.
+#[derive(Clone, Debug)]
+pub enum Item<'tree, 'source> {
+    /// A token from a text node, whitespaces included.
+    Token(Token<'source>, Span),
+    /// Any other node, except comments.
+    Node(&'tree Node<'source>),
+}
+
+/// Split nodes into items, discarding comments.
+#[must_use]
+pub fn items<'tree, 'source>(
+    nodes: &'tree [Node<'source>],
+    source: &'source str,
+) -> Vec<Item<'tree, 'source>> {
+    let mut items = Vec::new();
+
+    for node in nodes.iter() {
+        match node {
+            Node::Text(span) => {
+                let slice = &source[span.clone()];
+                items.extend(Token::lexer(slice).spanned().map(|(token, range)| {
+                    Item::Token(token, span.start + range.start..span.start + range.end)
+                }));
+            }
+            Node::Comment(_) => (),
+            node => items.push(Item::Node(node)),
+        }
+    }
+    items
+}
+
+/// Splitter of nodes into [`Row`]s and cells.
+#[derive(Debug)]
+pub struct Splitter<'source> {
+    source: &'source str,
+    rules: Vec<Span>,
+    multicolumns: Vec<Multicolumn>,
+    /// Number of cells in the current row.
+    cells: usize,
+    /// Start of the current row.
+    row_start: usize,
+    /// Start of the current cell.
+    start: usize,
+    /// Significant content of the current cell.
+    content: Option<Span>,
+}
+
+impl<'source> Splitter<'source> {
+    /// Create a new splitter, whose first row starts at `start`.
+    #[must_use]
+    pub fn new(source: &'source str, start: usize) -> Self {
+        Self {
+            source,
+            rules: Vec::new(),
+            multicolumns: Vec::new(),
+            cells: 0,
+            row_start: start,
+            start,
+            content: None,
+        }
+    }
+
+    /// Extend the content of the current cell.
+    ///
+    /// This allows to add content that is not part of the split nodes, e.g.,
+    /// groups wrongly parsed as arguments of an environment.
+    pub fn extend(&mut self, span: Span) {
+        let start = self
+            .content
+            .as_ref()
+            .map_or(span.start, |content| content.start);
+        self.content = Some(start..span.end);
+    }
+
+    /// Split nodes, ending at `end`, into rows.
+    ///
+    /// Each cell is built by calling `cell` with its items, its span (separators
+    /// excluded) and the span of its significant content, i.e., trimmed from whitespaces,
+    /// comments and rules. Return the rows and the spans of the rules following the last row.
+    pub fn split<C, F>(
+        mut self,
+        nodes: &[Node<'source>],
+        end: usize,
+        mut cell: F,
+    ) -> (Vec<Row<C>>, Vec<Span>)
+    where
+        F: FnMut(&[Item<'_, 'source>], Span, Option<Span>) -> C,
+    {
+        let items = items(nodes, self.source);
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        let mut first = 0;
+        let mut pos = 0;
+
+        while let Some(item) = items.get(pos) {
+            pos += 1;
+            match item {
+                Item::Token(Token::TabsOrSpaces | Token::Newline, _) => (),
+                Item::Token(Token::And, span) => {
+                    cells.push(self.end_cell(&items[first..pos - 1], span.start, &mut cell));
+                    self.start = span.end;
+                    first = pos;
+                }
+                Item::Token(_, span) => self.extend(span.clone()),
+                Item::Node(Node::Command(command)) if ROW_COMMANDS.contains(&command.name) => {
+                    let span = command.span.clone();
+                    cells.push(self.end_cell(&items[first..pos - 1], span.start, &mut cell));
+                    let end = span.end;
+                    rows.push(self.end_row(std::mem::take(&mut cells), Some(span), end));
+                    first = pos;
+                }
+                Item::Node(Node::Command(command))
+                    if RULE_COMMANDS.contains(&command.name)
+                        && cells.is_empty()
+                        && self.content.is_none() =>
+                {
+                    let mut rule = command.span.clone();
+                    // `\cmidrule(lr){2-3}`: the trim is not parsed as an argument.
+                    if command.name == "cmidrule" {
+                        if let Some(Item::Token(Token::ParenOpen, _)) = items.get(pos) {
+                            while let Some(Item::Token(token, span)) = items.get(pos) {
+                                pos += 1;
+                                rule.end = span.end;
+                                if token == &Token::ParenClose {
+                                    break;
+                                }
+                            }
+                            if let Some(Item::Node(Node::Group(group))) = items.get(pos) {
+                                rule.end = group.span.end;
+                                pos += 1;
+                            }
+                        }
+                    }
+                    self.start = rule.end;
+                    self.rules.push(rule);
+                    first = pos;
+                }
+                Item::Node(Node::Command(command)) if command.name == "multicolumn" => {
+                    self.multicolumns.push(Multicolumn::from_command(
+                        command,
+                        self.cells,
+                        self.source,
+                    ));
+                    self.extend(command.span.clone());
+                }
+                Item::Node(node) => self.extend(node.span()),
+            }
+        }
+
+        // A trailing separator does not start a new row, but an empty content
+        // still has an empty row.
+        if self.content.is_some() || !cells.is_empty() || (rows.is_empty() && self.rules.is_empty())
+        {
+            let end = end.max(self.start);
+            cells.push(self.end_cell(&items[first..], end, &mut cell));
+            rows.push(self.end_row(cells, None, end));
+        }
+        (rows, self.rules)
+    }
+
+    /// End the current cell at `end`.
+    fn end_cell<C, F>(&mut self, items: &[Item<'_, 'source>], end: usize, cell: &mut F) -> C
+    where
+        F: FnMut(&[Item<'_, 'source>], Span, Option<Span>) -> C,
+    {
+        self.cells += 1;
+        cell(items, self.start..end, self.content.take())
+    }
+
+    /// End the current row at `end`, with an optional separator.
+    fn end_row<C>(&mut self, cells: Vec<C>, separator: Option<Span>, end: usize) -> Row<C> {
+        let row = Row {
+            cells,
+            rules: std::mem::take(&mut self.rules),
+            multicolumns: std::mem::take(&mut self.multicolumns),
+            separator,
+            span: self.row_start..end,
+        };
+        self.cells = 0;
+        self.row_start = end;
+        self.start = end;
+        row
+    }
+}
@@ -0,0 +1,289 @@
+//! Command names with the category code changes of `\makeatletter` and `\ExplSyntaxOn`.
+//!
+//! [`Token::CommandName`] only contains ASCII letters, as with the default category codes.
+//! In package code, however, `\makeatletter` makes `@` a letter until `\makeatother`,
+//! and `\ExplSyntaxOn` makes `_` and `:` letters until `\ExplSyntaxOff`.
+//! [`CatcodeLexer`] wraps an iterator of spanned tokens, tracks those commands, and merges
+//! the tokens that form a single command name, e.g., `\@ifnextchar` or `\seq_new:N`.
+//!
+//! Package and class files, i.e., `.sty` and `.cls` files, are read with `@` as a letter,
+//! see [`CatcodeLexer::with_at_letter`].
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::catcode::CatcodeLexer;
+//! use untex::prelude::*;
+//!
+//! let source = r"\makeatletter\c@section\makeatother\c@section
+//! \ExplSyntaxOn\seq_new:N\l_my_seq\ExplSyntaxOff";
+//! let commands: Vec<_> = CatcodeLexer::new(source, Token::lexer(source).spanned())
+//!     .filter(|(token, _)| *token == Token::CommandName)
+//!     .map(|(_, span)| &source[span])
+//!     .collect();
+//!
+//! assert_eq!(
+//!     commands,
+//!     vec![
+//!         r"\makeatletter",
+//!         r"\c@section",
+//!         r"\makeatother",
+//!         r"\c",
+//!         r"\ExplSyntaxOn",
+//!         r"\seq_new:N",
+//!         r"\l_my_seq",
+//!         r"\ExplSyntaxOff",
+//!     ]
+//! );
+//! ```
+
+use crate::latex::token::{SpannedToken, Token};
+use logos::Logos;
+use std::collections::VecDeque;
+use std::iter::Peekable;
+
+/// Iterator merging the tokens of command names that contain `@`, `_` or `:` letters,
+/// see the [module documentation](self).
+#[derive(Debug)]
+pub struct CatcodeLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    source: &'source str,
+    iter: Peekable<I>,
+    pending: VecDeque<SpannedToken<'source>>,
+    at_letter: bool,
+    expl_syntax: bool,
+}
+
+impl<'source, I> CatcodeLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    /// Create a new lexer over the spanned tokens of a source.
+    pub fn new<T>(source: &'source str, iter: T) -> Self
+    where
+        T: IntoIterator<IntoIter = I>,
+    {
+        Self {
+            source,
+            iter: iter.into_iter().peekable(),
+            pending: VecDeque::new(),
+            at_letter: false,
+            expl_syntax: false,
+        }
+    }
+
+    /// Set whether `@` is initially a letter, e.g., for `.sty` and `.cls` files.
+    #[must_use]
+    pub fn with_at_letter(mut self, at_letter: bool) -> Self {
+        self.at_letter = at_letter;
+        self
+    }
+
+    /// Set whether `_` and `:` are initially letters.
+    #[must_use]
+    pub fn with_expl_syntax(mut self, expl_syntax: bool) -> Self {
+        self.expl_syntax = expl_syntax;
+        self
+    }
+
+    /// Return whether `@` is currently a letter.
+    #[must_use]
+    pub fn at_letter(&self) -> bool {
+        self.at_letter
+    }
+
+    /// Return whether `_` and `:` are currently letters.
+    #[must_use]
+    pub fn expl_syntax(&self) -> bool {
+        self.expl_syntax
+    }
+
+    /// Return whether a character is currently a letter.
+    fn is_letter(&self, c: char) -> bool {
+        c.is_ascii_alphabetic()
+            || (self.at_letter && c == '@')
+            || (self.expl_syntax && (c == '_' || c == ':'))
+    }
+
+    /// Return the length of the leading letters of `s`.
+    fn letters_len(&self, s: &str) -> usize {
+        s.char_indices()
+            .find(|(_, c)| !self.is_letter(*c))
+            .map_or(s.len(), |(i, _)| i)
+    }
+}
+
+impl<'source, I> Iterator for CatcodeLexer<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    type Item = SpannedToken<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+        let (token, span) = self.iter.next()?;
+
+        if !matches!(
+            token,
+            Token::CommandName | Token::EscapedChar | Token::InvalidCommand
+        ) {
+            return Some((token, span));
+        }
+        match self.source.get(span.start + 1..span.end) {
+            Some(name) if !name.is_empty() && self.letters_len(name) == name.len() => (),
+            _ => return Some((token, span)),
+        }
+
+        let mut end = span.end;
+        while let Some((next_token, next)) = self.iter.peek() {
+            // Only tokens made of letters, or starting with some, can extend the name;
+            // verbatim regions, in particular, are kept whole
+            if next.start != end
+                || !matches!(
+                    next_token,
+                    Token::Word | Token::At | Token::Underscore | Token::Colon
+                )
+            {
+                break;
+            }
+            let next = next.clone();
+            let len = self.letters_len(&self.source[next.clone()]);
+            if len == 0 {
+                break;
+            }
+            self.iter.next();
+            end += len;
+
+            if len < next.len() {
+                // Only the leading letters belong to the command name
+                let rest = end..next.end;
+                self.pending
+                    .extend(Token::lexer(&self.source[rest.clone()]).spanned().map(
+                        |(token, range)| (token, rest.start + range.start..rest.start + range.end),
+                    ));
+                break;
+            }
+        }
+
+        match &self.source[span.start + 1..end] {
+            "makeatletter" => self.at_letter = true,
+            "makeatother" => self.at_letter = false,
+            "ExplSyntaxOn" => self.expl_syntax = true,
+            "ExplSyntaxOff" => self.expl_syntax = false,
+            _ => (),
+        }
+        Some((Token::CommandName, span.start..end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::verbatim::{VerbatimConfig, VerbatimLexer};
+
+    fn lex(source: &str, at_letter: bool) -> Vec<(Token<'_>, &str)> {
+        CatcodeLexer::new(source, Token::lexer(source).spanned())
+            .with_at_letter(at_letter)
+            .map(|(token, span)| (token, &source[span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_make_at_letter() {
+        assert_eq!(
+            lex(
+                r"\makeatletter\@ifnextchar[\@tempa@b{}\makeatother\@x",
+                false
+            ),
+            vec![
+                (Token::CommandName, r"\makeatletter"),
+                (Token::CommandName, r"\@ifnextchar"),
+                (Token::BracketOpen, "["),
+                (Token::CommandName, r"\@tempa@b"),
+                (Token::BraceOpen, "{"),
+                (Token::BraceClose, "}"),
+                (Token::CommandName, r"\makeatother"),
+                (Token::InvalidCommand, r"\@"),
+                (Token::Word, "x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_at_letter() {
+        assert_eq!(
+            lex(r"\c@page@ 1 \\ \@", true),
+            vec![
+                (Token::CommandName, r"\c@page@"),
+                (Token::TabsOrSpaces, " "),
+                (Token::Number, "1"),
+                (Token::TabsOrSpaces, " "),
+                (Token::DoubleBackslash, r"\\"),
+                (Token::TabsOrSpaces, " "),
+                (Token::CommandName, r"\@"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expl_syntax() {
+        assert_eq!(
+            lex(
+                r"\ExplSyntaxOn\__my_seq:Nn é\cs_new:Npé_\ExplSyntaxOff\a_b",
+                false
+            ),
+            vec![
+                (Token::CommandName, r"\ExplSyntaxOn"),
+                (Token::CommandName, r"\__my_seq:Nn"),
+                (Token::TabsOrSpaces, " "),
+                (Token::Word, "é"),
+                (Token::CommandName, r"\cs_new:Np"),
+                (Token::Word, "é"),
+                (Token::Underscore, "_"),
+                (Token::CommandName, r"\ExplSyntaxOff"),
+                (Token::CommandName, r"\a"),
+                (Token::Underscore, "_"),
+                (Token::Word, "b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_command() {
+        assert_eq!(
+            lex("Caf\\é ok", true),
+            vec![
+                (Token::Word, "Caf"),
+                (Token::InvalidCommand, "\\é"),
+                (Token::TabsOrSpaces, " "),
+                (Token::Word, "ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verbatim() {
+        let source = r"\makeatletter \verb@a $b%@ \verb|@x|";
+        let config = VerbatimConfig::default();
+        let tokens: Vec<_> = CatcodeLexer::new(source, VerbatimLexer::new(source, &config))
+            .map(|(token, span)| (token, &source[span]))
+            .collect();
+
+        assert_eq!(
+            tokens,
+            vec![
+                (Token::CommandName, r"\makeatletter"),
+                (Token::TabsOrSpaces, " "),
+                (Token::CommandName, r"\verb"),
+                (Token::Verbatim, "@a $b%@"),
+                (Token::TabsOrSpaces, " "),
+                (Token::CommandName, r"\verb"),
+                (Token::Verbatim, "|@x|"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! Diagnostics reported when parsing documents.
+//!
+//! Parsing (see [`SyntaxTree`](crate::latex::parse::SyntaxTree)) never fails:
+//! instead, issues found in the document are reported as [`Diagnostic`]s,
+//! and the syntax tree is built as if the document was correct.
+
+use crate::latex::lines::{ColumnUnit, LineIndex};
+use crate::latex::token::Span;
+use std::fmt;
+
+/// Severity of a diagnostic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The document will most likely not compile.
+    Error,
+    /// The document compiles, but may not render as expected.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// An issue found in a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Severity of the issue.
+    pub severity: Severity,
+    /// Span where the issue is located.
+    pub span: Span,
+    /// Message describing the issue.
+    pub message: String,
+    /// Other spans related to the issue, e.g., the closing delimiter of an
+    /// unbalanced group.
+    pub related_spans: Vec<Span>,
+}
+
+impl Diagnostic {
+    /// Create a new error diagnostic.
+    pub fn error<S>(span: Span, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            related_spans: Vec::new(),
+        }
+    }
+
+    /// Create a new warning diagnostic.
+    pub fn warning<S>(span: Span, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            severity: Severity::Warning,
+            span,
+            message: message.into(),
+            related_spans: Vec::new(),
+        }
+    }
+
+    /// Add a related span.
+    #[must_use]
+    pub fn with_related(mut self, span: Span) -> Self {
+        self.related_spans.push(span);
+        self
+    }
+
+    /// Return this diagnostic, prefixed with the line and column where it starts.
+    ///
+    /// Lines and columns start at 1, and columns are counted in characters.
+    ///
+    /// ```
+    /// use untex::latex::diagnostic::Diagnostic;
+    /// use untex::latex::lines::LineIndex;
+    ///
+    /// let source = "\\begin{document}\n  é}";
+    /// let diagnostic = Diagnostic::error(21..22, "unexpected '}'");
+    ///
+    /// assert_eq!(
+    ///     diagnostic.render(&LineIndex::new(source)),
+    ///     "2:4: error: unexpected '}'"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn render(&self, index: &LineIndex<'_>) -> String {
+        let position = index
+            .position(self.span.start, ColumnUnit::Char)
+            .one_based();
+        format!(
+            "{}:{}: {}: {}",
+            position.line, position.column, self.severity, self.message
+        )
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} at {:?}", self.severity, self.message, self.span)
+    }
+}
@@ -0,0 +1,398 @@
+//! Recognizing TeX dimensions, e.g., `1.5pt`, `-3mm` or `0.8\textwidth`.
+//!
+//! A dimension is a decimal number, with an optional sign, followed by a unit.
+//! The unit is either a keyword, e.g., `pt` or `em`, or a length macro, e.g.,
+//! `\textwidth` or `\baselineskip`. As in TeX:
+//!
+//! - spaces are allowed between the number and the unit, e.g., `12 bp`;
+//! - the decimal separator is either `.` or `,`, e.g., `1,5pt`;
+//! - keywords are case-insensitive, may be preceded by `true`, e.g., `1truein`,
+//!   and end after their two letters, e.g., `2ptx` is `2pt` followed by `x`.
+//!
+//! Dimensions with absolute units can be converted into each other,
+//! see [`Dimension::convert`].
+//!
+//! # Example
+//!
+//! ```
+//! use logos::Logos;
+//! use untex::latex::dimension::{dimensions, Unit};
+//! use untex::latex::token::Token;
+//!
+//! let source = r"\vspace{-3mm}\includegraphics[width=0.8\textwidth]{plot}";
+//! let found = dimensions(source, Token::lexer(source).spanned());
+//!
+//! assert_eq!(found.len(), 2);
+//! assert_eq!(found[0].value, -3.0);
+//! assert_eq!(found[0].unit, Unit::Mm);
+//! assert_eq!(&source[found[1].span.clone()], r"0.8\textwidth");
+//! assert_eq!(found[1].unit, Unit::Macro("textwidth"));
+//!
+//! let points = found[0].convert(Unit::Pt).unwrap();
+//! assert!((points.value + 8.5358).abs() < 1e-4);
+//! ```
+
+use crate::latex::token::{Span, SpannedToken, Token};
+use logos::Logos;
+use std::fmt;
+
+/// Unit of a [`Dimension`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Unit<'source> {
+    /// Point, `pt`.
+    Pt,
+    /// Pica, `pc`, i.e., 12pt.
+    Pc,
+    /// Inch, `in`, i.e., 72.27pt.
+    In,
+    /// Big point, `bp`, i.e., 1/72in.
+    Bp,
+    /// Centimeter, `cm`.
+    Cm,
+    /// Millimeter, `mm`.
+    Mm,
+    /// Didot point, `dd`, i.e., 1238/1157pt.
+    Dd,
+    /// Cicero, `cc`, i.e., 12dd.
+    Cc,
+    /// Scaled point, `sp`, i.e., 1/65536pt.
+    Sp,
+    /// Width of an 'M' in the current font, `em`.
+    Em,
+    /// Height of an 'x' in the current font, `ex`.
+    Ex,
+    /// Math unit, `mu`, i.e., 1/18em in the math font.
+    Mu,
+    /// A length macro, without the leading backslash, e.g., `textwidth`.
+    Macro(&'source str),
+}
+
+impl<'source> Unit<'source> {
+    /// Return the unit matching a keyword, e.g., `pt`, ignoring case.
+    #[must_use]
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword.to_ascii_lowercase().as_str() {
+            "pt" => Some(Unit::Pt),
+            "pc" => Some(Unit::Pc),
+            "in" => Some(Unit::In),
+            "bp" => Some(Unit::Bp),
+            "cm" => Some(Unit::Cm),
+            "mm" => Some(Unit::Mm),
+            "dd" => Some(Unit::Dd),
+            "cc" => Some(Unit::Cc),
+            "sp" => Some(Unit::Sp),
+            "em" => Some(Unit::Em),
+            "ex" => Some(Unit::Ex),
+            "mu" => Some(Unit::Mu),
+            _ => None,
+        }
+    }
+
+    /// Return the length of one unit in points, if this unit is absolute.
+    #[must_use]
+    pub fn points(&self) -> Option<f64> {
+        match self {
+            Unit::Pt => Some(1.0),
+            Unit::Pc => Some(12.0),
+            Unit::In => Some(72.27),
+            Unit::Bp => Some(72.27 / 72.0),
+            Unit::Cm => Some(72.27 / 2.54),
+            Unit::Mm => Some(72.27 / 25.4),
+            Unit::Dd => Some(1238.0 / 1157.0),
+            Unit::Cc => Some(12.0 * 1238.0 / 1157.0),
+            Unit::Sp => Some(1.0 / 65536.0),
+            Unit::Em | Unit::Ex | Unit::Mu | Unit::Macro(_) => None,
+        }
+    }
+
+    /// Return whether this unit has a fixed length, i.e., does not depend on the font
+    /// or on the document layout.
+    #[must_use]
+    pub fn is_absolute(&self) -> bool {
+        self.points().is_some()
+    }
+}
+
+impl<'source> fmt::Display for Unit<'source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self {
+            Unit::Pt => "pt",
+            Unit::Pc => "pc",
+            Unit::In => "in",
+            Unit::Bp => "bp",
+            Unit::Cm => "cm",
+            Unit::Mm => "mm",
+            Unit::Dd => "dd",
+            Unit::Cc => "cc",
+            Unit::Sp => "sp",
+            Unit::Em => "em",
+            Unit::Ex => "ex",
+            Unit::Mu => "mu",
+            Unit::Macro(name) => return write!(f, "\\{name}"),
+        };
+        f.write_str(keyword)
+    }
+}
+
+/// A dimension, i.e., a value and its unit, e.g., `1.5pt`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dimension<'source> {
+    /// Value, sign included.
+    pub value: f64,
+    /// Unit.
+    pub unit: Unit<'source>,
+    /// Span of the whole dimension, from the sign or the number up to the unit.
+    pub span: Span,
+}
+
+impl<'source> Dimension<'source> {
+    /// Return this dimension in points, if its unit is absolute.
+    #[must_use]
+    pub fn to_points(&self) -> Option<f64> {
+        self.unit.points().map(|points| self.value * points)
+    }
+
+    /// Return the same dimension, expressed in another unit.
+    ///
+    /// Both units must be absolute; the span is left unchanged.
+    #[must_use]
+    pub fn convert(&self, unit: Unit<'source>) -> Option<Self> {
+        let value = self.to_points()? / unit.points()?;
+        Some(Self {
+            value,
+            unit,
+            span: self.span.clone(),
+        })
+    }
+}
+
+impl<'source> fmt::Display for Dimension<'source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit)
+    }
+}
+
+/// Return whether a command name, without the leading backslash, is a length macro,
+/// e.g., `textwidth`, `baselineskip` or `tabcolsep`.
+#[must_use]
+pub fn is_length_macro(name: &str) -> bool {
+    const SUFFIXES: [&str; 9] = [
+        "width", "height", "depth", "skip", "sep", "indent", "length", "margin", "rule",
+    ];
+
+    name.chars().all(|c| c.is_ascii_alphabetic())
+        && SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// Return every dimension found in an iterator of spanned tokens.
+pub fn dimensions<'source, I>(source: &'source str, iter: I) -> Vec<Dimension<'source>>
+where
+    I: IntoIterator<Item = SpannedToken<'source>>,
+{
+    let tokens: Vec<_> = iter.into_iter().collect();
+    let mut dimensions = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        match recognize(source, &tokens, i) {
+            Some((dimension, next)) => {
+                dimensions.push(dimension);
+                i = next;
+            }
+            None => i += 1,
+        }
+    }
+    dimensions
+}
+
+/// Parse a whole string as a dimension, ignoring surrounding whitespace.
+///
+/// ```
+/// use untex::latex::dimension::{parse, Unit};
+///
+/// let dimension = parse(" 2\\baselineskip ").unwrap();
+/// assert_eq!(dimension.value, 2.0);
+/// assert_eq!(dimension.unit, Unit::Macro("baselineskip"));
+/// assert!(parse("2 apples").is_none());
+/// ```
+#[must_use]
+pub fn parse(source: &str) -> Option<Dimension<'_>> {
+    let start = source.len() - source.trim_start().len();
+    let end = source.trim_end().len();
+    match dimensions(source, Token::lexer(source).spanned()).as_slice() {
+        [dimension] if dimension.span == (start..end) => Some(dimension.clone()),
+        _ => None,
+    }
+}
+
+/// Recognize a dimension starting at `tokens[start]`, and return it with the index
+/// of the next token.
+fn recognize<'source>(
+    source: &'source str,
+    tokens: &[SpannedToken<'source>],
+    start: usize,
+) -> Option<(Dimension<'source>, usize)> {
+    let token = |i: usize| tokens.get(i).map(|(token, _)| token);
+    // Whether `tokens[i]` exists and directly follows `tokens[i - 1]`
+    let adjacent = |i: usize| {
+        i > 0 && matches!(tokens.get(i), Some((_, span)) if span.start == tokens[i - 1].1.end)
+    };
+
+    // A number directly following a word or another number is not a value
+    if adjacent(start)
+        && matches!(
+            token(start - 1),
+            Some(Token::Word | Token::Number | Token::Dot)
+        )
+    {
+        return None;
+    }
+
+    let is_separator = |i: usize| matches!(token(i), Some(Token::Dot | Token::Comma));
+
+    let mut i = start;
+    if matches!(token(i), Some(Token::Hyphen | Token::PlusSign)) && adjacent(i + 1) {
+        i += 1;
+    }
+    match token(i) {
+        Some(Token::Number) => {
+            i += 1;
+            if is_separator(i) && adjacent(i) {
+                i += 1;
+                if matches!(token(i), Some(Token::Number)) && adjacent(i) {
+                    i += 1;
+                }
+            }
+        }
+        Some(Token::Dot | Token::Comma)
+            if matches!(token(i + 1), Some(Token::Number)) && adjacent(i + 1) =>
+        {
+            i += 2;
+        }
+        _ => return None,
+    }
+    let number = tokens[start].1.start..tokens[i - 1].1.end;
+    let value: f64 = source[number.clone()].replace(',', ".").parse().ok()?;
+
+    if matches!(token(i), Some(Token::TabsOrSpaces)) {
+        i += 1;
+    }
+    let (unit_token, unit_span) = tokens.get(i)?;
+    let (unit, end) = match unit_token {
+        Token::Word => {
+            let mut span = unit_span.clone();
+            // `true` may be followed by spaces, e.g., `1 true pt`
+            if source[span.clone()].eq_ignore_ascii_case("true") {
+                i += 1;
+                if matches!(token(i), Some(Token::TabsOrSpaces)) {
+                    i += 1;
+                }
+                span = match tokens.get(i) {
+                    Some((Token::Word, span)) => span.clone(),
+                    _ => return None,
+                };
+            } else if source
+                .get(span.start..span.start + 4)
+                .map_or(false, |prefix| prefix.eq_ignore_ascii_case("true"))
+            {
+                span.start += 4;
+            }
+            // The keyword ends after its two letters, e.g., `2ptx` is `2pt` followed by `x`
+            let end = span.start + 2;
+            if end > span.end {
+                return None;
+            }
+            (Unit::from_keyword(source.get(span.start..end)?)?, end)
+        }
+        Token::CommandName => {
+            let name = &source[unit_span.start + 1..unit_span.end];
+            if !is_length_macro(name) {
+                return None;
+            }
+            (Unit::Macro(name), unit_span.end)
+        }
+        _ => return None,
+    };
+
+    let dimension = Dimension {
+        value,
+        unit,
+        span: number.start..end,
+    };
+    Some((dimension, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(source: &str) -> Vec<(f64, Unit<'_>, &str)> {
+        dimensions(source, Token::lexer(source).spanned())
+            .into_iter()
+            .map(|dimension| (dimension.value, dimension.unit, &source[dimension.span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_dimensions() {
+        assert_eq!(
+            find(r"1.5pt, -3.2em, 0.8\textwidth, 2\baselineskip, 12 bp, .5in, +3.sp"),
+            vec![
+                (1.5, Unit::Pt, "1.5pt"),
+                (-3.2, Unit::Em, "-3.2em"),
+                (0.8, Unit::Macro("textwidth"), r"0.8\textwidth"),
+                (2.0, Unit::Macro("baselineskip"), r"2\baselineskip"),
+                (12.0, Unit::Bp, "12 bp"),
+                (0.5, Unit::In, ".5in"),
+                (3.0, Unit::Sp, "+3.sp"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords() {
+        assert_eq!(
+            find(r"2ptx, 3PT, 1,5Cm, ,5mm, 1truein, 2 TRUE pt, 4 trueptx"),
+            vec![
+                (2.0, Unit::Pt, "2pt"),
+                (3.0, Unit::Pt, "3PT"),
+                (1.5, Unit::Cm, "1,5Cm"),
+                (0.5, Unit::Mm, ",5mm"),
+                (1.0, Unit::In, "1truein"),
+                (2.0, Unit::Pt, "2 TRUE pt"),
+                (4.0, Unit::Pt, "4 truept"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_not_dimensions() {
+        assert!(find(r"12 points, 3\alpha, page12pt, 1.2.3, 2 true, 3 truex").is_empty());
+    }
+
+    #[test]
+    fn test_convert() {
+        let inch = parse("1in").unwrap();
+        assert_eq!(inch.to_points(), Some(72.27));
+
+        let cm = inch.convert(Unit::Cm).unwrap();
+        assert!((cm.value - 2.54).abs() < 1e-9);
+        assert_eq!(cm.span, 0..3);
+
+        let cc = parse("1cc").unwrap().convert(Unit::Dd).unwrap();
+        assert!((cc.value - 12.0).abs() < 1e-9);
+
+        assert_eq!(parse("65536sp").unwrap().to_points(), Some(1.0));
+        assert!(parse("1em").unwrap().convert(Unit::Pt).is_none());
+        assert!(inch.convert(Unit::Ex).is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(parse("-3mm").unwrap().to_string(), "-3mm");
+        assert_eq!(
+            parse(r"0.5\linewidth").unwrap().to_string(),
+            r"0.5\linewidth"
+        );
+    }
+}
@@ -0,0 +1,507 @@
+//! Text edits applied to LaTeX sources.
+//!
+//! A [`TextEdit`] replaces one range of a source. Transformations, e.g., formatters
+//! (see [`Formatter`](crate::latex::format::Formatter)), produce an [`EditSet`], i.e.,
+//! non-overlapping edits that can be composed, applied to a source, rendered
+//! as a unified diff, or handed to an editor.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::edit::{EditSet, TextEdit};
+//!
+//! let source = "\\section{Intro}\nHello world!\n";
+//! let edits = EditSet::from_edits(vec![
+//!     TextEdit::new(22..27, "TeX"),
+//!     TextEdit::new(9..14, "Introduction"),
+//! ])
+//! .unwrap();
+//!
+//! assert_eq!(edits.apply(source), "\\section{Introduction}\nHello TeX!\n");
+//! assert_eq!(
+//!     edits.unified_diff(source, "main.tex"),
+//!     "--- a/main.tex\n+++ b/main.tex\n@@ -1,2 +1,2 @@\n\
+//!      -\\section{Intro}\n-Hello world!\n+\\section{Introduction}\n+Hello TeX!\n"
+//! );
+//! ```
+
+use crate::error::{Error, Result};
+use crate::latex::lines::LineIndex;
+use crate::latex::token::Span;
+
+/// Replacement of the text within `range` by `new_text`.
+///
+/// An empty range is an insertion, and an empty text is a deletion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Range, in bytes, of the replaced text.
+    pub range: Span,
+    /// Text inserted in place of the range.
+    pub new_text: String,
+}
+
+impl TextEdit {
+    /// Create a new text edit.
+    pub fn new<S>(range: Span, new_text: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            range,
+            new_text: new_text.into(),
+        }
+    }
+
+    /// Return the difference, in bytes, between the length of the source after
+    /// and before the edit.
+    #[must_use]
+    pub fn delta(&self) -> isize {
+        self.new_text.len() as isize - self.range.len() as isize
+    }
+
+    /// Return the range, in the edited source, covered by the new text.
+    #[must_use]
+    pub fn new_range(&self) -> Span {
+        self.range.start..self.range.start + self.new_text.len()
+    }
+
+    /// Return a copy of the source with this edit applied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or does not lie on [`char`] boundaries.
+    #[must_use]
+    pub fn apply(&self, source: &str) -> String {
+        let mut string = source.to_string();
+        string.replace_range(self.range.clone(), &self.new_text);
+        string
+    }
+}
+
+/// Set of non-overlapping [`TextEdit`]s, sorted by range.
+///
+/// Two edits overlap if they replace at least one common byte, or if one is an insertion
+/// strictly inside the range of the other. Insertions at the same position are applied
+/// in the order they were added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditSet {
+    edits: Vec<TextEdit>,
+}
+
+impl EditSet {
+    /// Create a new empty set.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a new set from edits, in any order.
+    pub fn from_edits<I>(edits: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = TextEdit>,
+    {
+        let mut set = Self::new();
+        for edit in edits {
+            set.insert(edit)?;
+        }
+        Ok(set)
+    }
+
+    /// Add an edit, and error if it overlaps with another edit of this set.
+    pub fn insert(&mut self, edit: TextEdit) -> Result<()> {
+        let key = (edit.range.start, edit.range.end);
+        let i = self
+            .edits
+            .partition_point(|other| (other.range.start, other.range.end) <= key);
+        let overlaps = |other: &TextEdit| {
+            other.range.start < edit.range.end && edit.range.start < other.range.end
+        };
+
+        for other in self.edits[i.saturating_sub(1)..].iter().take(2) {
+            if overlaps(other) {
+                return Err(Error::OverlappingEdits(
+                    other.range.clone(),
+                    edit.range.clone(),
+                ));
+            }
+        }
+        self.edits.insert(i, edit);
+        Ok(())
+    }
+
+    /// Return the edits, sorted by range.
+    #[must_use]
+    pub fn edits(&self) -> &[TextEdit] {
+        &self.edits
+    }
+
+    /// Return the number of edits.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+
+    /// Return whether this set has no edit.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Return the difference, in bytes, between the length of the source after
+    /// and before the edits.
+    #[must_use]
+    pub fn delta(&self) -> isize {
+        self.edits.iter().map(TextEdit::delta).sum()
+    }
+
+    /// Return a copy of the source with all edits applied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a range is out of bounds or does not lie on [`char`] boundaries.
+    #[must_use]
+    pub fn apply(&self, source: &str) -> String {
+        let mut string = String::with_capacity((source.len() as isize + self.delta()) as usize);
+        let mut offset = 0;
+
+        for edit in self.edits.iter() {
+            string.push_str(&source[offset..edit.range.start]);
+            string.push_str(&edit.new_text);
+            offset = edit.range.end;
+        }
+        string.push_str(&source[offset..]);
+        string
+    }
+
+    /// Return the edits equivalent to applying this set, and then `other`
+    /// on the resulting source.
+    ///
+    /// Edits of both sets that overlap in the intermediate source are merged.
+    #[must_use]
+    pub fn compose(&self, other: &EditSet) -> EditSet {
+        // Ranges of the new texts in the intermediate source
+        let mut delta = 0;
+        let inner: Vec<Span> = self
+            .edits
+            .iter()
+            .map(|edit| {
+                let start = shift(edit.range.start, delta);
+                delta += edit.delta();
+                start..start + edit.new_text.len()
+            })
+            .collect();
+
+        let mut composed = EditSet::new();
+        let (mut i, mut j) = (0, 0);
+        let mut delta = 0;
+
+        while i < inner.len() || j < other.edits.len() {
+            let start = match (inner.get(i), other.edits.get(j)) {
+                (Some(range), Some(edit)) => range.start.min(edit.range.start),
+                (Some(range), None) => range.start,
+                (None, Some(edit)) => edit.range.start,
+                (None, None) => unreachable!(),
+            };
+            let (first_i, first_j) = (i, j);
+            let mut end = start;
+
+            // A group of edits that overlap or touch in the intermediate source
+            loop {
+                if let Some(range) = inner.get(i).filter(|range| range.start <= end) {
+                    end = end.max(range.end);
+                    i += 1;
+                } else if let Some(edit) = other.edits.get(j).filter(|e| e.range.start <= end) {
+                    end = end.max(edit.range.end);
+                    j += 1;
+                } else {
+                    break;
+                }
+            }
+
+            // Text outside of the edits of `other` comes from the edits of `self`
+            let mut new_text = String::new();
+            let push_inner = |range: Span, new_text: &mut String| {
+                for (k, inner) in inner.iter().enumerate().take(i).skip(first_i) {
+                    let (a, b) = (range.start.max(inner.start), range.end.min(inner.end));
+                    if a < b {
+                        new_text
+                            .push_str(&self.edits[k].new_text[a - inner.start..b - inner.start]);
+                    }
+                }
+            };
+            let mut offset = start;
+            for edit in other.edits[first_j..j].iter() {
+                push_inner(offset..edit.range.start, &mut new_text);
+                new_text.push_str(&edit.new_text);
+                offset = edit.range.end;
+            }
+            push_inner(offset..end, &mut new_text);
+
+            let group_delta: isize = self.edits[first_i..i].iter().map(TextEdit::delta).sum();
+            let range = shift(start, -delta)..shift(end, -(delta + group_delta));
+            delta += group_delta;
+
+            if !(range.is_empty() && new_text.is_empty()) {
+                composed.edits.push(TextEdit::new(range, new_text));
+            }
+        }
+        composed
+    }
+
+    /// Return the unified diff between the source and the edited source,
+    /// with three lines of context, as output by `diff -u`.
+    ///
+    /// Edits that leave the source unchanged are ignored, and an empty string
+    /// is returned if there is no change.
+    #[must_use]
+    pub fn unified_diff(&self, source: &str, path: &str) -> String {
+        const CONTEXT: usize = 3;
+
+        let index = LineIndex::new(source);
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let line_start = |line: usize| {
+            index
+                .line_span(line)
+                .map_or(source.len(), |span| span.start)
+        };
+
+        // Blocks of adjacent lines changed by one or more edits: first line, end line, and edits
+        let mut blocks: Vec<(usize, usize, Vec<&TextEdit>)> = Vec::new();
+        for edit in self
+            .edits
+            .iter()
+            .filter(|edit| source[edit.range.clone()] != edit.new_text)
+        {
+            let first = index.line(edit.range.start);
+            let end = if edit.range.is_empty() {
+                (first + 1).min(lines.len())
+            } else {
+                index.line(edit.range.end - 1) + 1
+            };
+            match blocks.last_mut() {
+                Some(block) if first <= block.1 => {
+                    block.1 = block.1.max(end);
+                    block.2.push(edit);
+                }
+                _ => blocks.push((first, end, vec![edit])),
+            }
+        }
+
+        let mut diff = String::new();
+        if blocks.is_empty() {
+            return diff;
+        }
+        diff.push_str(&format!("--- a/{path}\n+++ b/{path}\n"));
+
+        let mut line_delta: isize = 0;
+        let mut blocks = blocks.into_iter().peekable();
+
+        while let Some(block) = blocks.next() {
+            // Blocks separated by at most twice the context form one hunk
+            let mut hunk = vec![block];
+            while let Some(next) = blocks.peek() {
+                if next.0 > hunk.last().unwrap().1 + 2 * CONTEXT {
+                    break;
+                }
+                hunk.push(blocks.next().unwrap());
+            }
+
+            let old_start = hunk[0].0.saturating_sub(CONTEXT);
+            let old_end = (hunk.last().unwrap().1 + CONTEXT).min(lines.len());
+            let mut body = String::new();
+            let mut new_len = old_end - old_start;
+            let mut line = old_start;
+
+            for (first, end, edits) in hunk {
+                for context in &lines[line..first] {
+                    push_line(&mut body, ' ', context);
+                }
+                for old in &lines[first..end] {
+                    push_line(&mut body, '-', old);
+                }
+
+                let offset = line_start(first);
+                let set = EditSet {
+                    edits: edits
+                        .into_iter()
+                        .map(|edit| {
+                            TextEdit::new(
+                                edit.range.start - offset..edit.range.end - offset,
+                                edit.new_text.as_str(),
+                            )
+                        })
+                        .collect(),
+                };
+                let new_text = set.apply(&source[offset..line_start(end)]);
+                let new_lines: Vec<&str> = new_text.split_inclusive('\n').collect();
+                for new in new_lines.iter() {
+                    push_line(&mut body, '+', new);
+                }
+
+                new_len = new_len + new_lines.len() - (end - first);
+                line = end;
+            }
+            for context in &lines[line..old_end] {
+                push_line(&mut body, ' ', context);
+            }
+
+            let old_len = old_end - old_start;
+            let new_start = shift(old_start, line_delta);
+            diff.push_str(&format!(
+                "@@ -{} +{} @@\n",
+                hunk_range(old_start, old_len),
+                hunk_range(new_start, new_len)
+            ));
+            diff.push_str(&body);
+            line_delta += new_len as isize - old_len as isize;
+        }
+        diff
+    }
+}
+
+impl IntoIterator for EditSet {
+    type Item = TextEdit;
+    type IntoIter = std::vec::IntoIter<TextEdit>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.edits.into_iter()
+    }
+}
+
+/// Return a position shifted by `delta`.
+fn shift(position: usize, delta: isize) -> usize {
+    (position as isize + delta) as usize
+}
+
+/// Append a line to a diff, marking a missing line break at the end of the file.
+fn push_line(diff: &mut String, prefix: char, line: &str) {
+    diff.push(prefix);
+    diff.push_str(line);
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Return the range of a hunk, as `start,len`, with a 1-based start unless the range is empty.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(
+            TextEdit::new(6..11, "TeX").apply("Hello world!"),
+            "Hello TeX!"
+        );
+        assert_eq!(TextEdit::new(0..0, "% ").apply("text"), "% text");
+        assert_eq!(TextEdit::new(0..4, "").apply("text"), "");
+    }
+
+    #[test]
+    fn test_delta() {
+        let edit = TextEdit::new(6..11, "TeX");
+        assert_eq!(edit.delta(), -2);
+        assert_eq!(edit.new_range(), 6..9);
+    }
+
+    #[test]
+    fn test_edit_set() {
+        let mut set = EditSet::new();
+        set.insert(TextEdit::new(4..6, "b")).unwrap();
+        set.insert(TextEdit::new(0..1, "a")).unwrap();
+        set.insert(TextEdit::new(4..4, "c")).unwrap();
+        set.insert(TextEdit::new(6..6, "d")).unwrap();
+        set.insert(TextEdit::new(4..4, "e")).unwrap();
+
+        let ranges: Vec<_> = set.edits().iter().map(|edit| edit.range.clone()).collect();
+        assert_eq!(ranges, vec![0..1, 4..4, 4..4, 4..6, 6..6]);
+        assert_eq!(set.apply("0123456789"), "a123cebd6789");
+        assert_eq!(set.delta(), 2);
+
+        assert!(matches!(
+            set.insert(TextEdit::new(5..7, "")),
+            Err(Error::OverlappingEdits(_, _))
+        ));
+        assert!(set.insert(TextEdit::new(5..5, "")).is_err());
+        assert!(set.insert(TextEdit::new(0..1, "")).is_err());
+        assert_eq!(set.len(), 5);
+    }
+
+    #[test]
+    fn test_compose() {
+        let source = "0123456789";
+        let sets = [
+            vec![TextEdit::new(1..3, "ab"), TextEdit::new(5..5, "xyz")],
+            vec![TextEdit::new(0..2, ""), TextEdit::new(6..9, "-")],
+            vec![TextEdit::new(4..6, "CD"), TextEdit::new(9..10, "")],
+            vec![TextEdit::new(0..0, "<"), TextEdit::new(2..7, "")],
+            vec![TextEdit::new(3..3, "+"), TextEdit::new(6..6, "+")],
+        ];
+        let sets: Vec<_> = sets
+            .into_iter()
+            .map(|edits| EditSet::from_edits(edits).unwrap())
+            .collect();
+
+        for first in sets.iter() {
+            let intermediate = first.apply(source);
+            for second in sets.iter() {
+                if second
+                    .edits()
+                    .iter()
+                    .any(|e| e.range.end > intermediate.len())
+                {
+                    continue;
+                }
+                let expected = second.apply(&intermediate);
+                assert_eq!(
+                    first.compose(second).apply(source),
+                    expected,
+                    "{first:?} then {second:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let source: String = (1..=12).map(|i| format!("line {i}\n")).collect();
+        let set = EditSet::from_edits(vec![
+            TextEdit::new(0..0, "first\n"),
+            TextEdit::new(7..13, "two"),
+            TextEdit::new(source.len() - 8..source.len(), "end"),
+            TextEdit::new(35..41, "line 6"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            set.unified_diff(&source, "a.tex"),
+            "--- a/a.tex
++++ b/a.tex
+@@ -1,5 +1,6 @@
+-line 1
+-line 2
++first
++line 1
++two
+ line 3
+ line 4
+ line 5
+@@ -9,4 +10,4 @@
+ line 9
+ line 10
+ line 11
+-line 12
++end
+\\ No newline at end of file
+"
+        );
+        assert_eq!(EditSet::new().unified_diff(&source, "a.tex"), "");
+    }
+}
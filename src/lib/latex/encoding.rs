@@ -0,0 +1,340 @@
+//! Reading and writing documents in legacy input encodings.
+//!
+//! Older documents are often written in a single-byte encoding, declared with
+//! `\usepackage[latin1]{inputenc}`, and are not valid UTF-8. [`decode`] transcodes
+//! such a document into UTF-8, either with a given [`Encoding`] or with the one
+//! inferred from the `inputenc` options, see [`infer`].
+//!
+//! The resulting [`Decoded`] source keeps a map from its byte offsets back to the
+//! original bytes, and can encode a modified text back into the original encoding.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::encoding::{decode, Encoding};
+//!
+//! let bytes = b"\\usepackage[latin1]{inputenc}\nCaf\xe9 cr\xe8me";
+//! let decoded = decode(bytes, None).unwrap();
+//!
+//! assert_eq!(decoded.encoding(), Encoding::Latin1);
+//! assert!(decoded.as_str().ends_with("Café crème"));
+//!
+//! // 'è' is two bytes long in UTF-8, but only one in Latin-1
+//! let end = decoded.as_str().len();
+//! assert_eq!(decoded.original_offset(end), bytes.len());
+//!
+//! let text = decoded.as_str().replace("crème", "brûlée");
+//! assert!(decoded.encode(&text).unwrap().ends_with(b"br\xfbl\xe9e"));
+//! ```
+
+use crate::error::Error;
+use crate::latex::parse::SyntaxTree;
+use crate::latex::preamble::Preamble;
+use crate::latex::token::Span;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Characters of Windows-1252 for bytes `0x80` to `0x9F`.
+///
+/// Bytes left undefined by Windows-1252 are mapped to the control characters
+/// of the same value, so that decoding never fails and is always reversible.
+static CP1252: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Input encoding of a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// UTF-8, `utf8`.
+    Utf8,
+    /// ISO-8859-1, `latin1`.
+    Latin1,
+    /// ISO-8859-15, `latin9`, i.e., Latin-1 with the euro sign and a few more letters.
+    Latin9,
+    /// Windows-1252, `cp1252` or `ansinew`.
+    Cp1252,
+}
+
+impl Encoding {
+    /// Return the name of this encoding, as an `inputenc` option.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf8",
+            Encoding::Latin1 => "latin1",
+            Encoding::Latin9 => "latin9",
+            Encoding::Cp1252 => "cp1252",
+        }
+    }
+
+    /// Return the character of a byte, for single-byte encodings.
+    fn decode_byte(&self, byte: u8) -> char {
+        match (self, byte) {
+            (Encoding::Latin9, 0xA4) => '\u{20AC}',
+            (Encoding::Latin9, 0xA6) => '\u{0160}',
+            (Encoding::Latin9, 0xA8) => '\u{0161}',
+            (Encoding::Latin9, 0xB4) => '\u{017D}',
+            (Encoding::Latin9, 0xB8) => '\u{017E}',
+            (Encoding::Latin9, 0xBC) => '\u{0152}',
+            (Encoding::Latin9, 0xBD) => '\u{0153}',
+            (Encoding::Latin9, 0xBE) => '\u{0178}',
+            (Encoding::Cp1252, 0x80..=0x9F) => CP1252[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        }
+    }
+
+    /// Return the byte of a character, for single-byte encodings, if any.
+    fn encode_char(&self, c: char) -> Option<u8> {
+        if c.is_ascii() {
+            return Some(c as u8);
+        }
+        (0x80..=0xFF).find(|byte| self.decode_byte(*byte) == c)
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = Error;
+
+    /// Parse an `inputenc` option, or a common name of the encoding, e.g., `iso-8859-1`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Ok(Encoding::Utf8),
+            "latin1" | "iso-8859-1" | "iso8859-1" => Ok(Encoding::Latin1),
+            "latin9" | "iso-8859-15" | "iso8859-15" => Ok(Encoding::Latin9),
+            "cp1252" | "ansinew" | "windows-1252" => Ok(Encoding::Cp1252),
+            _ => Err(Error::InvalidEncoding(s.to_string())),
+        }
+    }
+}
+
+/// A source transcoded into UTF-8, see [`decode`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decoded {
+    encoding: Encoding,
+    text: String,
+    /// Pairs of offsets, in the text and in the original bytes, after each character
+    /// whose length differs between both.
+    offsets: Vec<(usize, usize)>,
+}
+
+impl Decoded {
+    /// Return the original encoding.
+    #[must_use]
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Return the UTF-8 text.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Return the UTF-8 text, consuming it.
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    /// Return the offset in the original bytes of an offset in the text.
+    #[must_use]
+    pub fn original_offset(&self, offset: usize) -> usize {
+        let i = self.offsets.partition_point(|(text, _)| *text <= offset);
+        match i.checked_sub(1) {
+            Some(i) => {
+                let (text, original) = self.offsets[i];
+                original + (offset - text)
+            }
+            None => offset,
+        }
+    }
+
+    /// Return the span in the original bytes of a span in the text.
+    #[must_use]
+    pub fn original_span(&self, span: Span) -> Span {
+        self.original_offset(span.start)..self.original_offset(span.end)
+    }
+
+    /// Encode a text, e.g., a formatted version of this source, into the original encoding.
+    ///
+    /// Characters that the original encoding cannot represent result in an
+    /// [`io::ErrorKind::InvalidData`] error.
+    pub fn encode(&self, text: &str) -> io::Result<Vec<u8>> {
+        encode(text, self.encoding)
+    }
+}
+
+/// Return the encoding declared with `\usepackage[...]{inputenc}`, if any.
+///
+/// Bytes are read as Latin-1, which is enough to parse the preamble of a document
+/// in any supported encoding.
+#[must_use]
+pub fn infer(bytes: &[u8]) -> Option<Encoding> {
+    let text: String = bytes.iter().map(|byte| char::from(*byte)).collect();
+    let tree = SyntaxTree::parse(text.as_str());
+    let preamble = Preamble::from_tree(&tree);
+    let package = preamble.package("inputenc")?;
+
+    package
+        .options
+        .iter()
+        .rev()
+        .find_map(|option| option.key.parse().ok())
+}
+
+/// Return an [`io::ErrorKind::InvalidData`] error locating invalid UTF-8 in bytes
+/// starting at `offset`, without the offending bytes.
+pub(crate) fn invalid_utf8(error: std::string::FromUtf8Error, offset: usize) -> io::Error {
+    let byte = offset + error.utf8_error().valid_up_to();
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid UTF-8 at byte {byte}"),
+    )
+}
+
+/// Transcode bytes into UTF-8.
+///
+/// If no encoding is given, bytes are read as UTF-8 when valid, or with the
+/// encoding inferred from the `inputenc` options, see [`infer`].
+/// Invalid UTF-8 results in an [`io::ErrorKind::InvalidData`] error.
+pub fn decode(bytes: &[u8], encoding: Option<Encoding>) -> io::Result<Decoded> {
+    let encoding = match encoding {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => Encoding::Utf8,
+        None => infer(bytes).unwrap_or(Encoding::Utf8),
+    };
+
+    if encoding == Encoding::Utf8 {
+        let text = String::from_utf8(bytes.to_vec()).map_err(|e| invalid_utf8(e, 0))?;
+        return Ok(Decoded {
+            encoding,
+            text,
+            offsets: Vec::new(),
+        });
+    }
+
+    let mut text = String::with_capacity(bytes.len());
+    let mut offsets = Vec::new();
+
+    for (i, byte) in bytes.iter().enumerate() {
+        let c = encoding.decode_byte(*byte);
+        text.push(c);
+        if c.len_utf8() > 1 {
+            offsets.push((text.len(), i + 1));
+        }
+    }
+    Ok(Decoded {
+        encoding,
+        text,
+        offsets,
+    })
+}
+
+/// Encode a UTF-8 text into bytes.
+///
+/// Characters that the encoding cannot represent result in an
+/// [`io::ErrorKind::InvalidData`] error.
+pub fn encode(text: &str, encoding: Encoding) -> io::Result<Vec<u8>> {
+    if encoding == Encoding::Utf8 {
+        return Ok(text.as_bytes().to_vec());
+    }
+
+    text.chars()
+        .map(|c| {
+            encoding.encode_char(c).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("character '{c}' cannot be encoded in {encoding}"),
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer() {
+        let source = b"\\documentclass{article}\n\\usepackage[T1]{fontenc}\n\\usepackage[ansinew]{inputenc}\n\\begin{document}\n\x80\n\\end{document}";
+        assert_eq!(infer(source), Some(Encoding::Cp1252));
+        assert_eq!(infer(b"\\usepackage[T1]{fontenc}"), None);
+        assert_eq!(infer(b"\\usepackage[unknown]{inputenc}"), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let decoded = decode("déjà".as_bytes(), None).unwrap();
+        assert_eq!(decoded.encoding(), Encoding::Utf8);
+        assert_eq!(decoded.original_offset(3), 3);
+
+        let bytes = b"\xa4 \x80";
+        assert_eq!(
+            decode(bytes, Some(Encoding::Latin1)).unwrap().as_str(),
+            "\u{a4} \u{80}"
+        );
+        assert_eq!(
+            decode(bytes, Some(Encoding::Latin9)).unwrap().as_str(),
+            "€ \u{80}"
+        );
+        assert_eq!(
+            decode(bytes, Some(Encoding::Cp1252)).unwrap().as_str(),
+            "\u{a4} €"
+        );
+
+        let error = decode(b"caf\xe9", None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid UTF-8 at byte 3");
+    }
+
+    #[test]
+    fn test_offsets() {
+        let bytes = b"a\xe9b\x80c";
+        let decoded = decode(bytes, Some(Encoding::Cp1252)).unwrap();
+        assert_eq!(decoded.as_str(), "aéb€c");
+
+        let offsets: Vec<_> = decoded
+            .as_str()
+            .char_indices()
+            .map(|(i, _)| decoded.original_offset(i))
+            .collect();
+        assert_eq!(offsets, vec![0, 1, 2, 3, 4]);
+        assert_eq!(decoded.original_span(3..7), 2..4);
+        assert_eq!(decoded.original_offset(decoded.as_str().len()), bytes.len());
+    }
+
+    #[test]
+    fn test_encode() {
+        let bytes: Vec<u8> = (0..=0xFF).collect();
+        for encoding in [Encoding::Latin1, Encoding::Latin9, Encoding::Cp1252] {
+            let decoded = decode(&bytes, Some(encoding)).unwrap();
+            assert_eq!(decoded.encode(decoded.as_str()).unwrap(), bytes);
+        }
+
+        assert_eq!(encode("€", Encoding::Latin9).unwrap(), b"\xa4");
+        assert!(encode("€", Encoding::Latin1).is_err());
+        assert!(encode("\u{a4}", Encoding::Latin9).is_err());
+        assert_eq!(encode("€", Encoding::Utf8).unwrap(), "€".as_bytes());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("UTF-8".parse::<Encoding>().unwrap(), Encoding::Utf8);
+        assert_eq!("iso-8859-15".parse::<Encoding>().unwrap(), Encoding::Latin9);
+        assert!(matches!(
+            "ebcdic".parse::<Encoding>(),
+            Err(Error::InvalidEncoding(_))
+        ));
+    }
+}
@@ -1,37 +1,54 @@
 //! Pretty formatting LaTeX documen via [`Token`] iterators.
 
-use crate::latex::token::{SpannedToken, Token};
+use crate::error::Result;
+use crate::latex::edit::{EditSet, TextEdit};
+use crate::latex::token::{Span, SpannedToken, Token};
 use std::io;
 use std::iter::Peekable;
 
-/// Trait for formatting tokens.
+/// Trait for formatting sources.
 ///
-/// Formatting tokens consist of generating an iterator of such tokens and,
-/// depending on the desired result, for each token:
-/// - removing it;
-/// - mutating it into another token;
-/// - or merging it into one or more tokens.
+/// Formatting a source consists of generating an iterator of [`TextEdit`]s,
+/// sorted by range, that:
+/// - remove text;
+/// - replace text by other text;
+/// - or insert new text.
 ///
 /// This trait is automatically implemented on iterators that emit
-/// `SpannedToken` items.
-#[allow(clippy::type_complexity)]
-pub trait Formatter<'source>: Iterator<Item = SpannedToken<'source>> {
-    /// Writes tokens to buffer.
-    fn write_formatted<W>(&mut self, source: &'source str, buffer: &mut W) -> io::Result<()>
+/// `TextEdit` items.
+pub trait Formatter: Iterator<Item = TextEdit> {
+    /// Return the edits as an [`EditSet`], ignoring those that leave the source unchanged.
+    fn edit_set(self, source: &str) -> Result<EditSet>
+    where
+        Self: Sized,
+    {
+        EditSet::from_edits(self.filter(|edit| source[edit.range.clone()] != edit.new_text))
+    }
+
+    /// Writes formatted source to buffer.
+    fn write_formatted<W>(&mut self, source: &str, buffer: &mut W) -> io::Result<()>
     where
         W: io::Write,
     {
-        for (token, span) in self {
-            match token {
-                Token::OwnedString(string) => buffer.write_all(string.as_bytes())?,
-                _ => buffer.write_all(source[span].as_bytes())?,
-            }
+        let mut offset = 0;
+        for edit in self {
+            buffer.write_all(&source.as_bytes()[offset..edit.range.start])?;
+            buffer.write_all(edit.new_text.as_bytes())?;
+            offset = edit.range.end;
         }
-        Ok(())
+        buffer.write_all(&source.as_bytes()[offset..])
     }
 }
 
-impl<'source, I> Formatter<'source> for I where I: Iterator<Item = SpannedToken<'source>> {}
+impl<I> Formatter for I where I: Iterator<Item = TextEdit> {}
+
+/// Step of an [`AutoIndentFormatter`].
+enum Step {
+    /// Indent the current line.
+    Indent(String),
+    /// Keep a token.
+    Token(Span),
+}
 
 /// Iterator to auto indent a document
 ///
@@ -49,7 +66,12 @@ where
     inside_document: bool,
     target_indentation_level: u8,
     is_indented: bool,
+    after_verbatim: bool,
     indent_chars: String,
+    /// Span of the indentation removed from the current line, if any.
+    removed: Option<Span>,
+    /// End of the last token.
+    offset: usize,
 }
 
 impl<'source, I> AutoIndentFormatter<'source, I>
@@ -63,18 +85,35 @@ where
             inside_document: false,
             target_indentation_level: 0,
             is_indented: false,
+            after_verbatim: false,
             indent_chars: "  ".to_string(),
+            removed: None,
+            offset: 0,
         }
     }
-}
 
-impl<'source, I> Iterator for AutoIndentFormatter<'source, I>
-where
-    I: Iterator<Item = SpannedToken<'source>>,
-{
-    type Item = SpannedToken<'source>;
+    /// Return a formatter over other tokens, starting in the current state.
+    ///
+    /// This allows to format a source chunk by chunk, as long as each chunk ends
+    /// right before a line break, see [`latex::stream`](crate::latex::stream).
+    pub fn resume<'other, J>(self, iter: J) -> AutoIndentFormatter<'other, J>
+    where
+        J: Iterator<Item = SpannedToken<'other>>,
+    {
+        AutoIndentFormatter {
+            iter: iter.peekable(),
+            inside_document: self.inside_document,
+            target_indentation_level: self.target_indentation_level,
+            is_indented: self.is_indented,
+            after_verbatim: self.after_verbatim,
+            indent_chars: self.indent_chars,
+            removed: None,
+            offset: 0,
+        }
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Move to the next step.
+    fn step(&mut self) -> Option<Step> {
         // Auto Indent Formatter
 
         // Pre indent matching
@@ -82,11 +121,13 @@ where
             Some(&(Token::EnvironmentBegin("document"), _)) => {
                 self.inside_document = true;
             }
-            Some(&(Token::EnvironmentEnd(_), _)) => {
-                // To count an end environment only once
-                if !self.is_indented && self.inside_document {
-                    self.target_indentation_level -= 1;
-                }
+            // To count an end environment only once
+            // Stray `\end{...}` must not underflow the indentation level
+            // The `\end{...}` of a verbatim environment directly follows its content
+            Some(&(Token::EnvironmentEnd(_), _))
+                if (!self.is_indented || self.after_verbatim) && self.inside_document =>
+            {
+                self.target_indentation_level = self.target_indentation_level.saturating_sub(1);
             }
             _ => {}
         };
@@ -94,8 +135,13 @@ where
         if !self.is_indented {
             // Remove current indent
             if let Some(&(Token::TabsOrSpaces, _)) = self.iter.peek() {
-                self.iter.next();
-                return self.next();
+                let (_, span) = self.iter.next().unwrap();
+                self.offset = span.end;
+                self.removed = Some(match self.removed.take() {
+                    Some(removed) => removed.start..span.end,
+                    None => span,
+                });
+                return self.step();
             }
 
             self.is_indented = true;
@@ -103,25 +149,43 @@ where
             for _ in 0..self.target_indentation_level {
                 indentation_value.push_str(&self.indent_chars);
             }
-
-            // Cannot use .. to define the range because it is a RangeFull a we need a Range
-            let custom_indentation: SpannedToken<'source> =
-                (Token::OwnedString(indentation_value), 0..1);
-            Some(custom_indentation)
+            Some(Step::Indent(indentation_value))
         } else {
             // Post indent matching
+            self.after_verbatim = matches!(self.iter.peek(), Some(&(Token::Verbatim, _)));
             match self.iter.peek() {
-                Some(&(Token::EnvironmentBegin(_), _)) => {
-                    if self.inside_document {
-                        self.target_indentation_level += 1;
-                    }
+                Some(&(Token::EnvironmentBegin(_), _)) if self.inside_document => {
+                    self.target_indentation_level = self.target_indentation_level.saturating_add(1);
                 }
                 Some(&(Token::Newline, _)) => {
                     self.is_indented = false;
                 }
                 _ => {}
             };
-            self.iter.next()
+            self.iter.next().map(|(_, span)| Step::Token(span))
+        }
+    }
+}
+
+impl<'source, I> Iterator for AutoIndentFormatter<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    type Item = TextEdit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.step()? {
+                Step::Token(span) => self.offset = span.end,
+                Step::Indent(indentation) => {
+                    let start = self.iter.peek().map_or(self.offset, |(_, span)| span.start);
+                    let range = self.removed.take().unwrap_or(start..start);
+
+                    if !(range.is_empty() && indentation.is_empty()) {
+                        return Some(TextEdit::new(range, indentation));
+                    }
+                }
+            }
         }
     }
 }
@@ -129,6 +193,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::latex::stream::StreamLexer;
+    use crate::latex::verbatim::{VerbatimConfig, VerbatimLexer};
     use logos::Logos;
     use std::io::BufWriter;
     use String;
@@ -176,4 +242,107 @@ It should go back to an indentation level of one
 
         assert_eq!(string.unwrap(), result)
     }
+
+    #[test]
+    fn test_stray_end_auto_indent() {
+        let source = "\\begin{document}\n\\end{itemize}\n\\end{document}\n\\end{document}\n";
+        let iter = Token::lexer(source).spanned();
+        let mut buf = BufWriter::new(Vec::new());
+
+        AutoIndentFormatter::new(iter)
+            .write_formatted(source, &mut buf)
+            .unwrap();
+        let bytes = buf.into_inner();
+        let string = String::from_utf8(bytes.unwrap());
+
+        assert_eq!(string.unwrap(), source)
+    }
+
+    #[test]
+    fn test_deeply_nested_auto_indent() {
+        let source = format!("\\begin{{document}}\n{}", "\\begin{a}\n".repeat(300));
+        let iter = Token::lexer(&source).spanned();
+        let mut buf = BufWriter::new(Vec::new());
+
+        AutoIndentFormatter::new(iter)
+            .write_formatted(&source, &mut buf)
+            .unwrap();
+        let bytes = buf.into_inner();
+        let string = String::from_utf8(bytes.unwrap());
+
+        let string = string.unwrap();
+        let last = string.lines().rfind(|line| !line.trim().is_empty());
+        assert_eq!(
+            last,
+            Some(format!("{}\\begin{{a}}", "  ".repeat(255)).as_str())
+        );
+    }
+
+    #[test]
+    fn test_verbatim_auto_indent() {
+        let source = "\\begin{document}\n\\begin{verbatim}\n\\begin{itemize}\n  50% $\n\\end{verbatim}\nText\n\\end{document}\n";
+        let result = "\\begin{document}\n  \\begin{verbatim}\n\\begin{itemize}\n  50% $\n\\end{verbatim}\n  Text\n\\end{document}\n";
+        let config = VerbatimConfig::default();
+        let iter = VerbatimLexer::new(source, &config);
+        let mut buf = BufWriter::new(Vec::new());
+
+        AutoIndentFormatter::new(iter)
+            .write_formatted(source, &mut buf)
+            .unwrap();
+        let bytes = buf.into_inner();
+        let string = String::from_utf8(bytes.unwrap());
+
+        assert_eq!(string.unwrap(), result)
+    }
+
+    #[test]
+    fn test_auto_indent_edits() {
+        let source = "\\begin{document}\n\\begin{center}\n a\n  \\end{center}\n\\end{document}";
+        let edits = AutoIndentFormatter::new(Token::lexer(source).spanned())
+            .edit_set(source)
+            .unwrap();
+
+        assert_eq!(
+            edits.edits(),
+            &[TextEdit::new(17..17, "  "), TextEdit::new(32..33, "    ")]
+        );
+        assert_eq!(
+            edits.unified_diff(source, "main.tex"),
+            "--- a/main.tex\n+++ b/main.tex\n@@ -1,5 +1,5 @@\n \\begin{document}\n-\\begin{center}\n- a\n+  \\begin{center}\n+    a\n   \\end{center}\n \\end{document}\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_resume_auto_indent() {
+        let source = "\\begin{document}\n\\begin{verbatim}\n x\n\\end{verbatim}\n\n\\begin{itemize}\n\\item a\n\\end{itemize}\n\\end{document}\n";
+        let config = VerbatimConfig::default();
+        let mut expected = Vec::new();
+        AutoIndentFormatter::new(VerbatimLexer::new(source, &config))
+            .write_formatted(source, &mut expected)
+            .unwrap();
+
+        let mut formatter = AutoIndentFormatter::new(std::iter::empty());
+        let mut buf = Vec::new();
+        // Reading one byte at a time, so that chunks end at almost every line break
+        let reader = source.as_bytes().chunks(1).fold(
+            Box::new(io::empty()) as Box<dyn io::Read>,
+            |reader, byte| Box::new(io::Read::chain(reader, byte)),
+        );
+        for chunk in StreamLexer::new(reader)
+            .with_capacity(20)
+            .with_verbatim(config.clone())
+        {
+            let chunk = chunk.unwrap();
+            let mut chunk_formatter = formatter.resume(VerbatimLexer::new(chunk.as_str(), &config));
+            chunk_formatter
+                .write_formatted(chunk.as_str(), &mut buf)
+                .unwrap();
+            formatter = chunk_formatter.resume(std::iter::empty());
+        }
+
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            String::from_utf8(expected).unwrap()
+        );
+    }
 }
@@ -1,8 +1,10 @@
 //! Highlighting parts of LaTeX documents via [`Token`] iterators.
 use crate::error::Result;
+use crate::latex::parse::{Argument, SyntaxTree};
 #[cfg(feature = "strum")]
 use crate::latex::token::TokenDiscriminants;
 use crate::latex::token::{Span, SpannedToken, Token};
+use crate::latex::visit::Visit;
 use std::iter::FilterMap;
 #[cfg(feature = "color")]
 use termcolor::{ColorSpec, WriteColor};
@@ -72,6 +74,20 @@ pub trait Highlighter<'source>: Iterator<Item = (bool, SpannedToken<'source>)> {
 
 impl<'source, I> Highlighter<'source> for I where I: Iterator<Item = (bool, SpannedToken<'source>)> {}
 
+/// Environments highlighted as math.
+static MATH_ENVIRONMENTS: &[&str] = &["equation", "equation*", "align", "align*"];
+
+/// Return the token closing a math environment, if `token` begins one.
+fn math_environment_end(token: &Token<'_>) -> Option<Token<'static>> {
+    match token {
+        Token::EnvironmentBegin(name) => MATH_ENVIRONMENTS
+            .iter()
+            .find(|environment| *environment == name)
+            .map(|environment| Token::EnvironmentEnd(environment)),
+        _ => None,
+    }
+}
+
 /// Highlights a specific token through its (discriminant) name.
 #[cfg(feature = "strum")]
 #[derive(Debug)]
@@ -92,6 +108,11 @@ where
     pub fn new(iter: I, token: TokenDiscriminants) -> Self {
         Self { iter, token }
     }
+
+    /// Return whether a token should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        TokenDiscriminants::from(token) == self.token
+    }
 }
 
 #[cfg(feature = "strum")]
@@ -102,13 +123,8 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) if TokenDiscriminants::from(&token) == self.token => {
-                Some((true, (token, span)))
-            }
-            Some(spanned_token) => Some((false, spanned_token)),
-            None => None,
-        }
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
     }
 }
 
@@ -120,7 +136,7 @@ where
 {
     iter: I,
     in_math_mode: bool,
-    closing_token: Option<Token<'source>>,
+    closing_token: Option<Token<'static>>,
 }
 
 impl<'source, I> MathHighlighter<'source, I>
@@ -135,6 +151,27 @@ where
             closing_token: None,
         }
     }
+
+    /// Update the state with the next token, and return whether it should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        if self.in_math_mode {
+            if self.closing_token.as_ref() == Some(token) {
+                self.in_math_mode = false;
+                self.closing_token = None;
+            }
+            true
+        } else {
+            self.closing_token = match token {
+                Token::DisplayMathOpen => Some(Token::DisplayMathClose),
+                Token::DollarSign => Some(Token::DollarSign),
+                Token::DoubleDollarSign => Some(Token::DoubleDollarSign),
+                Token::InlineMathOpen => Some(Token::InlineMathClose),
+                _ => math_environment_end(token),
+            };
+            self.in_math_mode = self.closing_token.is_some();
+            self.in_math_mode
+        }
+    }
 }
 
 impl<'source, I> Iterator for MathHighlighter<'source, I>
@@ -144,38 +181,8 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) => {
-                if self.in_math_mode {
-                    if token == self.closing_token.as_ref().cloned().unwrap() {
-                        self.in_math_mode = false;
-                        self.closing_token = None;
-                    }
-                    Some((true, (token, span)))
-                } else {
-                    self.in_math_mode = true;
-                    match token {
-                        Token::DisplayMathOpen => {
-                            self.closing_token = Some(Token::DisplayMathClose)
-                        }
-                        Token::DollarSign => self.closing_token = Some(Token::DollarSign),
-                        Token::DoubleDollarSign => {
-                            self.closing_token = Some(Token::DoubleDollarSign)
-                        }
-                        Token::EnvironmentBegin(name)
-                            if matches!(name, "equation" | "equation*" | "align" | "align*") =>
-                        {
-                            self.closing_token = Some(Token::EnvironmentEnd(name))
-                        }
-                        Token::InlineMathOpen => self.closing_token = Some(Token::InlineMathClose),
-                        _ => self.in_math_mode = false,
-                    }
-
-                    Some((self.in_math_mode, (token, span)))
-                }
-            }
-            None => None,
-        }
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
     }
 }
 
@@ -200,6 +207,16 @@ where
             in_preamble: false,
         }
     }
+
+    /// Update the state with the next token, and return whether it should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        match token {
+            Token::DocumentClass => self.in_preamble = true,
+            Token::EnvironmentBegin("document") => self.in_preamble = false,
+            _ => (),
+        }
+        self.in_preamble
+    }
 }
 
 impl<'source, I> Iterator for PreambleHighlighter<'source, I>
@@ -209,17 +226,8 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) => {
-                match token {
-                    Token::DocumentClass => self.in_preamble = true,
-                    Token::EnvironmentBegin("document") => self.in_preamble = false,
-                    _ => (),
-                }
-                Some((self.in_preamble, (token, span)))
-            }
-            None => None,
-        }
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
     }
 }
 
@@ -244,6 +252,19 @@ where
             in_document: false,
         }
     }
+
+    /// Update the state with the next token, and return whether it should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        match token {
+            Token::EnvironmentBegin("document") => self.in_document = true,
+            Token::EnvironmentEnd("document") => {
+                self.in_document = false;
+                return true;
+            }
+            _ => (),
+        }
+        self.in_document
+    }
 }
 
 impl<'source, I> Iterator for DocumentHighlighter<'source, I>
@@ -253,20 +274,8 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) => {
-                match token {
-                    Token::EnvironmentBegin("document") => self.in_document = true,
-                    Token::EnvironmentEnd("document") => {
-                        self.in_document = false;
-                        return Some((true, (token, span)));
-                    }
-                    _ => (),
-                }
-                Some((self.in_document, (token, span)))
-            }
-            None => None,
-        }
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
     }
 }
 
@@ -278,7 +287,7 @@ where
 {
     iter: I,
     in_math_mode: bool,
-    closing_token: Option<Token<'source>>,
+    closing_token: Option<Token<'static>>,
 }
 
 impl<'source, I> DisplayMathHighlighter<'source, I>
@@ -293,6 +302,25 @@ where
             closing_token: None,
         }
     }
+
+    /// Update the state with the next token, and return whether it should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        if self.in_math_mode {
+            if self.closing_token.as_ref() == Some(token) {
+                self.in_math_mode = false;
+                self.closing_token = None;
+            }
+            true
+        } else {
+            self.closing_token = match token {
+                Token::DisplayMathOpen => Some(Token::DisplayMathClose),
+                Token::DoubleDollarSign => Some(Token::DoubleDollarSign),
+                _ => math_environment_end(token),
+            };
+            self.in_math_mode = self.closing_token.is_some();
+            self.in_math_mode
+        }
+    }
 }
 
 impl<'source, I> Iterator for DisplayMathHighlighter<'source, I>
@@ -302,36 +330,8 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) => {
-                if self.in_math_mode {
-                    if token == self.closing_token.as_ref().cloned().unwrap() {
-                        self.in_math_mode = false;
-                        self.closing_token = None;
-                    }
-                    Some((true, (token, span)))
-                } else {
-                    self.in_math_mode = true;
-                    match token {
-                        Token::DisplayMathOpen => {
-                            self.closing_token = Some(Token::DisplayMathClose)
-                        }
-                        Token::DoubleDollarSign => {
-                            self.closing_token = Some(Token::DoubleDollarSign)
-                        }
-                        Token::EnvironmentBegin(name)
-                            if matches!(name, "equation" | "equation*" | "align" | "align*") =>
-                        {
-                            self.closing_token = Some(Token::EnvironmentEnd(name))
-                        }
-                        _ => self.in_math_mode = false,
-                    }
-
-                    Some((self.in_math_mode, (token, span)))
-                }
-            }
-            None => None,
-        }
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
     }
 }
 
@@ -343,7 +343,7 @@ where
 {
     iter: I,
     in_math_mode: bool,
-    closing_token: Option<Token<'source>>,
+    closing_token: Option<Token<'static>>,
 }
 
 impl<'source, I> InlineMathHighlighter<'source, I>
@@ -358,6 +358,25 @@ where
             closing_token: None,
         }
     }
+
+    /// Update the state with the next token, and return whether it should be highlighted.
+    pub fn highlight(&mut self, token: &Token<'_>) -> bool {
+        if self.in_math_mode {
+            if self.closing_token.as_ref() == Some(token) {
+                self.in_math_mode = false;
+                self.closing_token = None;
+            }
+            true
+        } else {
+            self.closing_token = match token {
+                Token::DollarSign => Some(Token::DollarSign),
+                Token::InlineMathOpen => Some(Token::InlineMathClose),
+                _ => None,
+            };
+            self.in_math_mode = self.closing_token.is_some();
+            self.in_math_mode
+        }
+    }
 }
 
 impl<'source, I> Iterator for InlineMathHighlighter<'source, I>
@@ -367,26 +386,97 @@ where
     type Item = (bool, SpannedToken<'source>);
 
     fn next(&mut self) -> Option<Self::Item> {
-        match self.iter.next() {
-            Some((token, span)) => {
-                if self.in_math_mode {
-                    if token == self.closing_token.as_ref().cloned().unwrap() {
-                        self.in_math_mode = false;
-                        self.closing_token = None;
-                    }
-                    Some((true, (token, span)))
-                } else {
-                    self.in_math_mode = true;
-                    match token {
-                        Token::DollarSign => self.closing_token = Some(Token::DollarSign),
-                        Token::InlineMathOpen => self.closing_token = Some(Token::InlineMathClose),
-                        _ => self.in_math_mode = false,
-                    }
-
-                    Some((self.in_math_mode, (token, span)))
-                }
-            }
-            None => None,
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&token), (token, span)))
+    }
+}
+
+/// Highlights the arguments of commands and environments.
+///
+/// Arguments are grouped as in a [`SyntaxTree`], i.e., following the signatures it
+/// was parsed with, including those of user-defined macros
+/// (see [`SyntaxTree::parse_with`] and [`latex::macros`](crate::latex::macros)).
+#[derive(Debug)]
+pub struct ArgumentHighlighter<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    iter: I,
+    /// Spans of the outermost arguments, in source order.
+    spans: Vec<Span>,
+    index: usize,
+}
+
+impl<'source, I> ArgumentHighlighter<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    /// Create a new argument highlighter, with the arguments of a tree
+    /// parsed from the same source.
+    pub fn new(iter: I, tree: &SyntaxTree<'_>) -> Self {
+        let mut spans = ArgumentSpans(Vec::new());
+        tree.visit(&mut spans);
+        Self {
+            iter,
+            spans: spans.0,
+            index: 0,
         }
     }
+
+    /// Update the state with the span of the next token, and return whether
+    /// it should be highlighted.
+    pub fn highlight(&mut self, span: &Span) -> bool {
+        while matches!(self.spans.get(self.index), Some(arg) if arg.end <= span.start) {
+            self.index += 1;
+        }
+        matches!(self.spans.get(self.index), Some(arg) if arg.start <= span.start && span.end <= arg.end)
+    }
+}
+
+impl<'source, I> Iterator for ArgumentHighlighter<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    type Item = (bool, SpannedToken<'source>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (token, span) = self.iter.next()?;
+        Some((self.highlight(&span), (token, span)))
+    }
+}
+
+/// Collect the spans of the outermost arguments.
+struct ArgumentSpans(Vec<Span>);
+
+impl<'source> Visit<'source> for ArgumentSpans {
+    fn visit_argument(&mut self, argument: &Argument<'source>) {
+        self.0.push(argument.span());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::macros::harvest;
+    use crate::latex::signature::Registry;
+    use logos::Logos;
+
+    #[test]
+    fn test_argument_highlighter() {
+        let source = r"\newcommand{\one}[1]{#1} \one{a}{b} \emph {c}";
+        let highlighted = |registry: &Registry| -> String {
+            let tree = SyntaxTree::parse_with(source, registry);
+            ArgumentHighlighter::new(Token::lexer(source).spanned(), &tree)
+                .highlight_spans()
+                .map(|span| &source[span])
+                .collect()
+        };
+        let mut registry = Registry::default();
+
+        // Unknown commands greedily take every following group
+        assert_eq!(highlighted(&registry), r"{\one}[1]{#1}{a}{b}{c}");
+
+        registry.extend(&harvest(source, Token::lexer(source).spanned()));
+        assert_eq!(highlighted(&registry), r"{\one}[1]{#1}{a}{c}");
+    }
 }
@@ -0,0 +1,442 @@
+//! Incremental re-lexing and re-parsing of [`Token`] streams after text edits.
+//!
+//! [`Token::lexer`] is context-free: starting from any token boundary, it produces
+//! the same tokens as when lexing the whole source. Thus, after a [`TextEdit`],
+//! only the tokens between the start of the edited line and the first token
+//! boundary shared by the old and new sources, after the edit, have to be re-lexed,
+//! see [`relex`]. Tokens are updated in place, and only those that actually changed
+//! are replaced.
+//!
+//! Tokens are, however, expected to be lexed as with [`lex`], i.e., with [`CatcodeLexer`]
+//! and [`VerbatimLexer`], whose output depends on what precedes: `\makeatletter`,
+//! `\ExplSyntaxOn` and verbatim regions, including environments defined with
+//! `\lstnewenvironment`. When the edited region overlaps such a construct, the whole
+//! source is lexed again with [`lex`], and only the tokens that differ are replaced.
+//!
+//! [`CatcodeLexer`]: crate::latex::catcode::CatcodeLexer
+//! [`VerbatimLexer`]: crate::latex::verbatim::VerbatimLexer
+//!
+//! A syntax tree parsed from those tokens can then be updated with
+//! [`SyntaxTree::reparse_with`](crate::latex::parse::SyntaxTree::reparse_with),
+//! which only parses again the content of the innermost node enclosing the changed tokens.
+//!
+//! As tokens and nodes outside of the edited region are kept, they may still borrow
+//! from the source before the edit: both sources must live as long as the tokens.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::edit::TextEdit;
+//! use untex::latex::incremental::relex;
+//! use untex::latex::parse::{lex, SyntaxTree};
+//! use untex::latex::signature::Registry;
+//!
+//! let old_source = "\\begin{document}\n\\emph{first} line\nsecond line\n\\end{document}\n";
+//! let registry = Registry::default();
+//! let (mut tokens, _) = lex(old_source);
+//! let mut tree = SyntaxTree::from_tokens_with(old_source, tokens.clone(), &registry);
+//!
+//! let edit = TextEdit::new(23..28, "other");
+//! let new_source = edit.apply(old_source);
+//! let relexed = relex(&mut tokens, &edit, &new_source);
+//!
+//! assert_eq!(tokens, lex(&new_source).0);
+//! assert_eq!(relexed.changed, 4..5);
+//! assert_eq!(relexed.replaced, 4..5);
+//!
+//! // Only the argument of `\emph` is parsed again
+//! let span = tree.reparse_with(&new_source, &tokens, &relexed, &registry);
+//! assert_eq!(&new_source[span], "other");
+//! assert_eq!(tree.to_string(), new_source);
+//! ```
+
+use crate::latex::edit::TextEdit;
+use crate::latex::parse::lex;
+use crate::latex::token::{Span, SpannedToken, Token};
+use crate::latex::verbatim::VerbatimConfig;
+use logos::Logos;
+use std::ops::Range;
+
+/// Commands after which [`Token::lexer`] and [`lex`] may disagree, without the backslash.
+static CONTEXT_COMMANDS: &[&str] = &[
+    "makeatletter",
+    "makeatother",
+    "ExplSyntaxOn",
+    "ExplSyntaxOff",
+    "lstnewenvironment",
+];
+
+/// Ranges of tokens changed by [`relex`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Relexed {
+    /// Range of indices, in the updated tokens, of the re-lexed tokens.
+    pub changed: Range<usize>,
+    /// Range of indices, in the old tokens, of the tokens that were replaced
+    /// by the re-lexed ones.
+    pub replaced: Range<usize>,
+    /// Span, in the edited source, covered by the re-lexed tokens.
+    pub changed_span: Span,
+    /// Span, in the source before the edit, covered by the replaced tokens.
+    pub replaced_span: Span,
+}
+
+impl Relexed {
+    /// Return the difference, in bytes, between the length of the source after
+    /// and before the edit.
+    #[must_use]
+    pub fn delta(&self) -> isize {
+        self.changed_span.end as isize - self.replaced_span.end as isize
+    }
+}
+
+/// Re-lex a token stream, in place, after an edit.
+///
+/// `tokens` must be the tokens of the source before the edit, as returned by [`lex`],
+/// and `source` the source after the edit (see [`TextEdit::apply`]). Only the tokens
+/// that changed are replaced, and the spans of the tokens after them are shifted.
+///
+/// If the edit may change how the surrounding tokens are lexed, see the
+/// [module documentation](self), the whole source is lexed again.
+pub fn relex<'source>(
+    tokens: &mut Vec<SpannedToken<'source>>,
+    edit: &TextEdit,
+    source: &'source str,
+) -> Relexed {
+    let delta = edit.delta();
+    let shift = |pos: usize| (pos as isize + delta) as usize;
+
+    // Restart from the beginning of the line containing the edit.
+    let mut restart = tokens
+        .iter()
+        .rposition(|(token, span)| token == &Token::Newline && span.end <= edit.range.start)
+        .map_or(0, |i| i + 1);
+    let mut start = restart.checked_sub(1).map_or(0, |i| tokens[i].1.end);
+    let new_end = edit.new_range().end;
+
+    let mut relexed: Vec<SpannedToken<'source>> = Vec::new();
+    let mut resync = tokens.len();
+
+    for (token, span) in Token::lexer(&source[start..]).spanned() {
+        let span = span.start + start..span.end + start;
+        let end = span.end;
+        relexed.push((token, span));
+
+        // Stop as soon as a token boundary, after the edit, is shared with the old tokens.
+        if end >= new_end {
+            let old_end = (end as isize - delta) as usize;
+            if let Ok(i) = tokens[restart..].binary_search_by_key(&old_end, |(_, span)| span.start)
+            {
+                resync = restart + i;
+                break;
+            }
+        }
+    }
+
+    if depends_on_context(tokens, restart..resync, &relexed, edit, source) {
+        restart = 0;
+        start = 0;
+        resync = tokens.len();
+        relexed = lex(source).0;
+    }
+
+    // Keep the re-lexed tokens that are identical to the old ones, at both ends,
+    // i.e., with the same kind and text.
+    let old = &tokens[restart..resync];
+    let prefix = relexed
+        .iter()
+        .zip(old.iter())
+        .take_while(|(new, old)| new == old && old.1.end <= edit.range.start)
+        .count();
+    let suffix = relexed[prefix..]
+        .iter()
+        .rev()
+        .zip(old[prefix..].iter().rev())
+        .take_while(|((new, new_span), (old, old_span))| {
+            new == old
+                && new_span.start == shift(old_span.start)
+                && old_span.start >= edit.range.end
+        })
+        .count();
+
+    let changed_start = match prefix {
+        0 => start,
+        _ => relexed[prefix - 1].1.end,
+    };
+    let changed_end = match suffix {
+        0 => relexed.last().map_or(start, |(_, span)| span.end),
+        _ => relexed[relexed.len() - suffix].1.start,
+    };
+    let changed = restart + prefix..restart + relexed.len() - suffix;
+    let replaced = restart + prefix..resync - suffix;
+
+    relexed.truncate(relexed.len() - suffix);
+    tokens.splice(replaced.clone(), relexed.drain(prefix..));
+
+    for (_, span) in tokens[changed.end..].iter_mut() {
+        *span = shift(span.start)..shift(span.end);
+    }
+
+    Relexed {
+        changed,
+        replaced,
+        changed_span: changed_start..changed_end,
+        replaced_span: changed_start..(changed_end as isize - delta) as usize,
+    }
+}
+
+/// Return whether re-lexing the tokens in `replaced` as `relexed`, with [`Token::lexer`],
+/// may differ from lexing the edited source with [`lex`].
+///
+/// This is the case if `\makeatletter` or `\ExplSyntaxOn` is in effect at the start
+/// of the edited region, if the old or new tokens contain a command or an environment
+/// changing how the following text is lexed, or if the region touches a verbatim token.
+fn depends_on_context(
+    tokens: &[SpannedToken<'_>],
+    replaced: Range<usize>,
+    relexed: &[SpannedToken<'_>],
+    edit: &TextEdit,
+    source: &str,
+) -> bool {
+    // Name of a command, either before or after the edit, without the backslash;
+    // commands overlapping the edit are unknown.
+    let old_name = |span: &Span| {
+        if span.end <= edit.range.start {
+            Some(&source[span.start + 1..span.end])
+        } else if span.start >= edit.range.end {
+            let delta = edit.delta();
+            let shift = |pos: usize| (pos as isize + delta) as usize;
+            Some(&source[shift(span.start) + 1..shift(span.end)])
+        } else {
+            None
+        }
+    };
+    let config = VerbatimConfig::default();
+    let is_context_command = |name: &str| {
+        let name = name.trim_end();
+        CONTEXT_COMMANDS.contains(&name) || config.is_command(name)
+    };
+
+    let mut catcodes = false;
+    for (token, span) in &tokens[..replaced.start] {
+        if *token == Token::CommandName {
+            match old_name(span).map(str::trim_end) {
+                Some("makeatletter" | "ExplSyntaxOn") => catcodes = true,
+                Some("makeatother" | "ExplSyntaxOff") => catcodes = false,
+                _ => (),
+            }
+        }
+    }
+    if catcodes || matches!(tokens.get(replaced.end), Some((Token::Verbatim, _))) {
+        return true;
+    }
+
+    let mut environments = false;
+    let old = tokens[replaced.clone()]
+        .iter()
+        .map(|(token, span)| (token, old_name(span)));
+    let new = relexed
+        .iter()
+        .map(|(token, span)| (token, Some(&source[span.start + 1..span.end])));
+
+    for (token, name) in old.chain(new) {
+        match token {
+            Token::Verbatim => return true,
+            Token::CommandName if name.map_or(true, is_context_command) => return true,
+            Token::EnvironmentBegin(name) | Token::EnvironmentEnd(name) => {
+                if config.environment(name).is_some() {
+                    return true;
+                }
+                environments = true;
+            }
+            _ => (),
+        }
+    }
+
+    // Any environment may be verbatim, if the source defines some.
+    environments
+        && tokens[..replaced.start]
+            .iter()
+            .chain(&tokens[replaced.end..])
+            .any(|(token, span)| {
+                *token == Token::CommandName
+                    && old_name(span).map(str::trim_end) == Some("lstnewenvironment")
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::parse::SyntaxTree;
+    use crate::latex::signature::Registry;
+
+    fn assert_relex(old_source: &str, edit: TextEdit) -> Relexed {
+        let (mut tokens, _) = lex(old_source);
+        let old_len = tokens.len();
+        let new_source: &'static str = Box::leak(edit.apply(old_source).into_boxed_str());
+        let relexed = relex(&mut tokens, &edit, new_source);
+        let (expected, _) = lex(new_source);
+
+        assert_eq!(tokens, expected);
+        assert_eq!(
+            tokens.len() - relexed.changed.len(),
+            old_len - relexed.replaced.len()
+        );
+        assert_eq!(relexed.delta(), edit.delta());
+        relexed
+    }
+
+    /// Assert that re-parsing gives the same tree as parsing the edited source,
+    /// and return the re-parsed text.
+    fn assert_reparse(old_source: &str, edit: TextEdit) -> &'static str {
+        let registry = Registry::default();
+        let (mut tokens, _) = lex(old_source);
+        let mut tree = SyntaxTree::from_tokens_with(old_source, tokens.clone(), &registry);
+        let new_source: &'static str = Box::leak(edit.apply(old_source).into_boxed_str());
+        let relexed = relex(&mut tokens, &edit, new_source);
+        let span = tree.reparse_with(new_source, &tokens, &relexed, &registry);
+
+        let expected = SyntaxTree::parse_with(new_source, &registry);
+        assert_eq!(tokens, lex(new_source).0);
+        let sorted = |tree: &SyntaxTree<'_>| {
+            let mut diagnostics = tree.diagnostics().to_vec();
+            diagnostics.sort_by_key(|diagnostic| diagnostic.span.start);
+            diagnostics
+        };
+        assert_eq!(tree.nodes(), expected.nodes());
+        assert_eq!(sorted(&tree), sorted(&expected));
+        assert_eq!(tree.to_string(), new_source);
+        &new_source[span]
+    }
+
+    #[test]
+    fn test_relex_insert() {
+        let relexed = assert_relex("a\nbc d\ne\n", TextEdit::new(3..3, "x"));
+        assert_eq!(relexed.changed, 2..3);
+        assert_eq!(relexed.replaced, 2..3);
+        assert_eq!(relexed.changed_span, 2..5);
+        assert_eq!(relexed.replaced_span, 2..4);
+    }
+
+    #[test]
+    fn test_relex_comment() {
+        let source = "\\begin{document}\n$ a $ b\n\\end{document}\n";
+        let relexed = assert_relex(source, TextEdit::new(17..17, "%"));
+        assert_eq!(relexed.changed, 2..3);
+        assert_eq!(relexed.replaced, 2..9);
+    }
+
+    #[test]
+    fn test_relex_merge_lines() {
+        let relexed = assert_relex("ab\ncd\nef", TextEdit::new(2..3, ""));
+        assert_eq!(relexed.changed, 0..1);
+        assert_eq!(relexed.replaced, 0..3);
+    }
+
+    #[test]
+    fn test_relex_unchanged_tokens() {
+        let relexed = assert_relex("\\emph{ab} cd\n", TextEdit::new(7..8, "x"));
+        assert_eq!(relexed.changed, 2..3);
+        assert_eq!(relexed.replaced, 2..3);
+
+        let relexed = assert_relex("a b c\n", TextEdit::new(2..3, "d"));
+        assert_eq!(relexed.changed, 2..3);
+        assert_eq!(relexed.replaced, 2..3);
+    }
+
+    #[test]
+    fn test_relex_environment() {
+        assert_relex(
+            "\\begin{a}\n\\end{a}\n\\begin{b}\\end{b}",
+            TextEdit::new(7..8, "abc"),
+        );
+    }
+
+    #[test]
+    fn test_relex_edges() {
+        assert_relex("", TextEdit::new(0..0, "new"));
+        assert_relex("old", TextEdit::new(0..3, ""));
+        assert_relex("line\n", TextEdit::new(5..5, "\\cmd"));
+        assert_relex("a\r\nb", TextEdit::new(1..2, ""));
+    }
+
+    #[test]
+    fn test_reparse_innermost() {
+        let source =
+            "\\begin{document}\n\\begin{itemize}\n\\item {a} b\n\\end{itemize}\n\\end{document}\n";
+
+        assert_eq!(assert_reparse(source, TextEdit::new(40..41, "c")), "c");
+        assert_eq!(
+            assert_reparse(source, TextEdit::new(43..44, "$x$")),
+            "\n\\item {a} $x$\n"
+        );
+        assert_eq!(
+            assert_reparse(source, TextEdit::new(43..44, "{")),
+            "\n\\item {a} {\n"
+        );
+    }
+
+    #[test]
+    fn test_reparse_diagnostics() {
+        let source = "\\begin{a}\n{ x\n\\end{a} y }\n";
+
+        assert_reparse(source, TextEdit::new(12..13, "}"));
+        assert_reparse(source, TextEdit::new(10..11, ""));
+        assert_reparse(source, TextEdit::new(20..21, "\\end{a}"));
+    }
+
+    #[test]
+    fn test_reparse_whole() {
+        let source = "\\begin{a}\nb\n\\end{a}\n";
+
+        assert_eq!(
+            assert_reparse(source, TextEdit::new(9..11, "[c]")),
+            "\\begin{a}[c]\n\\end{a}\n"
+        );
+        assert_eq!(
+            assert_reparse(source, TextEdit::new(0..0, "x")),
+            "x\\begin{a}\nb\n\\end{a}\n"
+        );
+        assert_reparse("", TextEdit::new(0..0, "{"));
+        assert_reparse("\\frac{a}\n{b}", TextEdit::new(9..10, ""));
+    }
+
+    #[test]
+    fn test_relex_context() {
+        let source = "\\makeatletter\n\\c@page\n";
+        let relexed = assert_relex(source, TextEdit::new(16..16, "x"));
+        assert_eq!(relexed.changed, 2..3);
+        assert_eq!(relexed.replaced, 2..3);
+
+        let source = "\\begin{verbatim}\na\n$b\n\\end{verbatim}\n";
+        let relexed = assert_relex(source, TextEdit::new(19..20, "%"));
+        assert_eq!(relexed.changed, 1..2);
+        assert_eq!(relexed.replaced, 1..2);
+
+        assert_relex("a\n\\verb|%|\n", TextEdit::new(8..9, ""));
+        assert_relex("\\begin{a}\n%\n\\end{a}", TextEdit::new(7..8, "verbatim"));
+        assert_relex(
+            "\\lstnewenvironment{a}{}{}\n\\begin{a}\n%\n\\end{a}",
+            TextEdit::new(0..1, ""),
+        );
+    }
+
+    #[test]
+    fn test_reparse_context() {
+        let sources = [
+            "\\begin{document}\n\\verb|$x%| {a} $y$\n\\begin{verbatim}\n$ { % \\end{itemize}\n\\end{verbatim}\n{b}\n\\end{document}\n",
+            "\\lstnewenvironment{code}{}{}\n\\begin{code}\n$ { %\n\\end{code}\n$x$ \\begin{cod}\n{\n\\end{cod}\n",
+            "\\makeatletter\n\\c@page {a}\n\\makeatother\n\\c@page\n\\ExplSyntaxOn \\seq_new:N\n",
+        ];
+
+        for source in sources {
+            for i in 0..=source.len() {
+                for insert in ["%", "{", "}", "$", "\\", "\n", "x", "@", "e"] {
+                    assert_reparse(source, TextEdit::new(i..i, insert));
+                }
+                if i < source.len() {
+                    assert_reparse(source, TextEdit::new(i..i + 1, ""));
+                }
+            }
+        }
+    }
+}
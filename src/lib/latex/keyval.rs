@@ -0,0 +1,269 @@
+//! Parsing key-value option lists, e.g., `[thick, fill=gray!60]`.
+//!
+//! Option lists follow the `keyval` and `pgfkeys` conventions: items are separated
+//! by commas, and each item is either a single key or a `key=value` pair.
+//! Keys may contain spaces (e.g., `x expr`), and commas or equal signs inside
+//! braces are protected (e.g., `title={A, B}`).
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::keyval::parse;
+//!
+//! let options = parse("x expr=\\coordindex+1, y index=0, legend={a, b},thick");
+//! let pairs: Vec<_> = options.iter().map(|kv| (kv.key, kv.value)).collect();
+//!
+//! assert_eq!(
+//!     pairs,
+//!     vec![
+//!         ("x expr", Some("\\coordindex+1")),
+//!         ("y index", Some("0")),
+//!         ("legend", Some("{a, b}")),
+//!         ("thick", None),
+//!     ]
+//! );
+//! assert_eq!(options[2].unbraced_value(), Some("a, b"));
+//! ```
+
+use crate::latex::parse::Group;
+use crate::latex::token::Span;
+
+/// A `key=value` pair, or a single key, from an option list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyVal<'source> {
+    /// Key, trimmed.
+    pub key: &'source str,
+    /// Span of the key.
+    pub key_span: Span,
+    /// Value, trimmed, if any.
+    ///
+    /// A key followed by an equal sign with nothing after has an empty value.
+    pub value: Option<&'source str>,
+    /// Span of the value, if any.
+    pub value_span: Option<Span>,
+    /// Span of the whole item, from the key to the end of the value.
+    pub span: Span,
+}
+
+impl<'source> KeyVal<'source> {
+    /// Return the value, without the outer braces if the whole value is braced,
+    /// e.g., `a, b` for `{a, b}`.
+    #[must_use]
+    pub fn unbraced_value(&self) -> Option<&'source str> {
+        self.value.map(strip_braces)
+    }
+}
+
+/// Strip one level of braces around `s`, if they enclose the whole string.
+fn strip_braces(s: &str) -> &str {
+    if !(s.starts_with('{') && s.ends_with('}')) || s.len() < 2 {
+        return s;
+    }
+    let mut depth = 0;
+    let mut chars = s.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 && i + 1 < s.len() {
+                    return s;
+                }
+            }
+            _ => (),
+        }
+    }
+    &s[1..s.len() - 1]
+}
+
+/// Parse an option list.
+#[must_use]
+pub fn parse(source: &str) -> Vec<KeyVal<'_>> {
+    parse_at(source, 0..source.len())
+}
+
+/// Parse the content of a group, e.g., an optional argument, as an option list.
+///
+/// Returned spans are relative to `source`.
+#[must_use]
+pub fn parse_group<'source>(group: &Group<'source>, source: &'source str) -> Vec<KeyVal<'source>> {
+    parse_at(source, group.inner_span())
+}
+
+/// Span of significant characters, i.e., neither whitespace nor comments.
+#[derive(Default)]
+struct Region(Option<Span>);
+
+impl Region {
+    fn extend(&mut self, span: Span) {
+        self.0 = Some(self.0.as_ref().map_or(span.start, |region| region.start)..span.end);
+    }
+}
+
+/// Parse the option list in `source[span]`.
+fn parse_at(source: &str, span: Span) -> Vec<KeyVal<'_>> {
+    let mut items = Vec::new();
+    let mut key = Region::default();
+    let mut value: Option<Region> = None;
+    let mut depth = 0usize;
+
+    let mut push = |key: &mut Region, value: &mut Option<Region>| {
+        let value = value.take();
+        let key_span = key.0.take();
+        let value_span = value.map(|region| {
+            region.0.unwrap_or_else(|| {
+                let end = key_span.as_ref().map_or(span.start, |key| key.end);
+                end..end
+            })
+        });
+        let key_span = match (key_span, &value_span) {
+            (Some(key_span), _) => key_span,
+            (None, Some(value_span)) => value_span.start..value_span.start,
+            (None, None) => return,
+        };
+        let end = value_span.as_ref().map_or(key_span.end, |value| value.end);
+        items.push(KeyVal {
+            key: &source[key_span.clone()],
+            span: key_span.start..end,
+            key_span,
+            value: value_span.clone().map(|value| &source[value]),
+            value_span,
+        });
+    };
+
+    let slice = &source[span.clone()];
+    let mut chars = slice.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        let start = span.start + i;
+        let mut end = start + c.len_utf8();
+
+        match c {
+            '%' => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            ',' if depth == 0 => {
+                push(&mut key, &mut value);
+                continue;
+            }
+            '=' if depth == 0 && value.is_none() => {
+                value = Some(Region::default());
+                continue;
+            }
+            '\\' => {
+                if let Some((j, c)) = chars.next() {
+                    end = span.start + j + c.len_utf8();
+                }
+            }
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+        match value.as_mut() {
+            Some(region) => region.extend(start..end),
+            None => key.extend(start..end),
+        }
+    }
+    push(&mut key, &mut value);
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::parse::{Argument, Node, SyntaxTree};
+
+    fn pairs<'source>(options: &[KeyVal<'source>]) -> Vec<(&'source str, Option<&'source str>)> {
+        options.iter().map(|kv| (kv.key, kv.value)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            pairs(&parse("thick,fill=gray!60")),
+            vec![("thick", None), ("fill", Some("gray!60"))]
+        );
+        assert_eq!(pairs(&parse("")), vec![]);
+        assert_eq!(pairs(&parse(" , ,")), vec![]);
+        assert_eq!(pairs(&parse("a=")), vec![("a", Some(""))]);
+        assert_eq!(pairs(&parse("=b")), vec![("", Some("b"))]);
+        assert_eq!(pairs(&parse("a = b = c")), vec![("a", Some("b = c"))]);
+    }
+
+    #[test]
+    fn test_parse_protected() {
+        assert_eq!(
+            pairs(&parse(r"a={x,y=z}, b=\{, c={{d}},e")),
+            vec![
+                ("a", Some("{x,y=z}")),
+                ("b", Some(r"\{")),
+                ("c", Some("{{d}}")),
+                ("e", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_spans() {
+        let source = "  x expr = 1 ,% comment, a=b\n  y index=0 % trailing\n";
+        let options = parse(source);
+
+        assert_eq!(
+            pairs(&options),
+            vec![("x expr", Some("1")), ("y index", Some("0"))]
+        );
+        assert_eq!(options[0].key_span, 2..8);
+        assert_eq!(options[0].value_span, Some(11..12));
+        assert_eq!(&source[options[1].span.clone()], "y index=0");
+    }
+
+    #[test]
+    fn test_unbraced_value() {
+        let options = parse("a={b}, c={d}{e}, f, g={{h}}");
+        let values: Vec<_> = options.iter().map(KeyVal::unbraced_value).collect();
+        assert_eq!(values, vec![Some("b"), Some("{d}{e}"), None, Some("{h}")]);
+    }
+
+    #[test]
+    fn test_parse_group() {
+        let source = include_str!("../../../tests/data/pgfplots_table.tex");
+        let tree = SyntaxTree::parse(source);
+        let options: Vec<_> = tree
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                Node::Command(command) => command.args.first().and_then(Argument::group),
+                _ => None,
+            })
+            .flat_map(|group| parse_group(group, source))
+            .collect();
+
+        assert_eq!(
+            pairs(&options),
+            vec![
+                ("border", Some("1")),
+                ("pgfplots", None),
+                ("compat", Some("1.5"))
+            ]
+        );
+        assert_eq!(&source[options[2].span.clone()], "compat=1.5");
+
+        let start = source.find("x expr").unwrap();
+        let end = source.find("] {").unwrap();
+        let options = parse(&source[start..end]);
+        assert_eq!(
+            pairs(&options),
+            vec![("x expr", Some(r"\coordindex+1")), ("y index", Some("0"))]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! Conversion between byte offsets and line/column positions.
+//!
+//! A [`LineIndex`] is built once per source, and converts the byte offsets of
+//! [`Span`]s into [`Position`]s, and back again. Columns are counted in one of three
+//! [`ColumnUnit`]s: bytes, characters, e.g., for terminals, or UTF-16 code units,
+//! e.g., for editors speaking the Language Server Protocol.
+//!
+//! Positions are 0-based, see [`Position::one_based`] for 1-based ones.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::lines::{ColumnUnit, LineIndex, Position};
+//!
+//! let source = "\\section{Café}\n$𝔸 = \\emptyset$";
+//! let index = LineIndex::new(source);
+//! let offset = source.find("= ").unwrap();
+//!
+//! let position = index.position(offset, ColumnUnit::Char);
+//! assert_eq!(position, Position::new(1, 3));
+//! assert_eq!(position.one_based(), Position::new(2, 4));
+//!
+//! // '𝔸' is two code units long in UTF-16, and four bytes long in UTF-8
+//! assert_eq!(index.position(offset, ColumnUnit::Utf16), Position::new(1, 4));
+//! assert_eq!(index.position(offset, ColumnUnit::Byte), Position::new(1, 6));
+//!
+//! assert_eq!(index.offset(Position::new(1, 4), ColumnUnit::Utf16), Some(offset));
+//! ```
+
+use crate::latex::token::Span;
+
+/// Unit in which columns are counted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ColumnUnit {
+    /// UTF-8 bytes.
+    Byte,
+    /// Unicode scalar values, i.e., Rust [`char`]s.
+    Char,
+    /// UTF-16 code units.
+    Utf16,
+}
+
+impl ColumnUnit {
+    /// Return the length of a character in this unit.
+    fn len(&self, c: char) -> usize {
+        match self {
+            ColumnUnit::Byte => c.len_utf8(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// A line and a column in a source.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Line.
+    pub line: usize,
+    /// Column, in some [`ColumnUnit`].
+    pub column: usize,
+}
+
+impl Position {
+    /// Create a new position.
+    #[must_use]
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+
+    /// Return this 0-based position as a 1-based one.
+    #[must_use]
+    pub fn one_based(self) -> Self {
+        Self::new(self.line + 1, self.column + 1)
+    }
+
+    /// Return this 1-based position as a 0-based one, if both line and column are positive.
+    #[must_use]
+    pub fn zero_based(self) -> Option<Self> {
+        Some(Self::new(
+            self.line.checked_sub(1)?,
+            self.column.checked_sub(1)?,
+        ))
+    }
+}
+
+/// Index of the lines of a source, see the [module documentation](self).
+///
+/// Lines end with `'\n'` or `"\r\n"`, as [`Token::Newline`](crate::latex::token::Token::Newline).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineIndex<'source> {
+    source: &'source str,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl<'source> LineIndex<'source> {
+    /// Create a new index over a source.
+    #[must_use]
+    pub fn new(source: &'source str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Return the number of lines, i.e., one more than the number of line breaks.
+    #[must_use]
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Return the span of a line, without its line break, if the line exists.
+    #[must_use]
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        let start = *self.line_starts.get(line)?;
+        let end = match self.line_starts.get(line + 1) {
+            Some(next) => {
+                let text = &self.source[start..*next];
+                next - if text.ends_with("\r\n") { 2 } else { 1 }
+            }
+            None => self.source.len(),
+        };
+        Some(start..end)
+    }
+
+    /// Return the line containing a byte offset.
+    ///
+    /// Offsets past the end of the source are on the last line.
+    #[must_use]
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= offset) - 1
+    }
+
+    /// Return the position of a byte offset.
+    ///
+    /// Offsets inside a character are moved back to its start, and offsets
+    /// past the end of the source are moved back to the end.
+    #[must_use]
+    pub fn position(&self, offset: usize, unit: ColumnUnit) -> Position {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line(offset);
+        let text = &self.source[self.line_starts[line]..offset];
+        let column = match unit {
+            ColumnUnit::Byte => text.len(),
+            _ => text.chars().map(|c| unit.len(c)).sum(),
+        };
+        Position::new(line, column)
+    }
+
+    /// Return the positions of the start and the end of a span.
+    #[must_use]
+    pub fn range(&self, span: Span, unit: ColumnUnit) -> (Position, Position) {
+        (
+            self.position(span.start, unit),
+            self.position(span.end, unit),
+        )
+    }
+
+    /// Return the byte offset of a position, if any.
+    ///
+    /// A column can point at most to the end of its line, before the line break,
+    /// and must not point inside a character, e.g., between two UTF-16 surrogates.
+    #[must_use]
+    pub fn offset(&self, position: Position, unit: ColumnUnit) -> Option<usize> {
+        let span = self.line_span(position.line)?;
+        let mut column = 0;
+
+        for (i, c) in self.source[span.clone()].char_indices() {
+            if column >= position.column {
+                return (column == position.column).then_some(span.start + i);
+            }
+            column += unit.len(c);
+        }
+        (column == position.column).then_some(span.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let index = LineIndex::new("a\r\n\nbc\n");
+        assert_eq!(index.line_count(), 4);
+
+        let spans: Vec<_> = (0..5).map(|line| index.line_span(line)).collect();
+        assert_eq!(
+            spans,
+            vec![Some(0..1), Some(3..3), Some(4..6), Some(7..7), None]
+        );
+
+        let lines: Vec<_> = (0..9).map(|offset| index.line(offset)).collect();
+        assert_eq!(lines, vec![0, 0, 0, 1, 2, 2, 2, 3, 3]);
+    }
+
+    #[test]
+    fn test_position() {
+        let source = "é😀x\nab";
+        let index = LineIndex::new(source);
+        let columns = |unit| -> Vec<_> {
+            source
+                .char_indices()
+                .map(|(i, _)| index.position(i, unit).column)
+                .collect()
+        };
+
+        assert_eq!(columns(ColumnUnit::Byte), vec![0, 2, 6, 7, 0, 1]);
+        assert_eq!(columns(ColumnUnit::Char), vec![0, 1, 2, 3, 0, 1]);
+        assert_eq!(columns(ColumnUnit::Utf16), vec![0, 1, 3, 4, 0, 1]);
+
+        // Inside '😀', and past the end
+        assert_eq!(index.position(4, ColumnUnit::Char), Position::new(0, 1));
+        assert_eq!(index.position(100, ColumnUnit::Char), Position::new(1, 2));
+        assert_eq!(
+            index.range(2..9, ColumnUnit::Utf16),
+            (Position::new(0, 1), Position::new(1, 1))
+        );
+    }
+
+    #[test]
+    fn test_offset() {
+        let source = "é😀x\r\nab";
+        let index = LineIndex::new(source);
+
+        for unit in [ColumnUnit::Byte, ColumnUnit::Char, ColumnUnit::Utf16] {
+            for (offset, _) in source.char_indices().filter(|(_, c)| *c != '\n') {
+                let position = index.position(offset, unit);
+                assert_eq!(index.offset(position, unit), Some(offset), "{unit:?}");
+            }
+        }
+
+        assert_eq!(index.offset(Position::new(0, 2), ColumnUnit::Utf16), None);
+        assert_eq!(index.offset(Position::new(0, 5), ColumnUnit::Utf16), None);
+        assert_eq!(
+            index.offset(Position::new(1, 2), ColumnUnit::Char),
+            Some(11)
+        );
+        assert_eq!(index.offset(Position::new(2, 0), ColumnUnit::Char), None);
+    }
+
+    #[test]
+    fn test_one_based() {
+        let position = Position::new(0, 4);
+        assert_eq!(position.one_based(), Position::new(1, 5));
+        assert_eq!(position.one_based().zero_based(), Some(position));
+        assert_eq!(position.zero_based(), None);
+    }
+}
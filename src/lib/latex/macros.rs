@@ -0,0 +1,425 @@
+//! Harvesting user-defined macros from [`Token`] iterators.
+//!
+//! Documents often define their own commands and environments, e.g., with
+//! `\newcommand{\vect}[1]{...}` or `\NewDocumentCommand{\foo}{O{x} m}{...}`.
+//! This module extracts such definitions, see [`MacroDefinition`], so that
+//! their signatures can be added to a [`Registry`].
+//!
+//! Such a registry groups arguments when parsing, see
+//! [`SyntaxTree::parse_with`](crate::latex::parse::SyntaxTree::parse_with),
+//! and when highlighting arguments, see
+//! [`ArgumentHighlighter`](crate::latex::highlight::ArgumentHighlighter).
+//! The auto-indent formatter, which only indents environments, does not group
+//! arguments: it only needs the user-defined verbatim environments, see
+//! [`VerbatimConfig::extend`](crate::latex::verbatim::VerbatimConfig::extend).
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::macros::harvest;
+//! use untex::latex::signature::Registry;
+//! use untex::prelude::*;
+//!
+//! let source = r"\newcommand{\vect}[2][x]{\mathbf{#1 #2}}";
+//! let definitions = harvest(source, Token::lexer(source).spanned());
+//!
+//! assert_eq!(definitions[0].name, "vect");
+//! assert_eq!(definitions[0].arity, 2);
+//! assert_eq!(definitions[0].default, Some("x"));
+//!
+//! let mut registry = Registry::latex2e();
+//! registry.extend(&definitions);
+//! assert_eq!(registry.command("vect").unwrap().to_string(), "O{x} m");
+//! ```
+
+use crate::latex::signature::{Registry, Signature};
+use crate::latex::token::{Span, SpannedToken, Token};
+
+/// Kind of macro being defined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MacroKind {
+    /// A command, e.g., with `\newcommand`.
+    Command,
+    /// An environment, e.g., with `\newenvironment`.
+    Environment,
+}
+
+/// A macro definition found in a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MacroDefinition<'source> {
+    /// Kind of macro.
+    pub kind: MacroKind,
+    /// Name of the defining command, without leading backslash, e.g., `"newcommand"`.
+    pub definer: &'source str,
+    /// Name of the defined macro, without leading backslash.
+    pub name: &'source str,
+    /// Number of arguments.
+    ///
+    /// For `\newcommand`-like definitions, an invalid number, i.e., not between 0 and 9,
+    /// is read as 0, and the definition has no signature.
+    pub arity: usize,
+    /// Default value of the first argument, for `\newcommand`-like definitions.
+    pub default: Option<&'source str>,
+    /// Argument specification, for `xparse`-like definitions.
+    pub argspec: Option<&'source str>,
+    /// Signature of the macro, if its argument specification is supported
+    /// (see [`Signature`]'s `FromStr` implementation).
+    pub signature: Option<Signature>,
+    /// Span of the whole definition.
+    pub span: Span,
+}
+
+/// Syntax used by a defining command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Syntax {
+    /// `\newcommand{\name}[n][default]{body}`.
+    NewCommand,
+    /// `\newenvironment{name}[n][default]{begin}{end}`.
+    NewEnvironment,
+    /// `\NewDocumentCommand{\name}{argspec}{body}`.
+    DocumentCommand,
+    /// `\NewDocumentEnvironment{name}{argspec}{begin}{end}`.
+    DocumentEnvironment,
+    /// `\DeclareMathOperator{\name}{text}`.
+    MathOperator,
+}
+
+/// Return the syntax used by a defining command, if `name` is one.
+fn syntax(name: &str) -> Option<Syntax> {
+    match name {
+        "newcommand" | "renewcommand" | "providecommand" | "DeclareRobustCommand" => {
+            Some(Syntax::NewCommand)
+        }
+        "newenvironment" | "renewenvironment" | "lstnewenvironment" => Some(Syntax::NewEnvironment),
+        "NewDocumentCommand"
+        | "RenewDocumentCommand"
+        | "ProvideDocumentCommand"
+        | "DeclareDocumentCommand"
+        | "NewExpandableDocumentCommand"
+        | "RenewExpandableDocumentCommand"
+        | "ProvideExpandableDocumentCommand"
+        | "DeclareExpandableDocumentCommand" => Some(Syntax::DocumentCommand),
+        "NewDocumentEnvironment"
+        | "RenewDocumentEnvironment"
+        | "ProvideDocumentEnvironment"
+        | "DeclareDocumentEnvironment" => Some(Syntax::DocumentEnvironment),
+        "DeclareMathOperator" => Some(Syntax::MathOperator),
+        _ => None,
+    }
+}
+
+/// Return all macro definitions found in a [`SpannedToken`] iterator,
+/// obtained from `source`.
+pub fn harvest<'source, I>(source: &'source str, iter: I) -> Vec<MacroDefinition<'source>>
+where
+    I: IntoIterator<Item = SpannedToken<'source>>,
+{
+    let tokens: Vec<_> = iter.into_iter().collect();
+    let mut scanner = Scanner {
+        source,
+        tokens: &tokens,
+        pos: 0,
+    };
+    let mut definitions = Vec::new();
+
+    while let Some((token, span)) = scanner.bump() {
+        if token != Token::CommandName {
+            continue;
+        }
+        let definer = &source[span.start + 1..span.end];
+
+        if let Some(syntax) = syntax(definer) {
+            let start = scanner.pos;
+            match scanner.definition(definer, syntax, span.start) {
+                Some(definition) => definitions.push(definition),
+                None => scanner.pos = start,
+            }
+        }
+    }
+
+    definitions
+}
+
+/// Cursor over tokens, used to read definitions.
+struct Scanner<'a, 'source> {
+    source: &'source str,
+    tokens: &'a [SpannedToken<'source>],
+    pos: usize,
+}
+
+impl<'a, 'source> Scanner<'a, 'source> {
+    fn bump(&mut self) -> Option<SpannedToken<'source>> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    /// Skip spaces and newlines, and return the next token, without consuming it.
+    ///
+    /// Callers should restore the position if the returned token is not consumed.
+    fn peek(&mut self) -> Option<&'a SpannedToken<'source>> {
+        while let Some((Token::TabsOrSpaces | Token::Newline, _)) = self.tokens.get(self.pos) {
+            self.pos += 1;
+        }
+        self.tokens.get(self.pos)
+    }
+
+    /// Return the end of the last consumed token.
+    fn last_end(&self) -> usize {
+        self.tokens[self.pos - 1].1.end
+    }
+
+    /// Read a balanced group, delimited by `open` and `close`, and return
+    /// the span of its content.
+    ///
+    /// Braces are always balanced, such that brackets can be protected with braces.
+    fn delimited(&mut self, open: &Token<'_>, close: &Token<'_>) -> Option<Span> {
+        let pos = self.pos;
+        let content = self.delimited_content(open, close);
+        if content.is_none() {
+            self.pos = pos;
+        }
+        content
+    }
+
+    fn delimited_content(&mut self, open: &Token<'_>, close: &Token<'_>) -> Option<Span> {
+        match self.peek() {
+            Some((token, span)) if token == open => {
+                self.pos += 1;
+                let start = span.end;
+                let mut depth = 0usize;
+
+                while let Some((token, span)) = self.bump() {
+                    if depth == 0 && &token == close {
+                        return Some(start..span.start);
+                    }
+                    match token {
+                        Token::BraceOpen => depth += 1,
+                        Token::BraceClose => depth = depth.saturating_sub(1),
+                        _ => (),
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn braced(&mut self) -> Option<Span> {
+        self.delimited(&Token::BraceOpen, &Token::BraceClose)
+    }
+
+    fn bracketed(&mut self) -> Option<Span> {
+        self.delimited(&Token::BracketOpen, &Token::BracketClose)
+    }
+
+    /// Read an optional star.
+    fn star(&mut self) {
+        let pos = self.pos;
+        match self.peek() {
+            Some((Token::Asterix, _)) => self.pos += 1,
+            _ => self.pos = pos,
+        }
+    }
+
+    /// Read a command name, either as `\name` or `{\name}`.
+    fn command_name(&mut self) -> Option<&'source str> {
+        match self.peek() {
+            Some((Token::CommandName | Token::InvalidCommand, span)) => {
+                self.pos += 1;
+                self.source.get(span.start + 1..span.end)
+            }
+            _ => {
+                let name = self.source[self.braced()?].trim();
+                name.strip_prefix('\\')
+            }
+        }
+    }
+
+    /// Read an environment name, as `{name}`.
+    fn environment_name(&mut self) -> Option<&'source str> {
+        let name = self.source[self.braced()?].trim();
+        (!name.is_empty()).then_some(name)
+    }
+
+    /// Read the rest of a definition, after the defining command.
+    fn definition(
+        &mut self,
+        definer: &'source str,
+        syntax: Syntax,
+        start: usize,
+    ) -> Option<MacroDefinition<'source>> {
+        let kind = match syntax {
+            Syntax::NewEnvironment | Syntax::DocumentEnvironment => MacroKind::Environment,
+            _ => MacroKind::Command,
+        };
+        if matches!(
+            syntax,
+            Syntax::NewCommand | Syntax::NewEnvironment | Syntax::MathOperator
+        ) {
+            self.star();
+        }
+        let name = match kind {
+            MacroKind::Command => self.command_name()?,
+            MacroKind::Environment => self.environment_name()?,
+        };
+
+        let (arity, default, argspec, signature) = match syntax {
+            Syntax::NewCommand | Syntax::NewEnvironment => {
+                // As in LaTeX, a macro has at most 9 arguments
+                let arity = match self.bracketed() {
+                    Some(span) => self.source[span].trim().parse().ok().filter(|n| *n <= 9),
+                    None => Some(0),
+                };
+                let default = self.bracketed().map(|span| &self.source[span]);
+                let signature =
+                    arity.map(|arity| Signature::with_arity(arity, default.map(str::to_string)));
+                (arity.unwrap_or(0), default, None, signature)
+            }
+            Syntax::DocumentCommand | Syntax::DocumentEnvironment => {
+                let argspec = &self.source[self.braced()?];
+                let signature: Option<Signature> = argspec.parse().ok();
+                let arity = signature.as_ref().map_or_else(
+                    || argspec.chars().filter(char::is_ascii_alphabetic).count(),
+                    |signature| signature.args().len(),
+                );
+                (arity, None, Some(argspec), signature)
+            }
+            Syntax::MathOperator => (0, None, None, Some(Signature::default())),
+        };
+
+        // Body, and end code for environments
+        self.braced();
+        if kind == MacroKind::Environment {
+            self.braced();
+        }
+
+        Some(MacroDefinition {
+            kind,
+            definer,
+            name,
+            arity,
+            default,
+            argspec,
+            signature,
+            span: start..self.last_end(),
+        })
+    }
+}
+
+impl<'a, 'source> Extend<&'a MacroDefinition<'source>> for Registry {
+    /// Insert the signature of every definition, replacing existing ones.
+    ///
+    /// Definitions without a signature are ignored.
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = &'a MacroDefinition<'source>>,
+    {
+        for definition in iter {
+            if let Some(signature) = definition.signature.clone() {
+                match definition.kind {
+                    MacroKind::Command => self.insert_command(definition.name, signature),
+                    MacroKind::Environment => self.insert_environment(definition.name, signature),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::parse::SyntaxTree;
+    use logos::Logos;
+
+    fn harvest_str(source: &str) -> Vec<MacroDefinition<'_>> {
+        harvest(source, Token::lexer(source).spanned())
+    }
+
+    #[test]
+    fn test_newcommand() {
+        let source =
+            r"\newcommand{\a}{A} \renewcommand*\b[2]{#1#2} \providecommand{\c}[1][{x]}]{#1}";
+        let definitions = harvest_str(source);
+
+        let summary: Vec<_> = definitions
+            .iter()
+            .map(|d| (d.definer, d.name, d.arity, d.default))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("newcommand", "a", 0, None),
+                ("renewcommand", "b", 2, None),
+                ("providecommand", "c", 1, Some("{x]}")),
+            ]
+        );
+        assert_eq!(&source[definitions[0].span.clone()], r"\newcommand{\a}{A}");
+    }
+
+    #[test]
+    fn test_invalid_arity() {
+        let source = r"\newcommand{\a}[99999999999999]{x} \newcommand\b[10]{x} \newcommand\c[x]{x}";
+        let definitions = harvest_str(source);
+
+        assert_eq!(definitions.len(), 3);
+        assert!(definitions
+            .iter()
+            .all(|d| d.arity == 0 && d.signature.is_none()));
+        assert_eq!(SyntaxTree::parse(source).to_string(), source);
+    }
+
+    #[test]
+    fn test_document_command() {
+        let source = "\\NewDocumentCommand{\\foo}{O{x} m}\n  {#1 #2}";
+        let definitions = harvest_str(source);
+
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].name, "foo");
+        assert_eq!(definitions[0].arity, 2);
+        assert_eq!(definitions[0].argspec, Some("O{x} m"));
+        assert_eq!(definitions[0].span, 0..source.len());
+        assert_eq!(
+            definitions[0].signature.as_ref().unwrap().to_string(),
+            "O{x} m"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_argspec() {
+        let definitions = harvest_str(r"\NewDocumentCommand\foo{t+ m}{}");
+
+        assert_eq!(definitions[0].arity, 2);
+        assert!(definitions[0].signature.is_none());
+    }
+
+    #[test]
+    fn test_environment_and_operator() {
+        let source =
+            r"\newenvironment{proof}[1][Proof]{#1}{} \DeclareMathOperator*{\argmax}{arg\,max}";
+        let definitions = harvest_str(source);
+
+        assert_eq!(
+            &source[definitions[0].span.clone()],
+            r"\newenvironment{proof}[1][Proof]{#1}{}"
+        );
+        assert_eq!(definitions[0].kind, MacroKind::Environment);
+        assert_eq!(definitions[0].name, "proof");
+        assert_eq!(definitions[0].default, Some("Proof"));
+        assert_eq!(definitions[1].kind, MacroKind::Command);
+        assert_eq!(definitions[1].name, "argmax");
+        assert_eq!(definitions[1].arity, 0);
+    }
+
+    #[test]
+    fn test_registry_extend() {
+        let source = r"\newcommand\vect[1]{} \NewDocumentEnvironment{box}{o m}{}{}";
+        let mut registry = Registry::empty();
+        registry.extend(&harvest_str(source));
+
+        assert_eq!(registry.command("vect").unwrap().to_string(), "m");
+        assert_eq!(registry.environment("box").unwrap().to_string(), "o m");
+    }
+}
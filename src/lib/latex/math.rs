@@ -0,0 +1,763 @@
+//! Expression trees for math content.
+//!
+//! A [`MathTree`] is built from a math region (see [`Math`]) or a math environment
+//! (see [`Environment::is_math`]) of a [`SyntaxTree`].
+//! Its content is split into [`Row`]s, separated by `\\`, and [`Cell`]s, separated by `&`.
+//! Each cell is a list of [`MathNode`]s, where superscripts and subscripts are attached
+//! to their base, `\left...\right` pairs delimit their content, and text commands
+//! (e.g., `\text{...}`) switch back to text mode.
+//!
+//! Like parsing, building a math tree never fails: issues, such as a `\left` without
+//! a matching `\right`, are reported as [`Diagnostic`]s (see [`MathTree::diagnostics`]).
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::math::{MathNode, MathTree};
+//!
+//! let source = r"x_i^2 &= \frac{1}{2} \\ &= \left( y \right)";
+//! let tree = MathTree::parse(source);
+//!
+//! assert_eq!(tree.rows().len(), 2);
+//! assert_eq!(tree.rows()[0].cells.len(), 2);
+//!
+//! match &tree.rows()[0].cells[0].nodes[0] {
+//!     MathNode::Script(script) => {
+//!         assert_eq!(&source[script.base.as_ref().unwrap().span()], "x");
+//!         assert_eq!(&source[script.sub.as_ref().unwrap().span()], "i");
+//!         assert_eq!(&source[script.sup.as_ref().unwrap().span()], "2");
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! assert!(matches!(&tree.rows()[1].cells[1].nodes[1], MathNode::Delimited(_)));
+//! assert!(tree.diagnostics().is_empty());
+//! ```
+
+use crate::latex::align::{self, Splitter};
+use crate::latex::diagnostic::Diagnostic;
+use crate::latex::parse::{Argument, Command, Environment, Group, Math, Node, SyntaxTree};
+use crate::latex::token::{Span, Token};
+
+/// Commands whose argument is typeset in text mode.
+static TEXT_COMMANDS: &[&str] = &[
+    "text",
+    "textrm",
+    "textsf",
+    "texttt",
+    "textbf",
+    "textit",
+    "textsl",
+    "textsc",
+    "textup",
+    "textmd",
+    "textnormal",
+    "emph",
+    "mbox",
+    "hbox",
+    "fbox",
+    "intertext",
+    "shortintertext",
+];
+
+/// A group of math nodes, delimited by braces or brackets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MathGroup<'source> {
+    /// Span of the opening delimiter.
+    pub open: Span,
+    /// Nodes inside the group.
+    pub nodes: Vec<MathNode<'source>>,
+    /// Span of the closing delimiter, if any.
+    pub close: Option<Span>,
+    /// Span of the whole group, delimiters included.
+    pub span: Span,
+}
+
+/// An argument of a [`MathCommand`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MathArgument<'source> {
+    /// A star `'*'`.
+    Star(Span),
+    /// An optional argument, e.g., the index in `\sqrt[3]{x}`.
+    Optional(MathGroup<'source>),
+    /// A required argument, e.g., the numerator in `\frac{a}{b}`.
+    Required(MathGroup<'source>),
+}
+
+/// A command, e.g., `\frac{a}{b}` or `\alpha`, with its arguments parsed as math.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MathCommand<'source> {
+    /// Command name, without the leading backslash.
+    pub name: &'source str,
+    /// Span of the command name, leading backslash included.
+    pub name_span: Span,
+    /// Arguments following the command name.
+    pub args: Vec<MathArgument<'source>>,
+    /// Span of the whole command, arguments included.
+    pub span: Span,
+}
+
+/// A node with a superscript, a subscript, or both, e.g., `x_i^2`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Script<'source> {
+    /// Base, if any, e.g., `x` in `x_i^2`.
+    pub base: Option<Box<MathNode<'source>>>,
+    /// Subscript, if any, e.g., `i` in `x_i^2`.
+    pub sub: Option<Box<MathNode<'source>>>,
+    /// Superscript, if any, e.g., `2` in `x_i^2`.
+    pub sup: Option<Box<MathNode<'source>>>,
+    /// Span of the whole node, base included.
+    pub span: Span,
+}
+
+/// Content delimited by `\left` and `\right`, e.g., `\left( x \right)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Delimited<'source> {
+    /// Span of the `\left` command.
+    pub left: Span,
+    /// Span of the opening delimiter, e.g., `(`, if any.
+    pub open: Option<Span>,
+    /// Nodes between the delimiters.
+    pub nodes: Vec<MathNode<'source>>,
+    /// Span of the `\right` command, if any.
+    pub right: Option<Span>,
+    /// Span of the closing delimiter, e.g., `)`, if any.
+    pub close: Option<Span>,
+    /// Span of the whole node.
+    pub span: Span,
+}
+
+/// An environment nested in math content, e.g., `\begin{pmatrix}...\end{pmatrix}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MathEnvironment<'source> {
+    /// Environment name.
+    pub name: &'source str,
+    /// Span of the `\begin{name}` token.
+    pub begin: Span,
+    /// Arguments following `\begin{name}`.
+    pub args: Vec<Argument<'source>>,
+    /// Rows of the environment.
+    pub rows: Vec<Row<'source>>,
+    /// Spans of the rules following the last row.
+    pub end_rules: Vec<Span>,
+    /// Span of the `\end{name}` token, if any.
+    pub end: Option<Span>,
+    /// Span of the whole environment.
+    pub span: Span,
+}
+
+/// Enumerates all nodes of a [`MathTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MathNode<'source> {
+    /// A single symbol, e.g., a letter, a digit or an operator.
+    Atom(Span),
+    /// A command, see [`MathCommand`].
+    Command(MathCommand<'source>),
+    /// A brace group, see [`MathGroup`].
+    Group(MathGroup<'source>),
+    /// Superscripts and subscripts, see [`Script`].
+    Script(Script<'source>),
+    /// A `\left...\right` pair, see [`Delimited`].
+    Delimited(Delimited<'source>),
+    /// A command typesetting its argument in text mode, e.g., `\text{if }`.
+    ///
+    /// The argument is kept as parsed by the [`SyntaxTree`].
+    Text(Command<'source>),
+    /// A nested environment, see [`MathEnvironment`].
+    Environment(MathEnvironment<'source>),
+}
+
+impl<'source> MathNode<'source> {
+    /// Return the span of this node.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            MathNode::Atom(span) => span.clone(),
+            MathNode::Command(command) => command.span.clone(),
+            MathNode::Group(group) => group.span.clone(),
+            MathNode::Script(script) => script.span.clone(),
+            MathNode::Delimited(delimited) => delimited.span.clone(),
+            MathNode::Text(command) => command.span.clone(),
+            MathNode::Environment(environment) => environment.span.clone(),
+        }
+    }
+}
+
+/// An alignment cell, i.e., content separated by `&`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell<'source> {
+    /// Nodes inside the cell.
+    pub nodes: Vec<MathNode<'source>>,
+    /// Span of the cell, separators excluded.
+    pub span: Span,
+}
+
+/// A row, i.e., content separated by `\\`, see [`latex::align`](crate::latex::align).
+pub type Row<'source> = align::Row<Cell<'source>>;
+
+/// An expression tree for math content.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MathTree<'source> {
+    rows: Vec<Row<'source>>,
+    end_rules: Vec<Span>,
+    diagnostics: Vec<Diagnostic>,
+    span: Span,
+}
+
+impl<'source> MathTree<'source> {
+    /// Parse a whole source as math content.
+    #[must_use]
+    pub fn parse(source: &'source str) -> Self {
+        let tree = SyntaxTree::parse(source);
+        Self::from_nodes(tree.nodes(), 0..source.len(), source)
+    }
+
+    /// Build the expression tree of a math region.
+    #[must_use]
+    pub fn from_math(math: &Math<'source>, source: &'source str) -> Self {
+        Self::from_nodes(&math.children, math.inner_span(), source)
+    }
+
+    /// Build the expression tree of a math environment, e.g., `align`.
+    #[must_use]
+    pub fn from_environment(environment: &Environment<'source>, source: &'source str) -> Self {
+        Self::from_nodes(&environment.children, environment.inner_span(), source)
+    }
+
+    /// Build the expression tree of nodes covering `span`.
+    #[must_use]
+    pub fn from_nodes(nodes: &[Node<'source>], span: Span, source: &'source str) -> Self {
+        let mut builder = Builder {
+            source,
+            diagnostics: Vec::new(),
+        };
+        let (rows, end_rules) = builder.rows(nodes, span.clone());
+
+        Self {
+            rows,
+            end_rules,
+            diagnostics: builder.diagnostics,
+            span,
+        }
+    }
+
+    /// Return the rows.
+    #[must_use]
+    pub fn rows(&self) -> &[Row<'source>] {
+        &self.rows
+    }
+
+    /// Return the spans of the rules (e.g., `\hline`) following the last row.
+    #[must_use]
+    pub fn end_rules(&self) -> &[Span] {
+        &self.end_rules
+    }
+
+    /// Return the diagnostics reported while building the tree.
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Return the span of the math content.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+}
+
+/// A unit of math content, obtained by splitting text nodes into tokens.
+#[derive(Clone, Debug)]
+enum Item<'tree, 'source> {
+    Atom(Span),
+    Hat(usize),
+    Underscore(usize),
+    And(usize),
+    Node(&'tree Node<'source>),
+}
+
+/// Reason a sequence of math nodes stopped.
+enum Stop {
+    End,
+    Right(Span),
+}
+
+struct Builder<'source> {
+    source: &'source str,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'source> Builder<'source> {
+    /// Split nodes into items, discarding spaces and comments.
+    fn items<'tree>(&self, nodes: &'tree [Node<'source>]) -> Vec<Item<'tree, 'source>> {
+        self.convert(&align::items(nodes, self.source))
+    }
+
+    /// Convert aligned items into math items, discarding spaces.
+    fn convert<'tree>(&self, aligned: &[align::Item<'tree, 'source>]) -> Vec<Item<'tree, 'source>> {
+        let mut items = Vec::new();
+
+        for item in aligned.iter() {
+            match item {
+                align::Item::Token(token, span) => {
+                    let start = span.start;
+                    match token {
+                        Token::TabsOrSpaces | Token::Newline => (),
+                        Token::Hat => items.push(Item::Hat(start)),
+                        Token::Underscore => items.push(Item::Underscore(start)),
+                        Token::And => items.push(Item::And(start)),
+                        // Each letter or digit is a distinct atom, as in TeX.
+                        Token::Word | Token::Number => {
+                            items.extend(
+                                self.source[span.clone()]
+                                    .char_indices()
+                                    .map(|(i, c)| Item::Atom(start + i..start + i + c.len_utf8())),
+                            );
+                        }
+                        _ => items.push(Item::Atom(span.clone())),
+                    }
+                }
+                align::Item::Node(node) => items.push(Item::Node(node)),
+            }
+        }
+        items
+    }
+
+    /// Build the rows of nodes covering `span`, and the rules following the last row.
+    fn rows(&mut self, nodes: &[Node<'source>], span: Span) -> (Vec<Row<'source>>, Vec<Span>) {
+        Splitter::new(self.source, span.start).split(nodes, span.end, |items, span, _| {
+            let items = self.convert(items);
+            let mut pos = 0;
+            let (nodes, _) = self.sequence(&items, &mut pos, false);
+            Cell { nodes, span }
+        })
+    }
+
+    /// Build the nodes of a group.
+    fn group(&mut self, group: &Group<'source>) -> MathGroup<'source> {
+        let items = self.items(&group.children);
+        let mut pos = 0;
+        let (nodes, _) = self.sequence(&items, &mut pos, false);
+
+        MathGroup {
+            open: group.open.clone(),
+            nodes,
+            close: group.close.clone(),
+            span: group.span.clone(),
+        }
+    }
+
+    /// Build a sequence of nodes, until the end of the items, or a `\right`
+    /// if `delimited` is true.
+    fn sequence(
+        &mut self,
+        items: &[Item<'_, 'source>],
+        pos: &mut usize,
+        delimited: bool,
+    ) -> (Vec<MathNode<'source>>, Stop) {
+        let mut nodes: Vec<MathNode<'source>> = Vec::new();
+
+        while let Some(item) = items.get(*pos).cloned() {
+            match item {
+                Item::Hat(start) | Item::Underscore(start) => {
+                    *pos += 1;
+                    let is_sup = matches!(item, Item::Hat(_));
+                    let marker = if is_sup { "^" } else { "_" };
+                    let operand = self.operand(items, pos);
+                    if operand.is_none() {
+                        self.diagnostics.push(Diagnostic::error(
+                            start..start + 1,
+                            format!("missing argument after `{marker}`"),
+                        ));
+                    }
+                    self.attach(&mut nodes, start, is_sup, operand);
+                }
+                Item::And(start) => {
+                    *pos += 1;
+                    nodes.push(MathNode::Atom(start..start + 1));
+                }
+                Item::Node(Node::Command(command)) if command.name == "right" => {
+                    *pos += 1;
+                    if delimited {
+                        return (nodes, Stop::Right(command.span.clone()));
+                    }
+                    self.diagnostics.push(Diagnostic::error(
+                        command.span.clone(),
+                        r"`\right` has no matching `\left`",
+                    ));
+                    nodes.push(self.command(command));
+                }
+                Item::Node(Node::Command(command)) if command.name == "left" => {
+                    *pos += 1;
+                    let delimited = self.delimited(items, pos, command.span.clone());
+                    nodes.push(MathNode::Delimited(delimited));
+                }
+                _ => {
+                    if let Some(node) = self.operand(items, pos) {
+                        nodes.push(node);
+                    }
+                }
+            }
+        }
+        (nodes, Stop::End)
+    }
+
+    /// Attach a superscript or a subscript to the last node.
+    fn attach(
+        &mut self,
+        nodes: &mut Vec<MathNode<'source>>,
+        start: usize,
+        is_sup: bool,
+        operand: Option<MathNode<'source>>,
+    ) {
+        let end = operand.as_ref().map_or(start + 1, |node| node.span().end);
+        let operand = operand.map(Box::new);
+
+        if let Some(MathNode::Script(script)) = nodes.last_mut() {
+            let slot = if is_sup {
+                &mut script.sup
+            } else {
+                &mut script.sub
+            };
+            if slot.is_none() {
+                *slot = operand;
+                script.span.end = end;
+                return;
+            }
+            self.diagnostics.push(Diagnostic::error(
+                start..end,
+                if is_sup {
+                    "double superscript"
+                } else {
+                    "double subscript"
+                },
+            ));
+        }
+
+        let base = nodes.pop().map(Box::new);
+        let begin = base.as_ref().map_or(start, |base| base.span().start);
+        let (sup, sub) = if is_sup {
+            (operand, None)
+        } else {
+            (None, operand)
+        };
+        nodes.push(MathNode::Script(Script {
+            base,
+            sub,
+            sup,
+            span: begin..end,
+        }));
+    }
+
+    /// Build a `\left...\right` pair, the `\left` command being already consumed.
+    fn delimited(
+        &mut self,
+        items: &[Item<'_, 'source>],
+        pos: &mut usize,
+        left: Span,
+    ) -> Delimited<'source> {
+        let open = self.delimiter(items, pos);
+        let (nodes, stop) = self.sequence(items, pos, true);
+
+        let (right, close) = match stop {
+            Stop::Right(right) => (Some(right), self.delimiter(items, pos)),
+            _ => {
+                self.diagnostics.push(Diagnostic::error(
+                    left.clone(),
+                    r"`\left` has no matching `\right`",
+                ));
+                (None, None)
+            }
+        };
+        let end = close
+            .as_ref()
+            .or(right.as_ref())
+            .or(nodes.last().map(|node| node.span()).as_ref())
+            .or(open.as_ref())
+            .map_or(left.end, |span| span.end);
+
+        Delimited {
+            span: left.start..end,
+            left,
+            open,
+            nodes,
+            right,
+            close,
+        }
+    }
+
+    /// Consume the delimiter following `\left` or `\right`, if any.
+    fn delimiter(&mut self, items: &[Item<'_, 'source>], pos: &mut usize) -> Option<Span> {
+        let span = match items.get(*pos)? {
+            Item::Atom(span) => span.clone(),
+            Item::Node(Node::Command(command)) => command.span.clone(),
+            _ => return None,
+        };
+        *pos += 1;
+        Some(span)
+    }
+
+    /// Consume a single node, e.g., the argument of a script.
+    fn operand(
+        &mut self,
+        items: &[Item<'_, 'source>],
+        pos: &mut usize,
+    ) -> Option<MathNode<'source>> {
+        let node = match items.get(*pos)? {
+            Item::Atom(span) => MathNode::Atom(span.clone()),
+            Item::Node(node) => match node {
+                Node::Command(command) => self.command(command),
+                Node::Group(group) => MathNode::Group(self.group(group)),
+                Node::Environment(environment) => {
+                    let (rows, end_rules) =
+                        self.rows(&environment.children, environment.inner_span());
+                    MathNode::Environment(MathEnvironment {
+                        name: environment.name,
+                        begin: environment.begin.clone(),
+                        args: environment.args.clone(),
+                        rows,
+                        end_rules,
+                        end: environment.end.clone(),
+                        span: environment.span.clone(),
+                    })
+                }
+                node => MathNode::Atom(node.span()),
+            },
+            Item::Hat(_) | Item::Underscore(_) | Item::And(_) => return None,
+        };
+        *pos += 1;
+        Some(node)
+    }
+
+    /// Build a command, parsing its arguments as math.
+    fn command(&mut self, command: &Command<'source>) -> MathNode<'source> {
+        if TEXT_COMMANDS.contains(&command.name) {
+            return MathNode::Text(command.clone());
+        }
+        let args = command
+            .args
+            .iter()
+            .map(|arg| match arg {
+                Argument::Star(span) => MathArgument::Star(span.clone()),
+                Argument::Optional(group) => MathArgument::Optional(self.group(group)),
+                Argument::Required(group) => MathArgument::Required(self.group(group)),
+            })
+            .collect();
+
+        MathNode::Command(MathCommand {
+            name: command.name,
+            name_span: command.name_span.clone(),
+            args,
+            span: command.span.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn first<'a, 'source>(tree: &'a MathTree<'source>) -> &'a [MathNode<'source>] {
+        &tree.rows()[0].cells[0].nodes
+    }
+
+    #[test]
+    fn test_atoms() {
+        let source = r"ab + 12\,c";
+        let tree = MathTree::parse(source);
+        let atoms: Vec<_> = first(&tree)
+            .iter()
+            .map(|node| &source[node.span()])
+            .collect();
+
+        assert_eq!(atoms, vec!["a", "b", "+", "1", "2", r"\,", "c"]);
+    }
+
+    #[test]
+    fn test_scripts() {
+        let source = r"_x ab^{n+1}_2 {}^3_y";
+        let tree = MathTree::parse(source);
+        let nodes = first(&tree);
+
+        assert_eq!(nodes.len(), 4);
+        assert!(matches!(&nodes[0], MathNode::Script(script) if script.base.is_none()));
+        match &nodes[2] {
+            MathNode::Script(script) => {
+                assert_eq!(script.span, 4..13);
+                assert!(
+                    matches!(script.sup.as_deref(), Some(MathNode::Group(group)) if group.nodes.len() == 3)
+                );
+                assert!(
+                    matches!(script.sub.as_deref(), Some(MathNode::Atom(span)) if span == &(12..13))
+                );
+            }
+            node => panic!("unexpected node {node:?}"),
+        }
+        assert!(
+            matches!(&nodes[3], MathNode::Script(script) if script.sup.is_some() && script.sub.is_some())
+        );
+        assert!(tree.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_script_errors() {
+        let tree = MathTree::parse("x^2^3 y_");
+        let messages: Vec<_> = tree
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec!["double superscript", "missing argument after `_`"]
+        );
+        assert_eq!(first(&tree).len(), 2);
+    }
+
+    #[test]
+    fn test_commands() {
+        let source = r"\frac{a}{b^2} + \sqrt[3]{x} + \alpha";
+        let tree = MathTree::parse(source);
+        let nodes = first(&tree);
+
+        match &nodes[0] {
+            MathNode::Command(command) => {
+                assert_eq!(command.name, "frac");
+                assert_eq!(command.args.len(), 2);
+                assert!(
+                    matches!(&command.args[1], MathArgument::Required(group) if matches!(group.nodes[0], MathNode::Script(_)))
+                );
+            }
+            node => panic!("unexpected node {node:?}"),
+        }
+        assert!(
+            matches!(&nodes[2], MathNode::Command(command) if command.name == "sqrt" && matches!(command.args[0], MathArgument::Optional(_)))
+        );
+        assert!(
+            matches!(&nodes[4], MathNode::Command(command) if command.name == "alpha" && command.args.is_empty())
+        );
+    }
+
+    #[test]
+    fn test_delimited() {
+        let source = r"\left[ \frac{1}{2} \left\{ x \right. \right] y";
+        let tree = MathTree::parse(source);
+        let nodes = first(&tree);
+
+        match &nodes[0] {
+            MathNode::Delimited(delimited) => {
+                assert_eq!(&source[delimited.open.clone().unwrap()], "[");
+                assert_eq!(&source[delimited.close.clone().unwrap()], "]");
+                assert_eq!(delimited.nodes.len(), 2);
+                assert!(
+                    matches!(&delimited.nodes[1], MathNode::Delimited(inner) if inner.nodes.len() == 1)
+                );
+            }
+            node => panic!("unexpected node {node:?}"),
+        }
+        assert_eq!(nodes.len(), 2);
+        assert!(tree.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_mismatched_delimiters() {
+        let tree = MathTree::parse(r"\left( x \right) \right) \left[ y");
+        let messages: Vec<_> = tree
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+
+        assert_eq!(
+            messages,
+            vec![
+                r"`\right` has no matching `\left`",
+                r"`\left` has no matching `\right`"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        let source = "\\begin{align}\n  a &= b \\\\\n  &= c \\\\[2pt]\n  &= d \\\\\n\\end{align}";
+        let tree = SyntaxTree::parse(source);
+        let environment = match &tree.nodes()[0] {
+            Node::Environment(environment) => environment,
+            node => panic!("unexpected node {node:?}"),
+        };
+        let math = MathTree::from_environment(environment, source);
+
+        assert_eq!(math.rows().len(), 3);
+        assert_eq!(
+            &source[math.rows()[1].separator.clone().unwrap()],
+            "\\\\[2pt]"
+        );
+        for row in math.rows() {
+            assert_eq!(row.cells.len(), 2);
+        }
+        assert_eq!(&source[math.rows()[0].cells[0].span.clone()], "\n  a ");
+        assert_eq!(&source[math.rows()[2].cells[1].span.clone()], "= d ");
+    }
+
+    #[test]
+    fn test_rules() {
+        let source = r"\begin{array}{cc} \hline a & \multicolumn{1}{c}{b} \\ \hline \end{array}";
+        let tree = MathTree::parse(source);
+
+        match &first(&tree)[0] {
+            MathNode::Environment(environment) => {
+                assert_eq!(environment.rows.len(), 1);
+                let row = &environment.rows[0];
+                assert_eq!(&source[row.rules[0].clone()], r"\hline");
+                assert_eq!(row.multicolumns[0].cell, 1);
+                assert_eq!(row.cells[0].nodes.len(), 1);
+                assert_eq!(&source[environment.end_rules[0].clone()], r"\hline");
+            }
+            node => panic!("unexpected node {node:?}"),
+        }
+    }
+
+    #[test]
+    fn test_text_and_environments() {
+        let source =
+            r"f(x) = \begin{cases} 1 & \text{if $x > 0$, } \\ 0 & \text{otherwise} \end{cases}";
+        let tree = MathTree::parse(source);
+        let nodes = first(&tree);
+
+        match nodes.last().unwrap() {
+            MathNode::Environment(environment) => {
+                assert_eq!(environment.name, "cases");
+                assert_eq!(environment.rows.len(), 2);
+                let text = &environment.rows[0].cells[1].nodes[0];
+                assert!(matches!(text, MathNode::Text(command) if command.name == "text"));
+                assert!(
+                    matches!(text, MathNode::Text(command) if matches!(command.args[0].group().unwrap().children[1], Node::Math(_)))
+                );
+            }
+            node => panic!("unexpected node {node:?}"),
+        }
+    }
+
+    #[test]
+    fn test_from_math() {
+        let source = include_str!("../../../tests/data/minimal.tex");
+        let tree = SyntaxTree::parse(source);
+        let math = tree
+            .document()
+            .unwrap()
+            .children
+            .iter()
+            .find_map(|node| match node {
+                Node::Math(math) => Some(math),
+                _ => None,
+            })
+            .unwrap();
+        let math = MathTree::from_math(math, source);
+        let nodes = first(&math);
+
+        assert_eq!(&source[math.span()], " A = \\pi r^2 ");
+        assert!(
+            matches!(&nodes[3], MathNode::Script(script) if matches!(script.base.as_deref(), Some(MathNode::Atom(_))))
+        );
+    }
+}
@@ -4,9 +4,33 @@
 //!
 //! Most of the content of this takes source from
 //! > "*LATEX2e: An unofficial reference manual*",
-//! written by *latexref.xyz*,
-//! available here: <https://latexref.xyz/dev/latex2e.pdf>.
+//! > written by *latexref.xyz*,
+//! > available here: <https://latexref.xyz/dev/latex2e.pdf>.
+pub mod accent;
+pub mod align;
+pub mod catcode;
+pub mod diagnostic;
+pub mod dimension;
+pub mod edit;
+pub mod encoding;
 pub mod format;
 pub mod highlight;
+pub mod incremental;
+pub mod keyval;
+pub mod lines;
+pub mod macros;
+pub mod math;
+pub mod outline;
 pub mod parse;
+pub mod preamble;
+pub mod project;
+pub mod query;
+pub mod references;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod signature;
+pub mod stream;
+pub mod table;
 pub mod token;
+pub mod verbatim;
+pub mod visit;
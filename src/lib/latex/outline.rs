@@ -0,0 +1,391 @@
+//! Document outline, built from sectioning commands.
+//!
+//! An [`Outline`] is the hierarchy of `\part`, `\chapter`, `\section`, `\subsection`,
+//! `\subsubsection`, `\paragraph` and `\subparagraph` commands found in a [`SyntaxTree`].
+//! Each [`Section`] holds the span of its heading and the span of its body, i.e.,
+//! everything up to the next heading of the same or a higher level.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::outline::{Level, Outline};
+//! use untex::latex::parse::SyntaxTree;
+//!
+//! let source = r"\section{Intro}
+//! Hello.
+//! \subsection*[Short]{A long title}
+//! \section{End}";
+//! let tree = SyntaxTree::parse(source);
+//! let outline = Outline::from_tree(&tree);
+//!
+//! let intro = &outline.sections()[0];
+//! assert_eq!(intro.level, Level::Section);
+//! assert_eq!(intro.title, "Intro");
+//! assert_eq!(&source[intro.body.clone()], "\nHello.\n\\subsection*[Short]{A long title}\n");
+//!
+//! let sub = &intro.children[0];
+//! assert!(sub.starred);
+//! assert_eq!(sub.short_title, Some("Short"));
+//! assert_eq!(outline.sections()[1].title, "End");
+//! ```
+
+use crate::latex::parse::{Argument, Command, Environment, SyntaxTree};
+use crate::latex::token::Span;
+use crate::latex::visit::{walk_environment, Visit};
+use std::fmt;
+
+/// Level of a sectioning command, from the highest to the lowest.
+///
+/// With the `serde` feature, a level is serialized as the name of its command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Level {
+    /// `\part`.
+    Part,
+    /// `\chapter`.
+    Chapter,
+    /// `\section`.
+    Section,
+    /// `\subsection`.
+    Subsection,
+    /// `\subsubsection`.
+    Subsubsection,
+    /// `\paragraph`.
+    Paragraph,
+    /// `\subparagraph`.
+    Subparagraph,
+}
+
+impl Level {
+    /// Return the level matching a command name, without the leading backslash.
+    #[must_use]
+    pub fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "part" => Some(Level::Part),
+            "chapter" => Some(Level::Chapter),
+            "section" => Some(Level::Section),
+            "subsection" => Some(Level::Subsection),
+            "subsubsection" => Some(Level::Subsubsection),
+            "paragraph" => Some(Level::Paragraph),
+            "subparagraph" => Some(Level::Subparagraph),
+            _ => None,
+        }
+    }
+
+    /// Return the name of the sectioning command.
+    #[must_use]
+    pub fn command(&self) -> &'static str {
+        match self {
+            Level::Part => "part",
+            Level::Chapter => "chapter",
+            Level::Section => "section",
+            Level::Subsection => "subsection",
+            Level::Subsubsection => "subsubsection",
+            Level::Paragraph => "paragraph",
+            Level::Subparagraph => "subparagraph",
+        }
+    }
+
+    /// Return the LaTeX sectioning depth, i.e., `-1` for parts, `0` for chapters, and so on.
+    #[must_use]
+    pub fn depth(&self) -> i8 {
+        *self as i8 - 1
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.command())
+    }
+}
+
+/// A section of the outline, with its subsections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Section<'source> {
+    /// Level of the sectioning command.
+    pub level: Level,
+    /// Whether the command is starred, e.g., `\section*{...}`.
+    pub starred: bool,
+    /// Short title, i.e., the optional argument, if any.
+    pub short_title: Option<&'source str>,
+    /// Title, as it appears in the source.
+    pub title: &'source str,
+    /// Span of the sectioning command, arguments included.
+    pub heading: Span,
+    /// Span of the content of the section, from the end of the heading to the
+    /// next heading of the same or a higher level, or to the end of the
+    /// enclosing environment.
+    pub body: Span,
+    /// Subsections.
+    pub children: Vec<Section<'source>>,
+}
+
+impl<'source> Section<'source> {
+    /// Create a new section from a sectioning command, with an empty body.
+    ///
+    /// Return [`None`] if the command is not a sectioning command.
+    #[must_use]
+    pub fn from_command(command: &Command<'source>, source: &'source str) -> Option<Self> {
+        let level = Level::from_command(command.name)?;
+        let mut section = Self {
+            level,
+            starred: false,
+            short_title: None,
+            title: "",
+            heading: command.span.clone(),
+            body: command.span.end..command.span.end,
+            children: Vec::new(),
+        };
+
+        for argument in command.args.iter() {
+            match argument {
+                Argument::Star(_) => section.starred = true,
+                Argument::Optional(group) => {
+                    section.short_title = Some(&source[group.inner_span()])
+                }
+                Argument::Required(group) => section.title = &source[group.inner_span()],
+            }
+        }
+        Some(section)
+    }
+}
+
+/// Collect sectioning commands, in order, with the end of their enclosing environment.
+struct Collector<'source> {
+    source: &'source str,
+    limits: Vec<usize>,
+    sections: Vec<(Section<'source>, usize)>,
+}
+
+impl<'source> Visit<'source> for Collector<'source> {
+    fn visit_command(&mut self, command: &Command<'source>) {
+        if let Some(section) = Section::from_command(command, self.source) {
+            let limit = *self.limits.last().unwrap();
+            self.sections.push((section, limit));
+        }
+    }
+
+    fn visit_environment(&mut self, environment: &Environment<'source>) {
+        let limit = environment
+            .end
+            .as_ref()
+            .map_or(environment.span.end, |end| end.start);
+        self.limits.push(limit);
+        walk_environment(self, environment);
+        self.limits.pop();
+    }
+}
+
+/// Hierarchy of the sections of a document.
+///
+/// With the `serde` feature, an outline is serialized as an array of sections.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize), serde(transparent))]
+pub struct Outline<'source> {
+    sections: Vec<Section<'source>>,
+}
+
+impl<'source> Outline<'source> {
+    /// Build the outline of a syntax tree.
+    #[must_use]
+    pub fn from_tree(tree: &SyntaxTree<'source>) -> Self {
+        let mut collector = Collector {
+            source: tree.source(),
+            limits: vec![tree.source().len()],
+            sections: Vec::new(),
+        };
+        tree.visit(&mut collector);
+
+        // Compute the end of each body.
+        let flat = collector.sections;
+        let ends: Vec<usize> = flat
+            .iter()
+            .enumerate()
+            .map(|(i, (section, limit))| {
+                flat[i + 1..]
+                    .iter()
+                    .find(|(next, _)| next.level <= section.level)
+                    .map_or(*limit, |(next, _)| next.heading.start.min(*limit))
+            })
+            .collect();
+
+        // Nest sections, using a stack of the currently open ones.
+        let mut stack: Vec<Section<'source>> = Vec::new();
+        let mut sections = Vec::new();
+
+        for ((mut section, _), end) in flat.into_iter().zip(ends) {
+            section.body.end = end.max(section.body.start);
+            close_sections(&mut stack, &mut sections, |open| {
+                open.level >= section.level || open.body.end <= section.heading.start
+            });
+            stack.push(section);
+        }
+        close_sections(&mut stack, &mut sections, |_| true);
+
+        Self { sections }
+    }
+
+    /// Return the top-level sections.
+    #[must_use]
+    pub fn sections(&self) -> &[Section<'source>] {
+        &self.sections
+    }
+
+    /// Consume the outline and return the top-level sections.
+    #[must_use]
+    pub fn into_sections(self) -> Vec<Section<'source>> {
+        self.sections
+    }
+
+    /// Return an iterator over all sections, in document order.
+    pub fn iter(&self) -> impl Iterator<Item = &Section<'source>> {
+        let mut stack: Vec<&Section<'source>> = self.sections.iter().rev().collect();
+        std::iter::from_fn(move || {
+            let section = stack.pop()?;
+            stack.extend(section.children.iter().rev());
+            Some(section)
+        })
+    }
+}
+
+/// Pop sections from the stack while `predicate` holds, attaching each one to its parent.
+fn close_sections<'source, P>(
+    stack: &mut Vec<Section<'source>>,
+    sections: &mut Vec<Section<'source>>,
+    predicate: P,
+) where
+    P: Fn(&Section<'source>) -> bool,
+{
+    while stack.last().map_or(false, &predicate) {
+        let section = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(section),
+            None => sections.push(section),
+        }
+    }
+}
+
+/// Write sections as a tree, each line prefixed by `prefix`.
+fn write_tree<W>(sections: &[Section<'_>], prefix: &str, buffer: &mut W) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    for (i, section) in sections.iter().enumerate() {
+        let last = i + 1 == sections.len();
+        let star = if section.starred { "*" } else { "" };
+        writeln!(
+            buffer,
+            "{}{} {}{}: {}",
+            prefix,
+            if last { "└──" } else { "├──" },
+            section.level,
+            star,
+            section.title
+        )?;
+        let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        write_tree(&section.children, &prefix, buffer)?;
+    }
+    Ok(())
+}
+
+impl<'source> fmt::Display for Outline<'source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tree(&self.sections, "", f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimal() {
+        let source = include_str!("../../../tests/data/minimal.tex");
+        let tree = SyntaxTree::parse(source);
+        let outline = Outline::from_tree(&tree);
+
+        assert_eq!(outline.sections().len(), 1);
+        let section = &outline.sections()[0];
+        assert_eq!(section.title, r"Introduction \{of document\}");
+        assert_eq!(section.level, Level::Section);
+        assert!(!section.starred);
+        assert!(source[section.body.clone()].ends_with("\\end{equation}\n\n"));
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let source = r"\part{P}
+\chapter{C1}
+\section{S1}
+\paragraph{Pa}
+\subsection{S11}
+\section*[s2]{S2}
+\chapter{C2}";
+        let outline = Outline::from_tree(&SyntaxTree::parse(source));
+
+        let levels: Vec<_> = outline.iter().map(|s| (s.level, s.title)).collect();
+        assert_eq!(
+            levels,
+            vec![
+                (Level::Part, "P"),
+                (Level::Chapter, "C1"),
+                (Level::Section, "S1"),
+                (Level::Paragraph, "Pa"),
+                (Level::Subsection, "S11"),
+                (Level::Section, "S2"),
+                (Level::Chapter, "C2"),
+            ]
+        );
+
+        let part = &outline.sections()[0];
+        assert_eq!(part.children.len(), 2);
+        assert_eq!(part.body.end, source.len());
+        let c1 = &part.children[0];
+        assert_eq!(c1.children.len(), 2);
+        assert_eq!(c1.children[0].children.len(), 2);
+        assert_eq!(c1.children[1].short_title, Some("s2"));
+        assert!(c1.children[1].starred);
+
+        let paragraph = &c1.children[0].children[0];
+        assert_eq!(&source[paragraph.body.clone()], "\n");
+
+        assert_eq!(
+            outline.to_string(),
+            "└── part: P
+    ├── chapter: C1
+    │   ├── section: S1
+    │   │   ├── paragraph: Pa
+    │   │   └── subsection: S11
+    │   └── section*: S2
+    └── chapter: C2
+"
+        );
+    }
+
+    #[test]
+    fn test_environment_limit() {
+        let source = "\\begin{document}\n\\section{A}\ntext\n\\end{document}\nafter";
+        let outline = Outline::from_tree(&SyntaxTree::parse(source));
+        let section = &outline.sections()[0];
+
+        assert_eq!(&source[section.heading.clone()], "\\section{A}");
+        assert_eq!(&source[section.body.clone()], "\ntext\n");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json() {
+        let outline = Outline::from_tree(&SyntaxTree::parse("\\section[a]{\"b\"}"));
+        let json = serde_json::to_string(&outline).unwrap();
+
+        assert_eq!(
+            json,
+            r#"[{"level":"section","starred":false,"short_title":"a","title":"\"b\"","heading":{"start":0,"end":16},"body":{"start":16,"end":16},"children":[]}]"#
+        );
+    }
+}
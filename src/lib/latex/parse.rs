@@ -1,147 +1,1617 @@
-//! *WIP* Parsing [`Token`](crate::latex::token::Token) iterators into more usable structures.
+//! Parsing [`Token`] iterators into a lossless syntax tree.
+//!
+//! The main structure of this module is [`SyntaxTree`], a list of [`Node`]s
+//! that, together, cover every single byte of the source they were parsed from.
+//! As such, printing a syntax tree (see [`SyntaxTree::write`]) always gives back
+//! the exact source.
+//!
+//! Parsing never fails: unbalanced delimiters are left open
+//! (e.g., [`Group::close`] is [`None`]) and closing delimiters that match nothing
+//! are kept as [`Node::Text`]. Each of those issues is reported as a [`Diagnostic`],
+//! see [`SyntaxTree::diagnostics`].
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::{Node, SyntaxTree};
+//!
+//! let source = r"\section*{Intro} Some $x^2$ text.";
+//! let tree = SyntaxTree::parse(source);
+//!
+//! assert!(matches!(&tree.nodes()[0], Node::Command(cmd) if cmd.name == "section"));
+//! assert_eq!(tree.to_string(), source);
+//! ```
 
-/*
-use crate::error::Result;
-use crate::token::Token;
-use itertools::Itertools;
-use logos::Lexer;
-use std::iter::Peekable;
+use crate::latex::catcode::CatcodeLexer;
+use crate::latex::diagnostic::Diagnostic;
+use crate::latex::incremental::Relexed;
+use crate::latex::macros::{harvest, MacroDefinition};
+use crate::latex::signature::{ArgSpec, Registry, Signature};
+use crate::latex::token::{Span, SpannedToken, Token};
+use crate::latex::verbatim::{VerbatimConfig, VerbatimLexer};
+use crate::latex::visit::{
+    walk_argument_mut, walk_command_mut, walk_environment_mut, walk_group_mut, walk_math_mut,
+    walk_node_mut, VisitMut,
+};
+use std::fmt;
 
-type Span = std::ops::Range<usize>;
+/// Kind of delimiters used by a [`Group`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupKind {
+    /// Curly braces, `{...}`.
+    Brace,
+    /// Square brackets, `[...]`, only used for optional arguments.
+    Bracket,
+}
+
+/// A group of nodes, delimited by braces or brackets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Group<'source> {
+    /// Delimiters kind.
+    pub kind: GroupKind,
+    /// Span of the opening delimiter.
+    pub open: Span,
+    /// Nodes inside the group.
+    pub children: Vec<Node<'source>>,
+    /// Span of the closing delimiter, if any.
+    pub close: Option<Span>,
+    /// Span of the whole group, delimiters included.
+    pub span: Span,
+}
+
+impl<'source> Group<'source> {
+    /// Return the span of the content, delimiters excluded.
+    #[must_use]
+    pub fn inner_span(&self) -> Span {
+        let end = self
+            .close
+            .as_ref()
+            .map_or(self.span.end, |close| close.start);
+        self.open.end..end
+    }
+}
+
+/// An argument of a [`Command`] or an [`Environment`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Argument<'source> {
+    /// A star `'*'`, e.g., in `\section*{...}`.
+    Star(Span),
+    /// An optional argument, delimited by brackets.
+    Optional(Group<'source>),
+    /// A required argument, delimited by braces.
+    Required(Group<'source>),
+}
+
+impl<'source> Argument<'source> {
+    /// Return the span of this argument.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            Argument::Star(span) => span.clone(),
+            Argument::Optional(group) | Argument::Required(group) => group.span.clone(),
+        }
+    }
 
-pub trait TryFromTokens {
-    fn try_from_tokens<'source, I>(source: &'source str, iter: &mut Peekable<I>) -> Result<Self>
+    /// Return the group of this argument, if any.
+    #[must_use]
+    pub fn group(&self) -> Option<&Group<'source>> {
+        match self {
+            Argument::Star(_) => None,
+            Argument::Optional(group) | Argument::Required(group) => Some(group),
+        }
+    }
+}
+
+/// A command, e.g., `\section*[short]{Long}`, with its arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Command<'source> {
+    /// Command name, without the leading backslash nor trailing spaces.
+    pub name: &'source str,
+    /// Span of the command name, leading backslash and trailing spaces included.
+    pub name_span: Span,
+    /// Arguments following the command name.
+    pub args: Vec<Argument<'source>>,
+    /// Span of the whole command, arguments included.
+    pub span: Span,
+}
+
+/// An environment, from `\begin{name}` to `\end{name}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Environment<'source> {
+    /// Environment name.
+    pub name: &'source str,
+    /// Span of the `\begin{name}` token.
+    pub begin: Span,
+    /// Arguments following `\begin{name}`.
+    pub args: Vec<Argument<'source>>,
+    /// Nodes inside the environment.
+    pub children: Vec<Node<'source>>,
+    /// Span of the `\end{name}` token, if any.
+    pub end: Option<Span>,
+    /// Span of the whole environment.
+    pub span: Span,
+}
+
+impl<'source> Environment<'source> {
+    /// Return the span of the content, i.e., after the arguments and before `\end{name}`.
+    #[must_use]
+    pub fn inner_span(&self) -> Span {
+        let start = self
+            .args
+            .last()
+            .map_or(self.begin.end, |arg| arg.span().end);
+        let end = self.end.as_ref().map_or(self.span.end, |end| end.start);
+        start..end.max(start)
+    }
+
+    /// Return whether this environment typesets its content in math mode.
+    #[must_use]
+    pub fn is_math(&self) -> bool {
+        matches!(
+            self.name.trim_end_matches('*'),
+            "equation"
+                | "align"
+                | "alignat"
+                | "gather"
+                | "multline"
+                | "flalign"
+                | "displaymath"
+                | "math"
+                | "eqnarray"
+        )
+    }
+}
+
+/// Delimiters used to enter math mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathKind {
+    /// Inline math, with `$...$`.
+    Dollar,
+    /// Display math, with `$$...$$`.
+    DoubleDollar,
+    /// Inline math, with `\(...\)`.
+    Parenthesis,
+    /// Display math, with `\[...\]`.
+    Bracket,
+}
+
+impl MathKind {
+    /// Return whether this math is displayed on its own line.
+    #[must_use]
+    pub fn is_display(&self) -> bool {
+        matches!(self, MathKind::DoubleDollar | MathKind::Bracket)
+    }
+
+    /// Return whether this math is inlined in the text.
+    #[must_use]
+    pub fn is_inline(&self) -> bool {
+        !self.is_display()
+    }
+}
+
+/// A math region, delimited by one of the [`MathKind`]s.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Math<'source> {
+    /// Delimiters kind.
+    pub kind: MathKind,
+    /// Span of the opening delimiter.
+    pub open: Span,
+    /// Nodes inside the math region.
+    pub children: Vec<Node<'source>>,
+    /// Span of the closing delimiter, if any.
+    pub close: Option<Span>,
+    /// Span of the whole math region, delimiters included.
+    pub span: Span,
+}
+
+impl<'source> Math<'source> {
+    /// Return the span of the content, delimiters excluded.
+    #[must_use]
+    pub fn inner_span(&self) -> Span {
+        let end = self
+            .close
+            .as_ref()
+            .map_or(self.span.end, |close| close.start);
+        self.open.end..end
+    }
+}
+
+/// Enumerates all nodes of a [`SyntaxTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node<'source> {
+    /// A command, see [`Command`].
+    Command(Command<'source>),
+    /// A brace group that is not an argument, see [`Group`].
+    Group(Group<'source>),
+    /// An environment, see [`Environment`].
+    Environment(Environment<'source>),
+    /// A math region, see [`Math`].
+    Math(Math<'source>),
+    /// A comment, from `'%'` up to the end of the line (excluded).
+    Comment(Span),
+    /// Any run of tokens without special meaning.
+    Text(Span),
+    /// Text replacing the node originally located at some span,
+    /// see [`Node::replace_with`].
+    ///
+    /// Replacements are never produced by the parser.
+    Replacement(Span, String),
+}
+
+impl<'source> Node<'source> {
+    /// Return the span of this node.
+    ///
+    /// For a [`Node::Replacement`], this is the span of the replaced node.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Command(command) => command.span.clone(),
+            Node::Group(group) => group.span.clone(),
+            Node::Environment(environment) => environment.span.clone(),
+            Node::Math(math) => math.span.clone(),
+            Node::Comment(span) | Node::Text(span) | Node::Replacement(span, _) => span.clone(),
+        }
+    }
+
+    /// Replace this node with some text.
+    ///
+    /// An empty text removes the node from the output.
+    pub fn replace_with<S>(&mut self, text: S)
     where
-        Self: Sized,
-        I: Iterator<Item = (Token, Span)>;
+        S: Into<String>,
+    {
+        *self = Node::Replacement(self.span(), text.into());
+    }
 
-    fn try_from_lexer<'source>(lexer: Lexer<'source, Token>) -> Result<Self>
+    /// Write this node to the buffer.
+    ///
+    /// Untouched nodes are written exactly as they appear in the source.
+    /// Renamed commands and environments, i.e., whose `name` does not match
+    /// the source anymore, are written with their new name.
+    pub fn write<W>(&self, source: &str, buffer: &mut W) -> fmt::Result
     where
-        Self: Sized,
+        W: fmt::Write,
     {
-        let source = lexer.source();
-        let mut iter = lexer.spanned().peekable();
-        <Self as TryFromTokens>::try_from_tokens(source, &mut iter)
+        match self {
+            Node::Command(command) => {
+                if command_name(&source[command.name_span.clone()]) == command.name {
+                    buffer.write_str(&source[command.name_span.clone()])?;
+                } else {
+                    write!(buffer, "\\{}", command.name)?;
+                }
+                write_args(&command.args, source, command.name_span.end, buffer)
+            }
+            Node::Group(group) => write_group(group, source, buffer),
+            Node::Environment(environment) => {
+                let renamed =
+                    environment_name(&source[environment.begin.clone()]) != environment.name;
+                if renamed {
+                    write!(buffer, "\\begin{{{}}}", environment.name)?;
+                } else {
+                    buffer.write_str(&source[environment.begin.clone()])?;
+                }
+                write_args(&environment.args, source, environment.begin.end, buffer)?;
+                write_children(&environment.children, source, buffer)?;
+                match &environment.end {
+                    Some(_) if renamed => write!(buffer, "\\end{{{}}}", environment.name),
+                    Some(end) => buffer.write_str(&source[end.clone()]),
+                    None => Ok(()),
+                }
+            }
+            Node::Math(math) => {
+                buffer.write_str(&source[math.open.clone()])?;
+                write_children(&math.children, source, buffer)?;
+                write_close(&math.close, source, buffer)
+            }
+            Node::Comment(span) | Node::Text(span) => buffer.write_str(&source[span.clone()]),
+            Node::Replacement(_, text) => buffer.write_str(text),
+        }
+    }
+}
+
+/// Return the command name from a `\name` slice, without the leading backslash.
+///
+/// Trailing spaces, e.g., in `"\\end  "`, are not part of the name,
+/// unless the name is made of whitespace only, e.g., in `"\\ "`.
+fn command_name(slice: &str) -> &str {
+    match slice[1..].trim_end() {
+        "" => &slice[1..],
+        name => name,
+    }
+}
+
+/// Return the environment name from a `\begin{name}` or `\end{name}` slice.
+fn environment_name(slice: &str) -> &str {
+    match (slice.find('{'), slice.rfind('}')) {
+        (Some(start), Some(end)) if start < end => slice[start + 1..end].trim(),
+        _ => slice,
+    }
+}
+
+/// Write the children nodes.
+fn write_children<W>(children: &[Node<'_>], source: &str, buffer: &mut W) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    for child in children.iter() {
+        child.write(source, buffer)?;
     }
+    Ok(())
 }
 
-pub struct LaTeXDocument {
-    preamble: Preamble,
-    document: Document,
+/// Write the closing delimiter, if any.
+fn write_close<W>(close: &Option<Span>, source: &str, buffer: &mut W) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    match close {
+        Some(span) => buffer.write_str(&source[span.clone()]),
+        None => Ok(()),
+    }
 }
 
-impl TryFromTokens for LaTeXDocument {
-    fn try_from_tokens<'source, I>(source: &'source str, iter: &mut Peekable<I>) -> Result<Self>
+/// Write a group, with its delimiters.
+fn write_group<W>(group: &Group<'_>, source: &str, buffer: &mut W) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    buffer.write_str(&source[group.open.clone()])?;
+    write_children(&group.children, source, buffer)?;
+    write_close(&group.close, source, buffer)
+}
+
+/// Write arguments, each preceded by the source text (i.e., spaces) between `pos`
+/// and the argument.
+fn write_args<W>(args: &[Argument<'_>], source: &str, mut pos: usize, buffer: &mut W) -> fmt::Result
+where
+    W: fmt::Write,
+{
+    for arg in args.iter() {
+        let span = arg.span();
+        if pos <= span.start {
+            buffer.write_str(&source[pos..span.start])?;
+        }
+        match arg {
+            Argument::Star(span) => buffer.write_str(&source[span.clone()])?,
+            Argument::Optional(group) | Argument::Required(group) => {
+                write_group(group, source, buffer)?
+            }
+        }
+        pos = span.end;
+    }
+    Ok(())
+}
+
+/// Lex a source with [`CatcodeLexer`] and [`VerbatimLexer`], and return its tokens
+/// and the macros it defines.
+///
+/// The verbatim environments defined in the source are only known once it is lexed,
+/// so it is lexed twice.
+#[must_use]
+pub fn lex(source: &str) -> (Vec<SpannedToken<'_>>, Vec<MacroDefinition<'_>>) {
+    let mut config = VerbatimConfig::default();
+    let definitions = harvest(
+        source,
+        CatcodeLexer::new(source, VerbatimLexer::new(source, &config)),
+    );
+    config.extend(&definitions);
+    let tokens = CatcodeLexer::new(source, VerbatimLexer::new(source, &config)).collect();
+    (tokens, definitions)
+}
+
+/// A lossless syntax tree, built from a [`Token`] stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxTree<'source> {
+    source: &'source str,
+    nodes: Vec<Node<'source>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'source> SyntaxTree<'source> {
+    /// Lex and parse a source into a syntax tree, using LaTeX2e signatures
+    /// and the signatures of macros defined in the source (see [`harvest`]).
+    ///
+    /// Command names follow `\makeatletter` and `\ExplSyntaxOn` (see [`CatcodeLexer`]),
+    /// and the content of verbatim regions, including environments defined in the source,
+    /// is kept as text (see [`VerbatimLexer`]).
+    #[must_use]
+    pub fn parse(source: &'source str) -> Self {
+        let (tokens, definitions) = lex(source);
+        let mut registry = Registry::default();
+        registry.extend(&definitions);
+        Self::from_tokens_with(source, tokens, &registry)
+    }
+
+    /// Lex and parse a source into a syntax tree, using the signatures from `registry`.
+    ///
+    /// Command names follow `\makeatletter` and `\ExplSyntaxOn` (see [`CatcodeLexer`]),
+    /// and the content of verbatim regions, including environments defined in the source,
+    /// is kept as text (see [`VerbatimLexer`]).
+    #[must_use]
+    pub fn parse_with(source: &'source str, registry: &Registry) -> Self {
+        let (tokens, _) = lex(source);
+        Self::from_tokens_with(source, tokens, registry)
+    }
+
+    /// Parse a [`SpannedToken`] iterator, obtained from `source`, into a syntax tree,
+    /// using LaTeX2e signatures and the signatures of macros defined in the source
+    /// (see [`harvest`]).
+    pub fn from_tokens<I>(source: &'source str, iter: I) -> Self
     where
-        I: Iterator<Item = (Token, Span)>,
+        I: IntoIterator<Item = SpannedToken<'source>>,
     {
-        let preamble = Preamble::try_from_tokens(source, iter)?;
-        let document = Document::try_from_tokens(source, iter)?;
+        let tokens: Vec<_> = iter.into_iter().collect();
+        let mut registry = Registry::default();
+        registry.extend(&harvest(source, tokens.iter().cloned()));
+        Self::from_tokens_with(source, tokens, &registry)
+    }
 
-        Ok(Self { preamble, document })
+    /// Parse a [`SpannedToken`] iterator, obtained from `source`, into a syntax tree,
+    /// using the signatures from `registry`.
+    pub fn from_tokens_with<I>(source: &'source str, iter: I, registry: &Registry) -> Self
+    where
+        I: IntoIterator<Item = SpannedToken<'source>>,
+    {
+        let tokens: Vec<_> = iter.into_iter().collect();
+        Parser::new(source, &tokens, registry).parse()
     }
-}
 
-struct Preamble {}
+    /// Update this tree after an edit, see [`latex::incremental`](crate::latex::incremental).
+    ///
+    /// `source` must be the edited source, `tokens` its tokens, as updated by
+    /// [`relex`](crate::latex::incremental::relex), and `registry` the signatures
+    /// this tree was parsed with, e.g., with [`SyntaxTree::from_tokens_with`].
+    ///
+    /// Only the content of the innermost closed group, math region or environment
+    /// enclosing the changed tokens is parsed again, and the spans of the nodes after it
+    /// are shifted. If there is no such node, or if the changed tokens modify
+    /// how it is closed, the whole source is parsed again.
+    ///
+    /// Return the span, in the edited source, that was parsed again.
+    ///
+    /// Definitions of macros, see [`harvest`], are not updated: if the edit changes
+    /// them, the whole source should be parsed again.
+    pub fn reparse_with(
+        &mut self,
+        source: &'source str,
+        tokens: &[SpannedToken<'source>],
+        relexed: &Relexed,
+        registry: &Registry,
+    ) -> Span {
+        let damaged = &relexed.replaced_span;
+        let mut shift = Shift {
+            from: damaged.end,
+            delta: relexed.delta(),
+        };
+        let mut candidates = Vec::new();
+        find_candidates(
+            &self.nodes,
+            damaged,
+            tokens,
+            &mut Vec::new(),
+            &mut Vec::new(),
+            &mut candidates,
+        );
+
+        // From the innermost node to the outermost one
+        for candidate in candidates.into_iter().rev() {
+            let close_start = shift.start(candidate.inner.end);
+            let start = tokens.partition_point(|(_, span)| span.start < candidate.inner.start);
+            let end = tokens.partition_point(|(_, span)| span.start < close_start);
+
+            let mut parser = Parser::new(source, tokens, registry);
+            parser.pos = start;
+            parser.stack = candidate.stack;
+            let children = parser.parse_nodes();
+            let top = parser.stack.len() - 1;
+
+            match parser.peek() {
+                Some((token, _)) if parser.pos == end && parser.closes(token) == Some(top) => (),
+                _ => continue,
+            }
+
+            self.visit_mut(&mut shift);
+            *children_mut(&mut self.nodes, &candidate.path) = children;
+
+            let inner = &candidate.inner;
+            self.diagnostics.retain(|diagnostic| {
+                diagnostic.span.start < inner.start || inner.end < diagnostic.span.end
+            });
+            for diagnostic in self.diagnostics.iter_mut() {
+                shift.span(&mut diagnostic.span);
+                for span in diagnostic.related_spans.iter_mut() {
+                    shift.span(span);
+                }
+            }
+            let i = self
+                .diagnostics
+                .partition_point(|diagnostic| diagnostic.span.start < inner.start);
+            self.diagnostics.splice(i..i, parser.diagnostics);
+            self.source = source;
+
+            return inner.start..close_start;
+        }
+
+        *self = Parser::new(source, tokens, registry).parse();
+        0..source.len()
+    }
+
+    /// Return the source this tree was parsed from.
+    #[must_use]
+    pub fn source(&self) -> &'source str {
+        self.source
+    }
+
+    /// Return the top-level nodes.
+    #[must_use]
+    pub fn nodes(&self) -> &[Node<'source>] {
+        &self.nodes
+    }
+
+    /// Return the top-level nodes, as mutable.
+    #[must_use]
+    pub fn nodes_mut(&mut self) -> &mut Vec<Node<'source>> {
+        &mut self.nodes
+    }
+
+    /// Consume the tree and return the top-level nodes.
+    #[must_use]
+    pub fn into_nodes(self) -> Vec<Node<'source>> {
+        self.nodes
+    }
 
-impl TryFromTokens for Preamble {
-    fn try_from_tokens<'source, I>(source: &'source str, iter: &mut Peekable<I>) -> Result<Self>
+    /// Return the diagnostics reported while parsing.
+    #[must_use]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Return the `document` environment, if any.
+    #[must_use]
+    pub fn document(&self) -> Option<&Environment<'source>> {
+        self.nodes.iter().find_map(|node| match node {
+            Node::Environment(environment) if environment.name == "document" => Some(environment),
+            _ => None,
+        })
+    }
+
+    /// Write the tree to the buffer.
+    ///
+    /// Unless nodes were modified, see [`SyntaxTree::nodes_mut`], this writes
+    /// the tree exactly as it appears in the source.
+    pub fn write<W>(&self, buffer: &mut W) -> fmt::Result
     where
-        I: Iterator<Item = (Token, Span)>,
+        W: fmt::Write,
     {
-        match iter
-            .skip_while(|(token, _)| {
-                matches!(token, Token::Comment | Token::Newline | Token::TabsOrSpaces)
-            })
-            .next()
-            .expect("Preamble should start with a \\documentclass, but nothing was found")
+        write_children(&self.nodes, self.source, buffer)
+    }
+}
+
+impl<'source> fmt::Display for SyntaxTree<'source> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+/// A step from a list of nodes to the children of one of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    /// The children of the i-th node, a group, an environment or a math region.
+    Children(usize),
+    /// The children of the group of the j-th argument of the i-th node.
+    Argument(usize, usize),
+}
+
+/// A node whose children can be parsed again, see [`SyntaxTree::reparse_with`].
+#[derive(Clone, Debug)]
+struct Candidate<'source> {
+    /// Path from the top-level nodes to the children.
+    path: Vec<Step>,
+    /// Parsing contexts, from the outermost to this node.
+    stack: Vec<Context<'source>>,
+    /// Span of the children, from the end of the opening delimiter (or of the arguments)
+    /// to the start of the closing one.
+    inner: Span,
+}
+
+/// Find the closed nodes enclosing a damaged span, from the outermost to the innermost one.
+///
+/// `tokens` are the tokens after the edit, which are identical before the damaged span.
+fn find_candidates<'source>(
+    nodes: &[Node<'source>],
+    damaged: &Span,
+    tokens: &[SpannedToken<'source>],
+    path: &mut Vec<Step>,
+    stack: &mut Vec<Context<'source>>,
+    candidates: &mut Vec<Candidate<'source>>,
+) {
+    let encloses = |inner: &Span| inner.start <= damaged.start && damaged.end <= inner.end;
+    let i = match nodes.iter().position(|node| encloses(&node.span())) {
+        Some(i) => i,
+        None => return,
+    };
+    let mut enter = |step, context, inner: Span, children: &[Node<'source>]| {
+        if encloses(&inner) {
+            path.push(step);
+            stack.push(context);
+            candidates.push(Candidate {
+                path: path.clone(),
+                stack: stack.clone(),
+                inner,
+            });
+            find_candidates(children, damaged, tokens, path, stack, candidates);
+            stack.pop();
+            path.pop();
+        }
+    };
+
+    match &nodes[i] {
+        Node::Command(Command { args, .. }) | Node::Environment(Environment { args, .. })
+            if args.iter().any(|arg| encloses(&arg.span())) =>
         {
-            (Token::DocumentClass, _) => (),
-            (token, span) => panic!(
-                "Preamble should start with a \\documentclass, not with {:#?}: {}",
-                token, &source[span]
-            ),
+            let j = args.iter().position(|arg| encloses(&arg.span())).unwrap();
+            if let Some(Group {
+                kind,
+                open,
+                children,
+                close: Some(close),
+                ..
+            }) = args[j].group()
+            {
+                enter(
+                    Step::Argument(i, j),
+                    Context::Group(*kind),
+                    open.end..close.start,
+                    children,
+                );
+            }
         }
+        Node::Group(Group {
+            kind,
+            open,
+            children,
+            close: Some(close),
+            ..
+        }) => enter(
+            Step::Children(i),
+            Context::Group(*kind),
+            open.end..close.start,
+            children,
+        ),
+        Node::Math(Math {
+            kind,
+            open,
+            children,
+            close: Some(close),
+            ..
+        }) => enter(
+            Step::Children(i),
+            Context::Math(*kind),
+            open.end..close.start,
+            children,
+        ),
+        Node::Environment(environment) => {
+            let start = environment
+                .args
+                .last()
+                .map_or(environment.begin.end, |arg| arg.span().end);
 
-        while let Some((token, _span)) = iter.peek() {
-            match token {
-                // If \begin is found, we assumed it is \begin{document}
-                Token::EnvironmentBegin => return Ok(Self {}),
-                _ => (),
+            // Changes before the first token, except spaces, could add arguments
+            let first = tokens[tokens.partition_point(|(_, span)| span.start < start)..]
+                .iter()
+                .find(|(token, _)| !matches!(token, Token::TabsOrSpaces | Token::Newline));
+            let unchanged = matches!(first, Some((_, span)) if span.start < damaged.start);
+
+            if let (Some(end), true) = (&environment.end, unchanged) {
+                enter(
+                    Step::Children(i),
+                    Context::Environment(environment.name),
+                    start..end.start,
+                    &environment.children,
+                );
             }
-            iter.next();
         }
-        Ok(Self {})
+        _ => (),
     }
 }
 
-struct Document {}
+/// Return the children at the end of a path, see [`find_candidates`].
+fn children_mut<'a, 'source>(
+    nodes: &'a mut Vec<Node<'source>>,
+    path: &[Step],
+) -> &'a mut Vec<Node<'source>> {
+    let (step, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return nodes,
+    };
+    let children = match (*step, &mut nodes[step.index()]) {
+        (Step::Children(_), Node::Group(Group { children, .. }))
+        | (Step::Children(_), Node::Environment(Environment { children, .. }))
+        | (Step::Children(_), Node::Math(Math { children, .. })) => children,
+        (Step::Argument(_, j), Node::Command(Command { args, .. }))
+        | (Step::Argument(_, j), Node::Environment(Environment { args, .. })) => {
+            match &mut args[j] {
+                Argument::Optional(group) | Argument::Required(group) => &mut group.children,
+                Argument::Star(_) => unreachable!("a star argument has no children"),
+            }
+        }
+        _ => unreachable!("path should lead to children"),
+    };
+    children_mut(children, rest)
+}
 
-impl TryFromTokens for Document {
-    fn try_from_tokens<'source, I>(source: &'source str, iter: &mut Peekable<I>) -> Result<Self>
-    where
-        I: Iterator<Item = (Token, Span)>,
-    {
-        let mut iter = iter.skip_while(|(token, _)| {
-            matches!(token, Token::Comment | Token::Newline | Token::TabsOrSpaces)
-        });
+impl Step {
+    /// Return the index of the node this step enters.
+    fn index(&self) -> usize {
+        match self {
+            Step::Children(i) | Step::Argument(i, _) => *i,
+        }
+    }
+}
+
+/// Shift spans after `from` by `delta`.
+///
+/// Spans ending at `from` are before it, and spans starting at `from` are after it.
+struct Shift {
+    from: usize,
+    delta: isize,
+}
+
+impl Shift {
+    fn start(&self, start: usize) -> usize {
+        if start >= self.from {
+            (start as isize + self.delta) as usize
+        } else {
+            start
+        }
+    }
+
+    fn span(&self, span: &mut Span) {
+        let end = if span.end > self.from {
+            (span.end as isize + self.delta) as usize
+        } else {
+            span.end
+        };
+        *span = self.start(span.start)..end.max(self.start(span.start));
+    }
+
+    fn option(&self, span: &mut Option<Span>) {
+        if let Some(span) = span {
+            self.span(span);
+        }
+    }
+}
+
+impl<'source> VisitMut<'source> for Shift {
+    fn visit_node(&mut self, node: &mut Node<'source>) {
+        // Nodes before the edit are left untouched
+        if node.span().end >= self.from {
+            walk_node_mut(self, node)
+        }
+    }
+
+    fn visit_command(&mut self, command: &mut Command<'source>) {
+        self.span(&mut command.name_span);
+        self.span(&mut command.span);
+        walk_command_mut(self, command)
+    }
+
+    fn visit_argument(&mut self, argument: &mut Argument<'source>) {
+        if let Argument::Star(span) = argument {
+            self.span(span);
+        }
+        walk_argument_mut(self, argument)
+    }
+
+    fn visit_group(&mut self, group: &mut Group<'source>) {
+        self.span(&mut group.open);
+        self.option(&mut group.close);
+        self.span(&mut group.span);
+        walk_group_mut(self, group)
+    }
+
+    fn visit_environment(&mut self, environment: &mut Environment<'source>) {
+        self.span(&mut environment.begin);
+        self.option(&mut environment.end);
+        self.span(&mut environment.span);
+        walk_environment_mut(self, environment)
+    }
+
+    fn visit_math(&mut self, math: &mut Math<'source>) {
+        self.span(&mut math.open);
+        self.option(&mut math.close);
+        self.span(&mut math.span);
+        walk_math_mut(self, math)
+    }
+
+    fn visit_comment(&mut self, span: &mut Span) {
+        self.span(span);
+    }
+
+    fn visit_text(&mut self, span: &mut Span) {
+        self.span(span);
+    }
+
+    fn visit_replacement(&mut self, span: &mut Span, _text: &mut String) {
+        self.span(span);
+    }
+}
+
+/// Parsing context, i.e., what delimiter is currently open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Context<'source> {
+    Group(GroupKind),
+    Environment(&'source str),
+    Math(MathKind),
+}
+
+/// Recursive descent parser, turning tokens into [`Node`]s.
+#[derive(Debug)]
+struct Parser<'source, 'tokens, 'registry> {
+    source: &'source str,
+    tokens: &'tokens [SpannedToken<'source>],
+    pos: usize,
+    stack: Vec<Context<'source>>,
+    registry: &'registry Registry,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'source, 'tokens, 'registry> Parser<'source, 'tokens, 'registry> {
+    fn new(
+        source: &'source str,
+        tokens: &'tokens [SpannedToken<'source>],
+        registry: &'registry Registry,
+    ) -> Self {
+        Self {
+            source,
+            tokens,
+            pos: 0,
+            stack: Vec::new(),
+            registry,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> SyntaxTree<'source> {
+        // With an empty context stack, no token is a closing one,
+        // so every token is consumed.
+        let nodes = self.parse_nodes();
+        self.check_document(&nodes);
+
+        SyntaxTree {
+            source: self.source,
+            nodes,
+            diagnostics: self.diagnostics,
+        }
+    }
 
-        match iter
-            .next_tuple()
-            .expect("Document should start with a \\begin{{document}}, but nothing was found")
+    /// Check the structure of a complete document, i.e., with a `document` environment.
+    fn check_document(&mut self, nodes: &[Node<'source>]) {
+        let index = match nodes.iter().position(
+            |node| matches!(node, Node::Environment(environment) if environment.name == "document"),
+        ) {
+            Some(index) => index,
+            None => return,
+        };
+
+        if !nodes[..index]
+            .iter()
+            .any(|node| matches!(node, Node::Command(command) if command.name == "documentclass"))
         {
-            (
-                (Token::EnvironmentBegin, _),
-                (Token::BraceOpen, _),
-                (Token::Word, word_span),
-                (Token::BraceClose, _),
-            ) if &source[word_span.clone()] == "document" => (),
-            _ => panic!("Document should start with a \\begin{{document}}"),
+            self.diagnostics.push(Diagnostic::warning(
+                nodes[index].span(),
+                r"missing `\documentclass` before `\begin{document}`",
+            ));
+        }
+
+        for node in nodes[index + 1..].iter() {
+            match node {
+                Node::Comment(_) => (),
+                Node::Text(span) if self.source[span.clone()].trim().is_empty() => (),
+                node => self.diagnostics.push(Diagnostic::warning(
+                    node.span(),
+                    r"content after `\end{document}` is ignored",
+                )),
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&SpannedToken<'source>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_token(&self) -> Option<&Token<'source>> {
+        self.peek().map(|(token, _)| token)
+    }
+
+    fn bump(&mut self) -> Option<SpannedToken<'source>> {
+        let token = self.tokens.get(self.pos).cloned();
+        if token.is_some() {
+            self.pos += 1;
         }
+        token
+    }
+
+    /// Return the end of the last consumed token.
+    fn last_end(&self) -> usize {
+        self.pos
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map_or(0, |(_, span)| span.end)
+    }
+
+    /// Return the index, in the context stack, of the context closed by `token`, if any.
+    fn closes(&self, token: &Token<'source>) -> Option<usize> {
+        let top = self.stack.len().checked_sub(1)?;
+
+        match token {
+            Token::BraceClose => {
+                for (i, context) in self.stack.iter().enumerate().rev() {
+                    match context {
+                        Context::Group(GroupKind::Brace) => return Some(i),
+                        Context::Environment(_) => return None,
+                        _ => (),
+                    }
+                }
+                None
+            }
+            Token::BracketClose => {
+                (self.stack[top] == Context::Group(GroupKind::Bracket)).then_some(top)
+            }
+            Token::EnvironmentEnd(name) => self
+                .stack
+                .iter()
+                .rposition(|context| context == &Context::Environment(name)),
+            Token::DollarSign => {
+                (self.stack[top] == Context::Math(MathKind::Dollar)).then_some(top)
+            }
+            Token::DoubleDollarSign => {
+                (self.stack[top] == Context::Math(MathKind::DoubleDollar)).then_some(top)
+            }
+            Token::InlineMathClose | Token::DisplayMathClose => {
+                let kind = if token == &Token::InlineMathClose {
+                    MathKind::Parenthesis
+                } else {
+                    MathKind::Bracket
+                };
+                for (i, context) in self.stack.iter().enumerate().rev() {
+                    match context {
+                        Context::Math(k) if *k == kind => return Some(i),
+                        Context::Environment(_) => return None,
+                        _ => (),
+                    }
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Parse nodes until a token closing the current (or any outer) context is found.
+    ///
+    /// The closing token is not consumed.
+    fn parse_nodes(&mut self) -> Vec<Node<'source>> {
+        let mut nodes = Vec::new();
+
+        while let Some((token, span)) = self.peek() {
+            if self.closes(token).is_some() {
+                break;
+            }
+            let span = span.clone();
 
-        while let Some((token, span)) = iter.next() {
             match token {
-                Token::EnvironmentEnd => {
-                    if let Some((token, span)) = iter
-                        .skip_while(|(token, _)| {
-                            matches!(token, Token::Comment | Token::Newline | Token::TabsOrSpaces)
-                        })
-                        .next()
-                    {
-                        panic!(
-                            "Unexpected token found after \\end{{document}}: {}",
-                            &source[span]
-                        );
+                Token::CommandName
+                | Token::DocumentClass
+                | Token::InvalidCommand
+                | Token::DoubleBackslash => {
+                    let command = self.parse_command();
+                    nodes.push(Node::Command(command));
+                }
+                Token::BraceOpen => {
+                    let group = self.parse_group(GroupKind::Brace);
+                    nodes.push(Node::Group(group));
+                }
+                Token::EnvironmentBegin(_) => {
+                    let environment = self.parse_environment();
+                    nodes.push(Node::Environment(environment));
+                }
+                Token::DollarSign => nodes.push(Node::Math(self.parse_math(MathKind::Dollar))),
+                Token::DoubleDollarSign => {
+                    nodes.push(Node::Math(self.parse_math(MathKind::DoubleDollar)))
+                }
+                Token::InlineMathOpen => {
+                    nodes.push(Node::Math(self.parse_math(MathKind::Parenthesis)))
+                }
+                Token::DisplayMathOpen => {
+                    nodes.push(Node::Math(self.parse_math(MathKind::Bracket)))
+                }
+                Token::Comment => {
+                    self.bump();
+                    nodes.push(Node::Comment(span));
+                }
+                _ => {
+                    if let Some(message) = unmatched_message(token) {
+                        let diagnostic = Diagnostic::error(span.clone(), message);
+                        self.diagnostics.push(diagnostic);
+                    }
+                    self.bump();
+                    push_text(&mut nodes, span);
+                }
+            }
+        }
+
+        nodes
+    }
+
+    /// Parse a group, the current token being its opening delimiter.
+    fn parse_group(&mut self, kind: GroupKind) -> Group<'source> {
+        let (_, open) = self.bump().expect("group should start with a delimiter");
+        let (children, close) = self.parse_delimited(Context::Group(kind), &open);
+        let end = close
+            .as_ref()
+            .map_or_else(|| self.last_end(), |close| close.end);
+
+        Group {
+            kind,
+            span: open.start..end.max(open.end),
+            open,
+            children,
+            close,
+        }
+    }
+
+    /// Parse a math region, the current token being its opening delimiter.
+    fn parse_math(&mut self, kind: MathKind) -> Math<'source> {
+        let (_, open) = self.bump().expect("math should start with a delimiter");
+        let (children, close) = self.parse_delimited(Context::Math(kind), &open);
+        let end = close
+            .as_ref()
+            .map_or_else(|| self.last_end(), |close| close.end);
+
+        Math {
+            kind,
+            span: open.start..end.max(open.end),
+            open,
+            children,
+            close,
+        }
+    }
+
+    /// Parse an environment, the current token being `\begin{name}`.
+    fn parse_environment(&mut self) -> Environment<'source> {
+        let (token, begin) = self.bump().expect("environment should start with \\begin");
+        let name = match token {
+            Token::EnvironmentBegin(name) => name,
+            _ => unreachable!("environment should start with \\begin"),
+        };
+        let args = match self.registry.environment(name) {
+            Some(signature) => self.parse_args_with(signature),
+            None => self.parse_args(),
+        };
+        let (children, end) = self.parse_delimited(Context::Environment(name), &begin);
+        let span_end = end.as_ref().map_or_else(|| self.last_end(), |end| end.end);
+
+        Environment {
+            name,
+            span: begin.start..span_end.max(begin.end),
+            begin,
+            args,
+            children,
+            end,
+        }
+    }
+
+    /// Parse nodes within a given context, and consume the closing token if it
+    /// closes this context.
+    ///
+    /// If the context is not closed, a diagnostic is reported on the opening span.
+    fn parse_delimited(
+        &mut self,
+        context: Context<'source>,
+        open: &Span,
+    ) -> (Vec<Node<'source>>, Option<Span>) {
+        self.stack.push(context);
+        let children = self.parse_nodes();
+        let depth = self.stack.len() - 1;
+
+        let close = match self.peek() {
+            Some((token, _)) if self.closes(token) == Some(depth) => {
+                self.bump().map(|(_, span)| span)
+            }
+            next => {
+                let opening = &self.source[open.clone()];
+                let diagnostic = match (context, next) {
+                    (Context::Environment(_), Some((Token::EnvironmentEnd(_), span))) => {
+                        Diagnostic::error(
+                            open.clone(),
+                            format!("`{opening}` is closed by `{}`", &self.source[span.clone()]),
+                        )
+                        .with_related(span.clone())
                     }
-                    return Ok(Self {});
+                    (_, Some((_, span))) => Diagnostic::error(
+                        open.clone(),
+                        format!(
+                            "unclosed `{opening}` before `{}`",
+                            &self.source[span.clone()]
+                        ),
+                    )
+                    .with_related(span.clone()),
+                    (_, None) => Diagnostic::error(
+                        open.clone(),
+                        format!("unclosed `{opening}` at end of input"),
+                    ),
+                };
+                self.diagnostics.push(diagnostic);
+                None
+            }
+        };
+        self.stack.pop();
+
+        (children, close)
+    }
+
+    /// Parse a command, the current token being the command name.
+    fn parse_command(&mut self) -> Command<'source> {
+        let (_, name_span) = self.bump().expect("command should start with its name");
+        let name = command_name(&self.source[name_span.clone()]);
+        let args = match self.registry.command(name) {
+            Some(signature) => self.parse_args_with(signature),
+            None => self.parse_args(),
+        };
+        let end = args.last().map_or(name_span.end, |arg| arg.span().end);
+
+        Command {
+            name,
+            span: name_span.start..end,
+            name_span,
+            args,
+        }
+    }
+
+    /// Return the position of the next token that is not a space,
+    /// skipping at most one newline.
+    fn skip_spaces(&self) -> usize {
+        let mut pos = self.pos;
+        let mut newline = false;
+
+        while let Some((token, _)) = self.tokens.get(pos) {
+            match token {
+                Token::TabsOrSpaces => (),
+                Token::Newline if !newline => newline = true,
+                _ => break,
+            }
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Parse arguments following a command or an environment,
+    /// as specified by its signature.
+    ///
+    /// Spaces, and at most one newline, are allowed before each argument.
+    /// A missing required argument is simply skipped, as only
+    /// arguments delimited with braces are supported.
+    fn parse_args_with(&mut self, signature: &Signature) -> Vec<Argument<'source>> {
+        let mut args = Vec::new();
+
+        for spec in signature.args() {
+            let pos = self.skip_spaces();
+
+            match (spec, self.tokens.get(pos)) {
+                (ArgSpec::Star, Some((Token::Asterix, span))) => {
+                    args.push(Argument::Star(span.clone()));
+                    self.pos = pos + 1;
+                }
+                (ArgSpec::Optional(_), Some((Token::BracketOpen, _))) => {
+                    self.pos = pos;
+                    args.push(Argument::Optional(self.parse_group(GroupKind::Bracket)));
+                }
+                (ArgSpec::Required, Some((Token::BraceOpen, _))) => {
+                    self.pos = pos;
+                    args.push(Argument::Required(self.parse_group(GroupKind::Brace)));
                 }
                 _ => (),
             }
         }
-        panic!("Missing \\end{{document}}");
-        Ok(Self {})
+
+        args
     }
-}
 
-struct Options<'source> {
-    s: &'source str,
+    /// Parse arguments following a command or an environment.
+    ///
+    /// As the number of arguments is unknown, this function greedily takes:
+    /// - a star, directly after the name;
+    /// - then, any optional argument directly following the previous one;
+    /// - or any required argument, possibly separated by spaces.
+    fn parse_args(&mut self) -> Vec<Argument<'source>> {
+        let mut args = Vec::new();
+
+        if let Some((Token::Asterix, span)) = self.peek() {
+            args.push(Argument::Star(span.clone()));
+            self.bump();
+        }
+
+        loop {
+            match self.peek_token() {
+                Some(Token::BracketOpen) => {
+                    args.push(Argument::Optional(self.parse_group(GroupKind::Bracket)))
+                }
+                Some(Token::BraceOpen) => {
+                    args.push(Argument::Required(self.parse_group(GroupKind::Brace)))
+                }
+                Some(Token::TabsOrSpaces)
+                    if matches!(self.tokens.get(self.pos + 1), Some((Token::BraceOpen, _))) =>
+                {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+
+        args
+    }
 }
 
-struct Arguments<'source> {
-    s: &'source str,
+/// Return the error message for a closing token that matches no opening token,
+/// or [`None`] if the token is not a closing one.
+fn unmatched_message(token: &Token<'_>) -> Option<String> {
+    match token {
+        Token::BraceClose => Some("unmatched `}`".to_string()),
+        Token::EnvironmentEnd(name) => Some(format!(
+            r"`\end{{{name}}}` has no matching `\begin{{{name}}}`"
+        )),
+        Token::InlineMathClose => Some(r"unmatched `\)`".to_string()),
+        Token::DisplayMathClose => Some(r"unmatched `\]`".to_string()),
+        _ => None,
+    }
 }
 
-struct Command<'source> {
-    name: &'source str,
-    opts: Options<'source>,
-    args: Arguments<'source>,
-    span: Span,
+/// Push a text span, merging it with the previous node if it is also text.
+fn push_text(nodes: &mut Vec<Node<'_>>, span: Span) {
+    match nodes.last_mut() {
+        Some(Node::Text(last)) if last.end == span.start => last.end = span.end,
+        _ => nodes.push(Node::Text(span)),
+    }
 }
 
-struct Environment<'source> {
-    name: &'source str,
-    opts: Option<Options<'source>>,
-    args: Option<Arguments<'source>>,
-    span: Span,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::diagnostic::Severity;
+    use crate::latex::outline::Outline;
+
+    fn assert_lossless(source: &str) {
+        assert_eq!(SyntaxTree::parse(source).to_string(), source);
+    }
+
+    #[test]
+    fn test_lossless() {
+        assert_lossless("");
+        assert_lossless(r"\section*[short]{Long} text % comment");
+        assert_lossless(r"\begin{tikzpicture}[scale=1.5] \draw[thick] (0,0); \end{tikzpicture}");
+        assert_lossless(r"$x^2$ and $$ y $$ and \(z\) and \[w\]");
+        assert_lossless(r"unbalanced { group \begin{itemize} $ math");
+        assert_lossless(r"stray } and ] and \end{itemize} and \)");
+        assert_lossless(include_str!("../../../tests/data/minimal.tex"));
+        assert_lossless(include_str!("../../../tests/data/pgfplots_table.tex"));
+        assert_lossless(include_str!("../../../tests/data/check_fails.tex"));
+    }
+
+    #[test]
+    fn test_command() {
+        let source = r"\section*[short]{Long}";
+        let tree = SyntaxTree::parse(source);
+
+        match tree.nodes() {
+            [Node::Command(command)] => {
+                assert_eq!(command.name, "section");
+                assert_eq!(command.span, 0..source.len());
+                assert!(matches!(command.args[0], Argument::Star(_)));
+                assert!(matches!(command.args[1], Argument::Optional(_)));
+                assert!(matches!(command.args[2], Argument::Required(_)));
+                let group = command.args[2].group().unwrap();
+                assert_eq!(&source[group.inner_span()], "Long");
+            }
+            nodes => panic!("expected a single command, got {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_command_non_ascii() {
+        let source = "Caf\\é ok";
+        let tree = SyntaxTree::parse(source);
+
+        match tree.nodes() {
+            [Node::Text(_), Node::Command(command), Node::Text(_)] => {
+                assert_eq!(command.name, "é");
+                assert_eq!(command.name_span, 3..6);
+            }
+            nodes => panic!("unexpected nodes {nodes:#?}"),
+        }
+        assert_eq!(tree.to_string(), source);
+    }
+
+    #[test]
+    fn test_command_at_letter() {
+        let source = r"\makeatletter\@namedef{a}{b}\makeatother";
+        let names: Vec<_> = SyntaxTree::parse(source)
+            .into_nodes()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Command(command) => Some(command.name),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(names, vec!["makeatletter", "@namedef", "makeatother"]);
+    }
+
+    #[test]
+    fn test_command_signature() {
+        let source = "\\item [x] text \\maketitle {y} \\frac{a}\n{b}{c}";
+        let commands: Vec<_> = SyntaxTree::parse(source)
+            .into_nodes()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Command(command) => Some((command.name, command.args.len())),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(commands, vec![("item", 1), ("maketitle", 0), ("frac", 2)]);
+    }
+
+    #[test]
+    fn test_custom_signature() {
+        let source = r"\foo*[a]{b}{c}";
+        let mut registry = Registry::empty();
+        registry.define_command("foo", "o m").unwrap();
+
+        match SyntaxTree::parse_with(source, &registry).nodes() {
+            [Node::Command(command), Node::Text(text), Node::Group(_), Node::Group(_)] => {
+                assert_eq!(command.args.len(), 0);
+                assert_eq!(&source[text.clone()], "*[a]");
+            }
+            nodes => panic!("unexpected nodes {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_user_macro_signature() {
+        let source = r"\newcommand{\pair}[2]{(#1, #2)} \pair{a} {b}{c}";
+        match SyntaxTree::parse(source).nodes() {
+            [Node::Command(_), Node::Text(_), Node::Command(pair), Node::Group(_)] => {
+                assert_eq!(pair.name, "pair");
+                assert_eq!(pair.args.len(), 2);
+            }
+            nodes => panic!("unexpected nodes {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_verbatim() {
+        let source = "\\verb|{| \\lstnewenvironment{code}{}{}
+\\begin{verbatim}
+\\section{Not a section}
+\\end{verbatim}
+\\begin{code}
+}\\section{Neither}
+\\end{code}";
+        let tree = SyntaxTree::parse(source);
+
+        assert!(tree.diagnostics().is_empty());
+        assert_eq!(tree.to_string(), source);
+        assert!(Outline::from_tree(&tree).sections().is_empty());
+
+        let environments: Vec<_> = tree
+            .nodes()
+            .iter()
+            .filter_map(|node| match node {
+                Node::Environment(environment) => Some(environment),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(environments.len(), 2);
+        for environment in environments {
+            assert!(matches!(&environment.children[..], [Node::Text(_)]));
+        }
+    }
+
+    #[test]
+    fn test_environment() {
+        let source = r"\begin{document}\begin{equation}x\end{equation}\end{document}";
+        let tree = SyntaxTree::parse(source);
+        let document = tree.document().unwrap();
+
+        assert_eq!(document.span, 0..source.len());
+        assert!(document.end.is_some());
+        match document.children.as_slice() {
+            [Node::Environment(equation)] => {
+                assert_eq!(equation.name, "equation");
+                assert!(equation.is_math());
+            }
+            nodes => panic!("expected a single environment, got {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_command_whitespace() {
+        for source in [
+            "\\\n",
+            "a\\\tb",
+            "\\\r\nb",
+            "\\end  x",
+            "\\begin\n",
+            "\\end \r\n{",
+        ] {
+            let tree = SyntaxTree::parse(source);
+            assert_eq!(tree.to_string(), source);
+        }
+        match SyntaxTree::parse("\\end  x").nodes() {
+            [Node::Command(command), Node::Text(_)] => {
+                assert_eq!(command.name, "end");
+                assert_eq!(command.name_span, 0..6);
+            }
+            nodes => panic!("unexpected nodes {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_environment_spaces() {
+        let source = r"\begin {frame:main}\begin{ figure }x\end{figure}\end {frame:main}";
+        let tree = SyntaxTree::parse(source);
+
+        assert!(tree.diagnostics().is_empty());
+        match tree.nodes() {
+            [Node::Environment(frame)] => {
+                assert_eq!(frame.name, "frame:main");
+                assert!(
+                    matches!(&frame.children[..], [Node::Environment(figure)] if figure.name == "figure")
+                );
+                assert_eq!(tree.to_string(), source);
+            }
+            nodes => panic!("expected a single environment, got {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_math() {
+        let source = r"$a$ $$b$$ \(c\) \[d\]";
+        let kinds: Vec<_> = SyntaxTree::parse(source)
+            .into_nodes()
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Math(math) => {
+                    assert!(math.close.is_some());
+                    Some(math.kind)
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            kinds,
+            vec![
+                MathKind::Dollar,
+                MathKind::DoubleDollar,
+                MathKind::Parenthesis,
+                MathKind::Bracket
+            ]
+        );
+    }
+
+    #[test]
+    fn test_nested_math_in_text() {
+        let source = r"$a \text{if $b$} c$";
+        match SyntaxTree::parse(source).nodes() {
+            [Node::Math(math)] => assert_eq!(math.span, 0..source.len()),
+            nodes => panic!("expected a single math region, got {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_unclosed() {
+        let source = r"\begin{itemize} item {a";
+        match SyntaxTree::parse(source).nodes() {
+            [Node::Environment(environment)] => {
+                assert!(environment.end.is_none());
+                assert_eq!(environment.span, 0..source.len());
+                match environment.children.last() {
+                    Some(Node::Group(group)) => assert!(group.close.is_none()),
+                    node => panic!("expected an unclosed group, got {node:#?}"),
+                }
+            }
+            nodes => panic!("expected a single environment, got {nodes:#?}"),
+        }
+    }
+
+    #[test]
+    fn test_mismatched_end() {
+        let source = r"\begin{a}\begin{b}\end{a}\end{b}";
+        let tree = SyntaxTree::parse(source);
+
+        match tree.nodes() {
+            [Node::Environment(a), Node::Text(text)] => {
+                assert_eq!(a.name, "a");
+                assert!(a.end.is_some());
+                assert_eq!(&source[text.clone()], r"\end{b}");
+            }
+            nodes => panic!("unexpected nodes {nodes:#?}"),
+        }
+    }
+
+    fn messages(source: &str) -> Vec<String> {
+        SyntaxTree::parse(source)
+            .diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_no_diagnostics() {
+        assert!(messages(include_str!("../../../tests/data/minimal.tex")).is_empty());
+        assert!(messages(include_str!("../../../tests/data/pgfplots_table.tex")).is_empty());
+        assert!(messages(r"fragment without \texttt{document}").is_empty());
+    }
+
+    #[test]
+    fn test_diagnostics_unbalanced() {
+        assert_eq!(
+            messages(r"a } b { c"),
+            vec!["unmatched `}`", "unclosed `{` at end of input"]
+        );
+        assert_eq!(
+            messages(r"\begin{a}\begin{b}\end{a}\end{b}"),
+            vec![
+                r"`\begin{b}` is closed by `\end{a}`",
+                r"`\end{b}` has no matching `\begin{b}`"
+            ]
+        );
+        assert_eq!(
+            messages(r"\begin{a} x{\end{a}"),
+            vec![r"unclosed `{` before `\end{a}`"]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_math() {
+        assert_eq!(
+            messages(r"\[ x \) $ y"),
+            vec![
+                r"unmatched `\)`",
+                "unclosed `$` at end of input",
+                r"unclosed `\[` at end of input"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_document() {
+        let source = include_str!("../../../tests/data/check_fails.tex");
+        let tree = SyntaxTree::parse(source);
+        let diagnostics = tree.diagnostics();
+
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(&source[diagnostics[0].span.clone()], "$");
+        assert_eq!(diagnostics[0].related_spans.len(), 1);
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+
+        assert_eq!(
+            messages("\\documentclass{article}\n\\begin{document}\n\\end{document}\n% comment\n"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            messages("\\begin{document}\\end{document} junk"),
+            vec![
+                r"missing `\documentclass` before `\begin{document}`",
+                r"content after `\end{document}` is ignored"
+            ]
+        );
+        assert_eq!(
+            messages("\\documentclass{article}\\begin{document}"),
+            vec![r"unclosed `\begin{document}` at end of input"]
+        );
+    }
 }
-*/
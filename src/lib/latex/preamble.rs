@@ -0,0 +1,309 @@
+//! Structured model of a document preamble.
+//!
+//! A [`Preamble`] holds the document class, the loaded packages and the
+//! metadata (title, authors, ...) declared before `\begin{document}`.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::SyntaxTree;
+//! use untex::latex::preamble::{MetadataKind, Preamble};
+//!
+//! let source = r"\documentclass[a4paper, 11pt]{article}
+//! \usepackage[utf8]{inputenc}
+//! \usepackage{amsmath, amssymb}
+//! \usepackage{cleveref}
+//! \usepackage{hyperref}
+//! \title{A title}
+//! \begin{document}
+//! \end{document}";
+//! let tree = SyntaxTree::parse(source);
+//! let preamble = Preamble::from_tree(&tree);
+//!
+//! let class = preamble.document_class.as_ref().unwrap();
+//! assert_eq!(class.name, "article");
+//! assert_eq!(class.options[1].key, "11pt");
+//!
+//! assert!(preamble.package("amssymb").is_some());
+//! assert!(preamble.position("cleveref") < preamble.position("hyperref"));
+//! assert_eq!(preamble.metadata(MetadataKind::Title).next().unwrap().value, "A title");
+//! ```
+
+use crate::latex::keyval::{parse_group, KeyVal};
+use crate::latex::parse::{Argument, Command, Group, SyntaxTree};
+use crate::latex::token::Span;
+use crate::latex::visit::{walk_command, Visit};
+
+/// The document class, from `\documentclass[options]{name}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocumentClass<'source> {
+    /// Class name.
+    pub name: &'source str,
+    /// Class options.
+    pub options: Vec<KeyVal<'source>>,
+    /// Span of the whole command.
+    pub span: Span,
+}
+
+/// A package, loaded with `\usepackage` or `\RequirePackage`.
+///
+/// A command loading multiple packages, e.g., `\usepackage{amsmath,amssymb}`,
+/// results in one package per name, all sharing the same options and span.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Package<'source> {
+    /// Package name.
+    pub name: &'source str,
+    /// Span of the package name.
+    pub name_span: Span,
+    /// Name of the command used to load the package, without the leading backslash.
+    pub command: &'source str,
+    /// Package options.
+    pub options: Vec<KeyVal<'source>>,
+    /// Span of the whole command.
+    pub span: Span,
+}
+
+/// Kind of metadata command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MetadataKind {
+    /// `\title`.
+    Title,
+    /// `\author`.
+    Author,
+    /// `\date`.
+    Date,
+    /// `\thanks`.
+    Thanks,
+    /// `\affil`, from the `authblk` package.
+    Affil,
+}
+
+impl MetadataKind {
+    /// Return the kind of metadata matching a command name, without the leading backslash.
+    #[must_use]
+    pub fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "title" => Some(MetadataKind::Title),
+            "author" => Some(MetadataKind::Author),
+            "date" => Some(MetadataKind::Date),
+            "thanks" => Some(MetadataKind::Thanks),
+            "affil" => Some(MetadataKind::Affil),
+            _ => None,
+        }
+    }
+}
+
+/// A metadata command, e.g., `\author[1]{Name}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metadata<'source> {
+    /// Kind of metadata.
+    pub kind: MetadataKind,
+    /// Optional argument, e.g., the affiliation marks used by `authblk`.
+    pub optional: Option<&'source str>,
+    /// Value, as it appears in the source.
+    pub value: &'source str,
+    /// Span of the whole command.
+    pub span: Span,
+}
+
+/// Document class, packages and metadata declared in a preamble.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preamble<'source> {
+    /// Document class, if any.
+    pub document_class: Option<DocumentClass<'source>>,
+    /// Packages, in loading order.
+    pub packages: Vec<Package<'source>>,
+    /// Metadata commands, in order of appearance.
+    ///
+    /// `\thanks` commands nested in other metadata, e.g., in `\author{...}`, are included.
+    pub entries: Vec<Metadata<'source>>,
+    /// Span of the preamble, i.e., everything before `\begin{document}`,
+    /// or the whole source if there is no `document` environment.
+    pub span: Span,
+}
+
+impl<'source> Preamble<'source> {
+    /// Build the preamble model of a syntax tree.
+    #[must_use]
+    pub fn from_tree(tree: &SyntaxTree<'source>) -> Self {
+        let source = tree.source();
+        let end = tree
+            .document()
+            .map_or(source.len(), |document| document.span.start);
+        let mut collector = Collector {
+            source,
+            preamble: Self {
+                span: 0..end,
+                ..Self::default()
+            },
+        };
+
+        for node in tree.nodes().iter() {
+            if node.span().start >= end {
+                break;
+            }
+            collector.visit_node(node);
+        }
+        collector.preamble
+    }
+
+    /// Return the first package with the given name, if any.
+    #[must_use]
+    pub fn package(&self, name: &str) -> Option<&Package<'source>> {
+        self.packages.iter().find(|package| package.name == name)
+    }
+
+    /// Return the loading position, i.e., the index in [`Preamble::packages`],
+    /// of the package with the given name, if any.
+    #[must_use]
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.packages
+            .iter()
+            .position(|package| package.name == name)
+    }
+
+    /// Return an iterator over the metadata of a given kind.
+    pub fn metadata(&self, kind: MetadataKind) -> impl Iterator<Item = &Metadata<'source>> {
+        self.entries.iter().filter(move |entry| entry.kind == kind)
+    }
+}
+
+/// Return the groups of the optional and the required arguments of a command.
+fn arguments<'a, 'source>(
+    command: &'a Command<'source>,
+) -> (Option<&'a Group<'source>>, Option<&'a Group<'source>>) {
+    let optional = command.args.iter().find_map(|arg| match arg {
+        Argument::Optional(group) => Some(group),
+        _ => None,
+    });
+    let required = command.args.iter().find_map(|arg| match arg {
+        Argument::Required(group) => Some(group),
+        _ => None,
+    });
+    (optional, required)
+}
+
+/// Collect the preamble commands.
+struct Collector<'source> {
+    source: &'source str,
+    preamble: Preamble<'source>,
+}
+
+impl<'source> Visit<'source> for Collector<'source> {
+    fn visit_command(&mut self, command: &Command<'source>) {
+        let (optional, required) = arguments(command);
+        let options = |source| optional.map_or_else(Vec::new, |group| parse_group(group, source));
+
+        match command.name {
+            "documentclass" => {
+                if let Some(group) = required {
+                    self.preamble.document_class = Some(DocumentClass {
+                        name: self.source[group.inner_span()].trim(),
+                        options: options(self.source),
+                        span: command.span.clone(),
+                    });
+                }
+            }
+            "usepackage" | "RequirePackage" => {
+                if let Some(group) = required {
+                    let options = options(self.source);
+                    for keyval in parse_group(group, self.source) {
+                        self.preamble.packages.push(Package {
+                            name: keyval.key,
+                            name_span: keyval.key_span,
+                            command: command.name,
+                            options: options.clone(),
+                            span: command.span.clone(),
+                        });
+                    }
+                }
+            }
+            name => {
+                if let (Some(kind), Some(group)) = (MetadataKind::from_command(name), required) {
+                    self.preamble.entries.push(Metadata {
+                        kind,
+                        optional: optional.map(|group| &self.source[group.inner_span()]),
+                        value: &self.source[group.inner_span()],
+                        span: command.span.clone(),
+                    });
+                }
+            }
+        }
+        walk_command(self, command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimal() {
+        let source = include_str!("../../../tests/data/minimal.tex");
+        let preamble = Preamble::from_tree(&SyntaxTree::parse(source));
+
+        let class = preamble.document_class.unwrap();
+        assert_eq!(class.name, "article");
+        assert!(class.options.is_empty());
+
+        assert_eq!(preamble.packages.len(), 1);
+        assert_eq!(preamble.packages[0].name, "inputenc");
+        assert_eq!(preamble.packages[0].options[0].key, "utf8");
+
+        let values: Vec<_> = preamble.entries.iter().map(|entry| entry.value).collect();
+        assert_eq!(values, vec!["minimal", "Jérome Eertmans", "April 2022"]);
+        assert!(source[preamble.span].ends_with("April 2022}\n\n"));
+    }
+
+    #[test]
+    fn test_packages() {
+        let source = r"\RequirePackage[ a={b,c} , d ]{ pkg1,
+  pkg2 % comment
+  , pkg3,}
+\begin{document}
+\usepackage{ignored}
+\end{document}";
+        let preamble = Preamble::from_tree(&SyntaxTree::parse(source));
+
+        let names: Vec<_> = preamble.packages.iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["pkg1", "pkg2", "pkg3"]);
+        assert_eq!(&source[preamble.packages[1].name_span.clone()], "pkg2");
+        let options: Vec<_> = preamble.packages[0]
+            .options
+            .iter()
+            .map(|option| (option.key, option.value))
+            .collect();
+        assert_eq!(options, vec![("a", Some("{b,c}")), ("d", None)]);
+        assert_eq!(preamble.packages[2].command, "RequirePackage");
+        assert_eq!(preamble.position("pkg3"), Some(2));
+        assert!(preamble.package("ignored").is_none());
+        assert!(preamble.document_class.is_none());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let source = r"\title{T}
+\author[1]{A\thanks{Funded.}}
+\affil[1]{University}
+\date{\today}";
+        let preamble = Preamble::from_tree(&SyntaxTree::parse(source));
+
+        let kinds: Vec<_> = preamble.entries.iter().map(|entry| entry.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                MetadataKind::Title,
+                MetadataKind::Author,
+                MetadataKind::Thanks,
+                MetadataKind::Affil,
+                MetadataKind::Date,
+            ]
+        );
+
+        let author = preamble.metadata(MetadataKind::Author).next().unwrap();
+        assert_eq!(author.optional, Some("1"));
+        assert_eq!(author.value, r"A\thanks{Funded.}");
+        assert_eq!(preamble.entries[2].value, "Funded.");
+        assert_eq!(preamble.entries[3].value, "University");
+    }
+}
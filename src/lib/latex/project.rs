@@ -0,0 +1,746 @@
+//! Multi-file projects, following `\input`, `\include`, `\subfile`, `\import` and `\subimport`.
+//!
+//! A [`Project`] starts from a root file and loads, in document order, every file it
+//! includes, recursively. Each file is loaded once, which also breaks inclusion cycles.
+//!
+//! Paths are resolved as LaTeX does:
+//!
+//! - `\input{name}` and `\include{name}` are relative to the current directory, i.e., the
+//!   directory of the root file, or the directory set by `\import` and `\subimport`;
+//! - `\import{dir}{name}` is relative to `dir`, itself relative to the root directory,
+//!   and `\subimport{dir}{name}` is relative to `dir`, itself relative to the current directory.
+//!   Both change the current directory to `dir` inside the imported file;
+//! - `\subfile{name}` is relative to the current directory, and changes it
+//!   to the directory of the subfile;
+//! - the `.tex` extension is implicit, and always added by `\include`;
+//! - `\include{name}` is skipped if `name` is not listed by a previous `\includeonly`.
+//!
+//! [`Project::expand`] concatenates all the files into one source, and maps the spans
+//! of that source back to [`FileSpan`]s, i.e., spans tagged with their originating file.
+//!
+//! # Example
+//!
+//! ```
+//! use std::collections::HashMap;
+//! use std::io;
+//! use std::path::{Path, PathBuf};
+//! use untex::latex::project::Project;
+//!
+//! let files: HashMap<PathBuf, &str> = HashMap::from([
+//!     ("main.tex".into(), r"\includeonly{intro}\include{intro}\include{outro}"),
+//!     ("intro.tex".into(), r"Hello \input{sections/body}"),
+//!     ("sections/body.tex".into(), "World!"),
+//! ]);
+//! let read = |path: &Path| {
+//!     files
+//!         .get(path)
+//!         .map(|source| source.to_string())
+//!         .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+//! };
+//! let project = Project::load_with("main.tex", read).unwrap();
+//!
+//! assert_eq!(project.files().len(), 3);
+//! assert!(project.inclusions()[2].excluded);
+//!
+//! let expanded = project.expand();
+//! assert_eq!(expanded.source(), r"\includeonly{intro}Hello World!\include{outro}");
+//!
+//! let span = expanded.locate(25..31).unwrap();
+//! assert_eq!(project.file(span.file).path, Path::new("sections/body.tex"));
+//! assert_eq!(span.span, 0..6);
+//! ```
+
+use crate::error::Result;
+use crate::latex::parse::{Argument, Command, Node, SyntaxTree};
+use crate::latex::token::Span;
+use crate::latex::visit::{walk_command, Visit};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Identifier of a file in a [`Project`], i.e., its index in [`Project::files`].
+pub type FileId = usize;
+
+/// A span tagged with the file it originates from.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FileSpan {
+    /// File containing the span.
+    pub file: FileId,
+    /// Span, relative to the source of the file.
+    pub span: Span,
+}
+
+/// A file loaded in a [`Project`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Path of the file.
+    pub path: PathBuf,
+    /// Content of the file.
+    pub source: String,
+    /// Current directory inside the file, used to resolve `\input` and `\include`.
+    pub directory: PathBuf,
+}
+
+/// Command used to include a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InclusionKind {
+    /// `\input{name}`.
+    Input,
+    /// `\include{name}`.
+    Include,
+    /// `\subfile{name}`, from the `subfiles` package.
+    Subfile,
+    /// `\import{dir}{name}`, from the `import` package.
+    Import,
+    /// `\subimport{dir}{name}`, from the `import` package.
+    Subimport,
+}
+
+impl InclusionKind {
+    /// Return the kind of inclusion matching a command name, without the leading backslash.
+    #[must_use]
+    pub fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "input" => Some(InclusionKind::Input),
+            "include" => Some(InclusionKind::Include),
+            "subfile" => Some(InclusionKind::Subfile),
+            "import" => Some(InclusionKind::Import),
+            "subimport" => Some(InclusionKind::Subimport),
+            _ => None,
+        }
+    }
+}
+
+/// An inclusion command, i.e., an edge of the file graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inclusion {
+    /// Kind of inclusion.
+    pub kind: InclusionKind,
+    /// Span of the command, in the including file.
+    pub command: FileSpan,
+    /// Name of the included file, as written, e.g., `chapters/intro`.
+    pub name: String,
+    /// Resolved path of the included file.
+    ///
+    /// If the file could not be read, this is the first path that was tried.
+    pub path: PathBuf,
+    /// Included file, if it could be read and was not excluded.
+    pub file: Option<FileId>,
+    /// Whether the inclusion was skipped because of `\includeonly`.
+    pub excluded: bool,
+}
+
+/// Root file and all the files it includes, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Project {
+    files: Vec<SourceFile>,
+    inclusions: Vec<Inclusion>,
+    graphics_paths: Vec<PathBuf>,
+    include_only: Option<Vec<String>>,
+}
+
+impl Project {
+    /// Load a project from the file system, starting from a root file.
+    pub fn load<P>(root: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::load_with(root, |path| std::fs::read_to_string(path))
+    }
+
+    /// Load a project, starting from a root file, and reading files with `read`.
+    pub fn load_with<P, F>(root: P, mut read: F) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> io::Result<String>,
+    {
+        let source = read(root.as_ref())?;
+        Ok(Self::from_source(root, source, read))
+    }
+
+    /// Create a project from the content of its root file, located at `path`,
+    /// and read included files with `read`.
+    ///
+    /// The file at `path` does not need to exist, e.g., for content read from standard input.
+    pub fn from_source<P, F>(path: P, source: String, read: F) -> Self
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> io::Result<String>,
+    {
+        let path = normalize(path.as_ref());
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut loader = Loader {
+            read,
+            project: Self {
+                files: vec![SourceFile {
+                    path,
+                    source,
+                    directory,
+                }],
+                inclusions: Vec::new(),
+                graphics_paths: Vec::new(),
+                include_only: None,
+            },
+        };
+
+        loader.load(0);
+        loader.project
+    }
+
+    /// Return the files, the root file first, in loading order.
+    #[must_use]
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    /// Return the file with a given identifier.
+    ///
+    /// # Panics
+    ///
+    /// If the identifier does not belong to this project.
+    #[must_use]
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id]
+    }
+
+    /// Return the root file.
+    #[must_use]
+    pub fn root(&self) -> &SourceFile {
+        &self.files[0]
+    }
+
+    /// Return the inclusion commands, in document order.
+    #[must_use]
+    pub fn inclusions(&self) -> &[Inclusion] {
+        &self.inclusions
+    }
+
+    /// Return the directories set by `\graphicspath`, relative to the root directory.
+    #[must_use]
+    pub fn graphics_paths(&self) -> &[PathBuf] {
+        &self.graphics_paths
+    }
+
+    /// Return the names listed by `\includeonly`, if any.
+    #[must_use]
+    pub fn include_only(&self) -> Option<&[String]> {
+        self.include_only.as_deref()
+    }
+
+    /// Return the paths where a graphics file, e.g., from `\includegraphics{name}`,
+    /// is searched for, in order.
+    #[must_use]
+    pub fn graphics_candidates(&self, name: &str) -> Vec<PathBuf> {
+        let directory = &self.root().directory;
+        std::iter::once(normalize(&directory.join(name)))
+            .chain(
+                self.graphics_paths
+                    .iter()
+                    .map(|path| normalize(&directory.join(path).join(name))),
+            )
+            .collect()
+    }
+
+    /// Concatenate the files of the project, replacing each inclusion command
+    /// with the content of the included file.
+    ///
+    /// Commands of excluded or missing files are kept as is.
+    #[must_use]
+    pub fn expand(&self) -> Expanded {
+        let mut expanded = Expanded::default();
+        self.expand_file(0, &mut vec![0], &mut expanded);
+        expanded
+    }
+
+    fn expand_file(&self, id: FileId, stack: &mut Vec<FileId>, expanded: &mut Expanded) {
+        let mut start = 0;
+
+        for inclusion in self.inclusions.iter().filter(|inc| inc.command.file == id) {
+            let file = match inclusion.file {
+                Some(file) if !stack.contains(&file) => file,
+                _ => continue,
+            };
+            expanded.push(self, id, start..inclusion.command.span.start);
+            stack.push(file);
+            self.expand_file(file, stack, expanded);
+            stack.pop();
+            start = inclusion.command.span.end;
+        }
+        expanded.push(self, id, start..self.files[id].source.len());
+    }
+}
+
+/// A part of an [`Expanded`] source, copied from a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Segment {
+    range: Span,
+    file: FileId,
+    offset: usize,
+}
+
+/// Content of a project as one source, see [`Project::expand`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Expanded {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl Expanded {
+    fn push(&mut self, project: &Project, file: FileId, span: Span) {
+        if span.is_empty() {
+            return;
+        }
+        let start = self.source.len();
+        self.source
+            .push_str(&project.files[file].source[span.clone()]);
+        self.segments.push(Segment {
+            range: start..self.source.len(),
+            file,
+            offset: span.start,
+        });
+    }
+
+    /// Return the expanded source.
+    #[must_use]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Return the expanded source.
+    #[must_use]
+    pub fn into_source(self) -> String {
+        self.source
+    }
+
+    /// Return the file, and the span in that file, of a span of the expanded source.
+    ///
+    /// A span that overlaps multiple files is truncated to the file of its start.
+    #[must_use]
+    pub fn locate(&self, span: Span) -> Option<FileSpan> {
+        let i = self
+            .segments
+            .partition_point(|segment| segment.range.end <= span.start);
+        let segment = self.segments.get(i)?;
+
+        if span.start < segment.range.start {
+            return None;
+        }
+        let end = span.end.min(segment.range.end);
+        Some(FileSpan {
+            file: segment.file,
+            span: segment.offset + span.start - segment.range.start
+                ..segment.offset + end - segment.range.start,
+        })
+    }
+}
+
+/// Normalize a path lexically, removing `.` and resolving `..` when possible.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Return the paths to try for a file name, with the implicit `.tex` extension first.
+fn candidates(path: PathBuf) -> Vec<PathBuf> {
+    if path
+        .extension()
+        .map_or(false, |extension| extension == "tex")
+    {
+        return vec![path];
+    }
+    let mut with_extension = path.clone().into_os_string();
+    with_extension.push(".tex");
+    vec![with_extension.into(), path]
+}
+
+/// Return the content of each `{...}` group in `s`, e.g., `a/` and `b/` in `{a/}{b/}`.
+fn braced_items(s: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    start = i + 1;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    items.push(s[start..i].trim());
+                }
+            }
+            _ => (),
+        }
+    }
+    items
+}
+
+/// A command relevant to the project structure.
+struct Found {
+    name: String,
+    span: Span,
+    args: Vec<String>,
+}
+
+/// Collect the commands relevant to the project structure.
+struct Collector<'source> {
+    source: &'source str,
+    found: Vec<Found>,
+}
+
+impl<'source> Visit<'source> for Collector<'source> {
+    fn visit_command(&mut self, command: &Command<'source>) {
+        if InclusionKind::from_command(command.name).is_some()
+            || matches!(command.name, "includeonly" | "graphicspath")
+        {
+            self.found.push(Found {
+                name: command.name.to_string(),
+                span: command.span.clone(),
+                args: command
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        Argument::Required(group) => {
+                            Some(self.source[group.inner_span()].trim().to_string())
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            });
+        }
+        walk_command(self, command);
+    }
+}
+
+/// Load the files of a project, recursively.
+struct Loader<F> {
+    read: F,
+    project: Project,
+}
+
+impl<F> Loader<F>
+where
+    F: FnMut(&Path) -> io::Result<String>,
+{
+    fn load(&mut self, id: FileId) {
+        let found = {
+            let source = &self.project.files[id].source;
+            let tree = SyntaxTree::parse(source);
+            let mut collector = Collector {
+                source,
+                found: Vec::new(),
+            };
+            tree.nodes()
+                .iter()
+                .for_each(|node: &Node| collector.visit_node(node));
+            collector.found
+        };
+
+        for Found { name, span, args } in found {
+            match (name.as_str(), args.as_slice()) {
+                ("includeonly", [list]) => {
+                    self.project.include_only = Some(
+                        list.split(',')
+                            .map(str::trim)
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_string)
+                            .collect(),
+                    );
+                }
+                ("graphicspath", [list]) => {
+                    self.project
+                        .graphics_paths
+                        .extend(braced_items(list).into_iter().map(PathBuf::from));
+                }
+                (name, args) => {
+                    if let Some(kind) = InclusionKind::from_command(name) {
+                        self.include(id, kind, span, args);
+                    }
+                }
+            }
+        }
+    }
+
+    fn include(&mut self, id: FileId, kind: InclusionKind, span: Span, args: &[String]) {
+        let current = &self.project.files[id];
+        let root_directory = &self.project.files[0].directory;
+
+        let (name, paths, directory) = match (kind, args) {
+            (InclusionKind::Input, [name]) => (
+                name,
+                candidates(current.directory.join(name)),
+                Some(current.directory.clone()),
+            ),
+            (InclusionKind::Include, [name]) => {
+                let mut path = current.directory.join(name).into_os_string();
+                path.push(".tex");
+                (name, vec![path.into()], Some(current.directory.clone()))
+            }
+            (InclusionKind::Subfile, [name]) => {
+                (name, candidates(current.directory.join(name)), None)
+            }
+            (InclusionKind::Import, [directory, name]) => {
+                let directory = root_directory.join(directory);
+                (name, candidates(directory.join(name)), Some(directory))
+            }
+            (InclusionKind::Subimport, [directory, name]) => {
+                let directory = current.directory.join(directory);
+                (name, candidates(directory.join(name)), Some(directory))
+            }
+            _ => return,
+        };
+        let paths: Vec<_> = paths.iter().map(|path| normalize(path)).collect();
+        let mut inclusion = Inclusion {
+            kind,
+            command: FileSpan { file: id, span },
+            name: name.clone(),
+            path: paths[0].clone(),
+            file: None,
+            excluded: false,
+        };
+
+        if kind == InclusionKind::Include {
+            if let Some(include_only) = &self.project.include_only {
+                inclusion.excluded = !include_only.contains(name);
+            }
+        }
+        if !inclusion.excluded {
+            for path in paths {
+                if let Some(file) = self.project.files.iter().position(|f| f.path == path) {
+                    inclusion.path = path;
+                    inclusion.file = Some(file);
+                    break;
+                }
+                if let Ok(source) = (self.read)(&path) {
+                    let file = self.project.files.len();
+                    let directory = directory.clone().unwrap_or_else(|| {
+                        path.parent().map(Path::to_path_buf).unwrap_or_default()
+                    });
+                    self.project.files.push(SourceFile {
+                        path: path.clone(),
+                        source,
+                        directory: normalize(&directory),
+                    });
+                    inclusion.path = path;
+                    inclusion.file = Some(file);
+                    self.project.inclusions.push(inclusion);
+                    self.load(file);
+                    return;
+                }
+            }
+        }
+        self.project.inclusions.push(inclusion);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn project(root: &str, files: &[(&str, &str)]) -> Project {
+        let files: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect();
+        Project::load_with(root, |path| {
+            files
+                .get(path)
+                .cloned()
+                .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        })
+        .unwrap()
+    }
+
+    fn paths(project: &Project) -> Vec<&Path> {
+        project
+            .files()
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect()
+    }
+
+    #[test]
+    fn test_input() {
+        let project = project(
+            "doc/main.tex",
+            &[
+                (
+                    "doc/main.tex",
+                    r"\input{a}\input{./b.tex}\input{c}% \input{d}",
+                ),
+                ("doc/a.tex", r"\input{sub/e}"),
+                ("doc/b.tex", ""),
+                ("doc/c", ""),
+                ("doc/sub/e.tex", r"\input{b}"),
+                ("doc/d.tex", ""),
+            ],
+        );
+
+        assert_eq!(
+            paths(&project),
+            vec![
+                Path::new("doc/main.tex"),
+                Path::new("doc/a.tex"),
+                Path::new("doc/sub/e.tex"),
+                Path::new("doc/b.tex"),
+                Path::new("doc/c"),
+            ]
+        );
+        // `\input{b}` in `sub/e.tex` is resolved against `doc/`, the current directory
+        // inherited through `\input`, and not against `doc/sub/`
+        let inclusion = &project.inclusions()[2];
+        assert_eq!(inclusion.command.file, 2);
+        assert_eq!(inclusion.file, Some(3));
+        assert_eq!(project.inclusions()[3].file, Some(3));
+    }
+
+    #[test]
+    fn test_include_only() {
+        let project = project(
+            "main.tex",
+            &[
+                (
+                    "main.tex",
+                    r"\includeonly{b, c}\include{a}\include{b}\include{c}",
+                ),
+                ("a.tex", ""),
+                ("b.tex", ""),
+            ],
+        );
+
+        assert_eq!(
+            project.include_only(),
+            Some(&["b".to_string(), "c".to_string()][..])
+        );
+        let inclusions: Vec<_> = project
+            .inclusions()
+            .iter()
+            .map(|inclusion| (inclusion.name.as_str(), inclusion.file, inclusion.excluded))
+            .collect();
+        assert_eq!(
+            inclusions,
+            vec![("a", None, true), ("b", Some(1), false), ("c", None, false)]
+        );
+        assert_eq!(project.inclusions()[2].path, Path::new("c.tex"));
+    }
+
+    #[test]
+    fn test_import_and_subfile() {
+        let project = project(
+            "main.tex",
+            &[
+                ("main.tex", r"\import{chapters/}{one}\subfile{parts/two}"),
+                ("chapters/one.tex", r"\input{fig}\subimport*{sec/}{s1}"),
+                ("chapters/fig.tex", ""),
+                ("chapters/sec/s1.tex", r"\input{t}"),
+                ("chapters/sec/t.tex", ""),
+                ("parts/two.tex", r"\subfile{three}\input{one}"),
+                ("parts/three.tex", ""),
+                ("parts/one.tex", ""),
+            ],
+        );
+
+        assert_eq!(
+            paths(&project),
+            vec![
+                Path::new("main.tex"),
+                Path::new("chapters/one.tex"),
+                Path::new("chapters/fig.tex"),
+                Path::new("chapters/sec/s1.tex"),
+                Path::new("chapters/sec/t.tex"),
+                Path::new("parts/two.tex"),
+                Path::new("parts/three.tex"),
+                Path::new("parts/one.tex"),
+            ]
+        );
+        assert_eq!(project.file(3).directory, Path::new("chapters/sec"));
+    }
+
+    #[test]
+    fn test_graphics_path() {
+        let project = project(
+            "doc/main.tex",
+            &[("doc/main.tex", r"\graphicspath{{figs/}{../img/}}")],
+        );
+
+        assert_eq!(
+            project.graphics_paths(),
+            &[PathBuf::from("figs/"), PathBuf::from("../img/")]
+        );
+        assert_eq!(
+            project.graphics_candidates("plot.pdf"),
+            vec![
+                PathBuf::from("doc/plot.pdf"),
+                PathBuf::from("doc/figs/plot.pdf"),
+                PathBuf::from("img/plot.pdf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let project = project(
+            "a.tex",
+            &[("a.tex", r"A\input{b}"), ("b.tex", r"B\input{a}\input{b}")],
+        );
+
+        assert_eq!(project.files().len(), 2);
+        assert_eq!(project.inclusions()[1].file, Some(0));
+        assert_eq!(project.expand().source(), r"AB\input{a}\input{b}");
+    }
+
+    #[test]
+    fn test_expand_and_locate() {
+        let project = project(
+            "main.tex",
+            &[
+                ("main.tex", "A\n\\input{b}\nC \\input{missing}"),
+                ("b.tex", "BB"),
+            ],
+        );
+        let expanded = project.expand();
+
+        assert_eq!(expanded.source(), "A\nBB\nC \\input{missing}");
+        assert_eq!(
+            expanded.locate(2..4),
+            Some(FileSpan {
+                file: 1,
+                span: 0..2
+            })
+        );
+        assert_eq!(
+            expanded.locate(3..6),
+            Some(FileSpan {
+                file: 1,
+                span: 1..2
+            })
+        );
+        assert_eq!(
+            expanded.locate(4..5),
+            Some(FileSpan {
+                file: 0,
+                span: 11..12
+            })
+        );
+        assert_eq!(expanded.locate(100..101), None);
+    }
+}
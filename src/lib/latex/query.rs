@@ -0,0 +1,630 @@
+//! Finding nodes of a [`SyntaxTree`] with selectors.
+//!
+//! A [`Query`] is made of one or more comma-separated selectors, in a syntax close to CSS:
+//!
+//! - `cmd`, `env`, `math`, `group`, `comment` and `text` match the corresponding nodes,
+//!   and `*` matches any node. `math` matches both math delimiters, e.g., `$...$`,
+//!   and math environments, e.g., `equation`;
+//! - `arg` matches any argument of a command or an environment, and `arg(n)` matches
+//!   the `n`-th one, starting at 1 and counting stars, optional and required arguments;
+//! - `[name=value]` matches the name of commands and environments, and `[text=value]`
+//!   the source text of a node. Other operators are `!=`, `^=` (prefix), `$=` (suffix)
+//!   and `*=` (substring), and values may be quoted, e.g., `[text*="a, b"]`;
+//! - `:display`, `:inline`, `:starred`, `:optional` and `:required` are pseudo-classes
+//!   that restrict math, starred commands or environments, and arguments;
+//! - `a b` matches `b` inside `a`, at any depth, and `a > b` matches `b` directly inside `a`.
+//!   A leading `>` only matches top-level nodes.
+//!
+//! Arguments are the children of their command or environment, and the nodes of an argument
+//! are the children of that argument.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::SyntaxTree;
+//! use untex::latex::query::query;
+//!
+//! let source = r"\begin{figure}
+//!   \includegraphics{plot}
+//!   \caption{A plot, see \cite{ref}.}
+//! \end{figure}
+//! \[ \frac{1}{2} \]";
+//! let tree = SyntaxTree::parse(source);
+//! let text = |selector| -> Vec<_> {
+//!     query(&tree, selector)
+//!         .unwrap()
+//!         .iter()
+//!         .map(|m| &source[m.span()])
+//!         .collect()
+//! };
+//!
+//! assert_eq!(
+//!     text("env[name=figure] > cmd[name=caption]"),
+//!     vec![r"\caption{A plot, see \cite{ref}.}"]
+//! );
+//! assert_eq!(text("cmd[name=cite] arg(1)"), vec!["{ref}"]);
+//! assert_eq!(text("math:display cmd[name=frac]"), vec![r"\frac{1}{2}"]);
+//! ```
+
+use crate::error::{Error, Result};
+use crate::latex::parse::{Argument, Node, SyntaxTree};
+use crate::latex::token::Span;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+/// Kind of element matched by a compound selector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Any,
+    Command,
+    Environment,
+    Math,
+    Group,
+    Comment,
+    Text,
+    /// An argument, optionally at a given position (starting at 1).
+    Argument(Option<usize>),
+}
+
+/// Attribute compared by a condition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Attribute {
+    Name,
+    Text,
+}
+
+/// Comparison operator of a condition.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Prefix,
+    Suffix,
+    Contains,
+}
+
+/// A condition on an attribute, e.g., `[name=figure]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Condition {
+    attribute: Attribute,
+    operator: Operator,
+    value: String,
+}
+
+impl Condition {
+    /// Return whether the attribute value satisfies this condition.
+    ///
+    /// A missing attribute never does.
+    fn matches(&self, value: Option<&str>) -> bool {
+        let value = match value {
+            Some(value) => value,
+            None => return false,
+        };
+        match self.operator {
+            Operator::Equal => value == self.value,
+            Operator::NotEqual => value != self.value,
+            Operator::Prefix => value.starts_with(&self.value),
+            Operator::Suffix => value.ends_with(&self.value),
+            Operator::Contains => value.contains(&self.value),
+        }
+    }
+}
+
+/// A pseudo-class, e.g., `:display`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PseudoClass {
+    Display,
+    Inline,
+    Starred,
+    Optional,
+    Required,
+}
+
+/// A kind, with conditions and pseudo-classes, e.g., `env[name=figure]:starred`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Compound {
+    kind: Kind,
+    conditions: Vec<Condition>,
+    pseudo_classes: Vec<PseudoClass>,
+}
+
+/// Relation between two consecutive compound selectors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+}
+
+/// A sequence of compound selectors.
+///
+/// The combinator of the first compound relates it to the root of the tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Selector {
+    steps: Vec<(Combinator, Compound)>,
+}
+
+/// A node, or an argument, matched by a [`Query`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Match<'a, 'source> {
+    /// A node.
+    Node(&'a Node<'source>),
+    /// An argument of a command or an environment.
+    Argument(&'a Argument<'source>),
+}
+
+impl<'a, 'source> Match<'a, 'source> {
+    /// Return the span of the match.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        match self {
+            Match::Node(node) => node.span(),
+            Match::Argument(argument) => argument.span(),
+        }
+    }
+}
+
+/// A node, or an argument, with its position in the tree.
+struct Element<'a, 'source> {
+    item: Match<'a, 'source>,
+    parent: Option<usize>,
+    /// Position among the arguments, starting at 1, or 0 for nodes.
+    index: usize,
+}
+
+impl<'a, 'source> Element<'a, 'source> {
+    /// Return the name of a command or an environment.
+    fn name(&self) -> Option<&'source str> {
+        match self.item {
+            Match::Node(Node::Command(command)) => Some(command.name),
+            Match::Node(Node::Environment(environment)) => Some(environment.name),
+            _ => None,
+        }
+    }
+}
+
+/// Flatten nodes, and their arguments, in document order.
+fn flatten<'a, 'source>(
+    nodes: &'a [Node<'source>],
+    parent: Option<usize>,
+    elements: &mut Vec<Element<'a, 'source>>,
+) {
+    for node in nodes {
+        let id = elements.len();
+        elements.push(Element {
+            item: Match::Node(node),
+            parent,
+            index: 0,
+        });
+
+        let (args, children): (&[Argument], &[Node]) = match node {
+            Node::Command(command) => (&command.args, &[]),
+            Node::Environment(environment) => (&environment.args, &environment.children),
+            Node::Group(group) => (&[], &group.children),
+            Node::Math(math) => (&[], &math.children),
+            _ => (&[], &[]),
+        };
+        for (i, argument) in args.iter().enumerate() {
+            let arg_id = elements.len();
+            elements.push(Element {
+                item: Match::Argument(argument),
+                parent: Some(id),
+                index: i + 1,
+            });
+            if let Some(group) = argument.group() {
+                flatten(&group.children, Some(arg_id), elements);
+            }
+        }
+        flatten(children, Some(id), elements);
+    }
+}
+
+impl Compound {
+    /// Return whether an element matches this compound selector.
+    fn matches(&self, element: &Element, source: &str) -> bool {
+        let kind = match (self.kind, element.item) {
+            (Kind::Any, _) => true,
+            (Kind::Command, Match::Node(Node::Command(_)))
+            | (Kind::Environment, Match::Node(Node::Environment(_)))
+            | (Kind::Math, Match::Node(Node::Math(_)))
+            | (Kind::Group, Match::Node(Node::Group(_)))
+            | (Kind::Comment, Match::Node(Node::Comment(_)))
+            | (Kind::Text, Match::Node(Node::Text(_))) => true,
+            (Kind::Math, Match::Node(Node::Environment(environment))) => environment.is_math(),
+            (Kind::Argument(index), Match::Argument(_)) => {
+                index.map_or(true, |index| index == element.index)
+            }
+            _ => false,
+        };
+
+        kind && self.conditions.iter().all(|condition| {
+            let value = match condition.attribute {
+                Attribute::Name => element.name(),
+                Attribute::Text => Some(&source[element.item.span()]),
+            };
+            condition.matches(value)
+        }) && self
+            .pseudo_classes
+            .iter()
+            .all(|pseudo_class| match (pseudo_class, element.item) {
+                (PseudoClass::Display, Match::Node(Node::Math(math))) => math.kind.is_display(),
+                (PseudoClass::Display, Match::Node(Node::Environment(environment))) => {
+                    environment.is_math() && environment.name != "math"
+                }
+                (PseudoClass::Inline, Match::Node(Node::Math(math))) => math.kind.is_inline(),
+                (PseudoClass::Inline, Match::Node(Node::Environment(environment))) => {
+                    environment.name == "math"
+                }
+                (PseudoClass::Starred, Match::Node(Node::Command(command))) => command
+                    .args
+                    .iter()
+                    .any(|arg| matches!(arg, Argument::Star(_))),
+                (PseudoClass::Starred, Match::Node(Node::Environment(environment))) => {
+                    environment.name.ends_with('*')
+                }
+                (PseudoClass::Optional, Match::Argument(Argument::Optional(_)))
+                | (PseudoClass::Required, Match::Argument(Argument::Required(_))) => true,
+                _ => false,
+            })
+    }
+}
+
+impl Selector {
+    /// Return whether the element at `i` matches the steps of this selector up to `k`.
+    fn matches_at(&self, k: usize, elements: &[Element], i: usize, source: &str) -> bool {
+        let (combinator, compound) = &self.steps[k];
+
+        if !compound.matches(&elements[i], source) {
+            return false;
+        }
+        let parent = elements[i].parent;
+
+        if k == 0 {
+            return *combinator == Combinator::Descendant || parent.is_none();
+        }
+        match combinator {
+            Combinator::Child => parent.map_or(false, |parent| {
+                self.matches_at(k - 1, elements, parent, source)
+            }),
+            Combinator::Descendant => {
+                let mut ancestor = parent;
+                while let Some(a) = ancestor {
+                    if self.matches_at(k - 1, elements, a, source) {
+                        return true;
+                    }
+                    ancestor = elements[a].parent;
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Parser for the selector syntax.
+struct Parser<'a> {
+    query: &'a str,
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn err(&self) -> Error {
+        Error::InvalidQuery(self.query.to_string())
+    }
+
+    /// Consume `c` if it is the next character.
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    /// Skip whitespaces, and return whether any was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let mut skipped = false;
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {
+            skipped = true;
+        }
+        skipped
+    }
+
+    /// Read a word made of ASCII alphanumeric characters, or `*`.
+    fn word(&mut self) -> String {
+        if self.eat('*') {
+            return "*".to_string();
+        }
+        let mut word = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_alphanumeric) {
+            word.push(c);
+        }
+        word
+    }
+
+    fn selector(&mut self) -> Result<Selector> {
+        let mut steps = Vec::new();
+        let mut combinator = Combinator::Descendant;
+        let mut pending = false;
+
+        self.skip_whitespace();
+        loop {
+            if self.eat('>') {
+                if pending {
+                    return Err(self.err());
+                }
+                combinator = Combinator::Child;
+                pending = true;
+                self.skip_whitespace();
+            }
+            match self.chars.peek() {
+                None | Some(',') => break,
+                _ => (),
+            }
+            steps.push((combinator, self.compound()?));
+            combinator = Combinator::Descendant;
+            pending = false;
+            self.skip_whitespace();
+        }
+
+        if steps.is_empty() || pending {
+            return Err(self.err());
+        }
+        Ok(Selector { steps })
+    }
+
+    fn compound(&mut self) -> Result<Compound> {
+        let word = self.word();
+        let kind = match word.as_str() {
+            "" | "*" => Kind::Any,
+            "cmd" => Kind::Command,
+            "env" => Kind::Environment,
+            "math" => Kind::Math,
+            "group" => Kind::Group,
+            "comment" => Kind::Comment,
+            "text" => Kind::Text,
+            "arg" if self.eat('(') => {
+                self.skip_whitespace();
+                let index = self.word().parse::<usize>().map_err(|_| self.err())?;
+                self.skip_whitespace();
+                if index == 0 || !self.eat(')') {
+                    return Err(self.err());
+                }
+                Kind::Argument(Some(index))
+            }
+            "arg" => Kind::Argument(None),
+            _ => return Err(self.err()),
+        };
+        let mut compound = Compound {
+            kind,
+            conditions: Vec::new(),
+            pseudo_classes: Vec::new(),
+        };
+
+        loop {
+            if self.eat('[') {
+                compound.conditions.push(self.condition()?);
+            } else if self.eat(':') {
+                let pseudo_class = match self.word().as_str() {
+                    "display" => PseudoClass::Display,
+                    "inline" => PseudoClass::Inline,
+                    "starred" => PseudoClass::Starred,
+                    "optional" => PseudoClass::Optional,
+                    "required" => PseudoClass::Required,
+                    _ => return Err(self.err()),
+                };
+                compound.pseudo_classes.push(pseudo_class);
+            } else {
+                break;
+            }
+        }
+
+        if word.is_empty() && compound.conditions.is_empty() && compound.pseudo_classes.is_empty() {
+            return Err(self.err());
+        }
+        Ok(compound)
+    }
+
+    /// Parse a condition, after its opening bracket.
+    fn condition(&mut self) -> Result<Condition> {
+        self.skip_whitespace();
+        let attribute = match self.word().as_str() {
+            "name" => Attribute::Name,
+            "text" => Attribute::Text,
+            _ => return Err(self.err()),
+        };
+        self.skip_whitespace();
+        let operator = match self.chars.next() {
+            Some('=') => Operator::Equal,
+            Some(c) if self.eat('=') => match c {
+                '!' => Operator::NotEqual,
+                '^' => Operator::Prefix,
+                '$' => Operator::Suffix,
+                '*' => Operator::Contains,
+                _ => return Err(self.err()),
+            },
+            _ => return Err(self.err()),
+        };
+        self.skip_whitespace();
+
+        let mut value = String::new();
+        match self.chars.next_if(|&c| c == '"' || c == '\'') {
+            Some(quote) => loop {
+                match self.chars.next() {
+                    Some(c) if c == quote => break,
+                    Some(c) => value.push(c),
+                    None => return Err(self.err()),
+                }
+            },
+            None => {
+                while let Some(c) = self.chars.next_if(|&c| c != ']') {
+                    value.push(c);
+                }
+                value.truncate(value.trim_end().len());
+            }
+        }
+        self.skip_whitespace();
+
+        if !self.eat(']') {
+            return Err(self.err());
+        }
+        Ok(Condition {
+            attribute,
+            operator,
+            value,
+        })
+    }
+}
+
+/// A parsed list of selectors, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Query {
+    selectors: Vec<Selector>,
+}
+
+impl Query {
+    /// Return the nodes and arguments of a tree matched by any of the selectors,
+    /// in document order.
+    #[must_use]
+    pub fn select<'a, 'source>(&self, tree: &'a SyntaxTree<'source>) -> Vec<Match<'a, 'source>> {
+        let mut elements = Vec::new();
+        flatten(tree.nodes(), None, &mut elements);
+
+        (0..elements.len())
+            .filter(|&i| {
+                self.selectors.iter().any(|selector| {
+                    selector.matches_at(selector.steps.len() - 1, &elements, i, tree.source())
+                })
+            })
+            .map(|i| elements[i].item)
+            .collect()
+    }
+}
+
+impl FromStr for Query {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            query: s,
+            chars: s.chars().peekable(),
+        };
+        let mut selectors = vec![parser.selector()?];
+
+        while parser.eat(',') {
+            selectors.push(parser.selector()?);
+        }
+        if parser.chars.peek().is_some() {
+            return Err(parser.err());
+        }
+        Ok(Self { selectors })
+    }
+}
+
+/// Parse a query and return the nodes and arguments of a tree it matches.
+pub fn query<'a, 'source>(
+    tree: &'a SyntaxTree<'source>,
+    selector: &str,
+) -> Result<Vec<Match<'a, 'source>>> {
+    Ok(selector.parse::<Query>()?.select(tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<'source>(source: &'source str, selector: &str) -> Vec<&'source str> {
+        let tree = SyntaxTree::parse(source);
+        query(&tree, selector)
+            .unwrap()
+            .iter()
+            .map(|m| &source[m.span()])
+            .collect()
+    }
+
+    #[test]
+    fn test_combinators() {
+        let source = r"\begin{figure}\centering{\caption{a}}\end{figure}\caption{b}";
+
+        assert_eq!(
+            texts(source, "env[name=figure] cmd[name=caption]"),
+            vec![r"\caption{a}"]
+        );
+        assert!(texts(source, "env[name=figure] > cmd[name=caption]").is_empty());
+        assert_eq!(texts(source, "env > group > cmd"), vec![r"\caption{a}"]);
+        assert_eq!(texts(source, "> cmd[name=caption]"), vec![r"\caption{b}"]);
+        assert_eq!(
+            texts(source, "cmd[name=caption]"),
+            vec![r"\caption{a}", r"\caption{b}"]
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        let source = r"\cite[p. 3]{key} \section*{Title} \begin{tabular}{cc}\end{tabular}";
+
+        assert_eq!(texts(source, "cmd[name=cite] arg(1)"), vec!["[p. 3]"]);
+        assert_eq!(
+            texts(source, "cmd[name=cite] > arg:required"),
+            vec!["{key}"]
+        );
+        assert_eq!(texts(source, "cmd:starred > arg(2)"), vec!["{Title}"]);
+        assert_eq!(texts(source, "env > arg"), vec!["{cc}"]);
+        assert_eq!(texts(source, "arg(1) > text"), vec!["p. 3", "cc"]);
+    }
+
+    #[test]
+    fn test_math() {
+        let source = r"$\frac{a}{b}$ \[\frac{c}{d}\] \begin{equation*}\frac{e}{f}\end{equation*}";
+
+        assert_eq!(
+            texts(source, "math:display cmd[name=frac]"),
+            vec![r"\frac{c}{d}", r"\frac{e}{f}"]
+        );
+        assert_eq!(
+            texts(source, "math:inline cmd[name=frac]"),
+            vec![r"\frac{a}{b}"]
+        );
+        assert_eq!(texts(source, "env:starred").len(), 1);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let source = r"\section{Intro} \subsection{Goals} % TODO: fix
+\subsubsection{Other}";
+
+        assert_eq!(
+            texts(source, "cmd[name$=section][text$='s}']"),
+            vec![r"\subsection{Goals}"]
+        );
+        assert_eq!(
+            texts(source, "cmd[name^=sub][name!=subsection]"),
+            vec![r"\subsubsection{Other}"]
+        );
+        assert_eq!(
+            texts(source, r#"comment[text*="TODO:"]"#),
+            vec!["% TODO: fix"]
+        );
+        assert_eq!(
+            texts(source, "cmd[name = section], comment"),
+            vec![r"\section{Intro}", "% TODO: fix"]
+        );
+    }
+
+    #[test]
+    fn test_parse_err() {
+        for selector in [
+            "",
+            "foo",
+            "cmd >",
+            "> > cmd",
+            "cmd[name]",
+            "cmd[name=a",
+            "cmd[size=a]",
+            "cmd:bold",
+            "arg(0)",
+            "arg(x)",
+            "cmd,",
+            "cmd)",
+        ] {
+            assert!(
+                matches!(selector.parse::<Query>(), Err(Error::InvalidQuery(_))),
+                "{selector:?} should not parse"
+            );
+        }
+    }
+}
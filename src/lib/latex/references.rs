@@ -0,0 +1,332 @@
+//! Index of labels, references and citations.
+//!
+//! A [`ReferenceIndex`] records every `\label`, every reference to a label, e.g., `\ref`
+//! or `\cref`, and every citation, e.g., `\cite` or `\parencite`, with its span and its
+//! enclosing environment. Comma-separated lists, e.g., `\cref{fig:a,fig:b}` or
+//! `\cite{a, b}`, result in one entry per key.
+//!
+//! Keys containing a `#`, e.g., `\label{#1}` in a macro definition, are ignored.
+//!
+//! For a multi-file document, build the index from the expanded source of a
+//! [`Project`](crate::latex::project::Project), and map spans back to their files
+//! with [`Expanded::locate`](crate::latex::project::Expanded::locate).
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::SyntaxTree;
+//! use untex::latex::references::ReferenceIndex;
+//!
+//! let source = r"\begin{equation}\label{eq:euler} e^{i\pi} + 1 = 0 \end{equation}
+//! See \eqref{eq:euler}, \cref{eq:euler,eq:other} and \parencite[p. 3]{euler1748}.";
+//! let tree = SyntaxTree::parse(source);
+//! let index = ReferenceIndex::from_tree(&tree);
+//!
+//! let label = index.definition("eq:euler").unwrap();
+//! assert_eq!(label.environment, Some("equation"));
+//! assert_eq!(&source[label.key_span.clone()], "eq:euler");
+//!
+//! let commands: Vec<_> = index.references_to("eq:euler").map(|r| r.command).collect();
+//! assert_eq!(commands, vec!["eqref", "cref"]);
+//!
+//! let undefined: Vec<_> = index.undefined_references().map(|r| r.key).collect();
+//! assert_eq!(undefined, vec!["eq:other"]);
+//! assert_eq!(index.citations_of("euler1748").count(), 1);
+//! ```
+
+use crate::latex::keyval::parse_group;
+use crate::latex::parse::{Argument, Command, Environment, Group, SyntaxTree};
+use crate::latex::token::Span;
+use crate::latex::visit::{walk_command, walk_environment, Visit};
+
+/// Kind of entry in a [`ReferenceIndex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum EntryKind {
+    /// A label definition, i.e., `\label{key}`.
+    Label,
+    /// A reference to a label, e.g., `\ref{key}`.
+    Reference,
+    /// A citation of a bibliography entry, e.g., `\cite{key}`.
+    Citation,
+}
+
+impl EntryKind {
+    /// Return the kind of entry created by a command name, without the leading backslash.
+    #[must_use]
+    pub fn from_command(name: &str) -> Option<Self> {
+        match name {
+            "label" => Some(EntryKind::Label),
+            "ref" | "eqref" | "pageref" | "autoref" | "Autoref" | "nameref" | "Nameref"
+            | "vref" | "Vref" | "cref" | "Cref" | "cpageref" | "Cpageref" | "labelcref"
+            | "labelcpageref" | "namecref" | "nameCref" | "lcnamecref" | "crefrange"
+            | "Crefrange" | "cpagerefrange" | "Cpagerefrange" | "subref" => {
+                Some(EntryKind::Reference)
+            }
+            "cite" | "nocite" | "citep" | "citet" | "citealp" | "citealt" | "citeauthor"
+            | "citeyear" | "citeyearpar" | "Citep" | "Citet" | "Citealp" | "Citealt"
+            | "Citeauthor" | "citenum" | "citetext" | "Cite" | "parencite" | "Parencite"
+            | "footcite" | "footcitetext" | "textcite" | "Textcite" | "smartcite" | "Smartcite"
+            | "autocite" | "Autocite" | "supercite" | "fullcite" | "footfullcite" | "citetitle"
+            | "citedate" | "citeurl" | "volcite" | "Volcite" | "pvolcite" | "Pvolcite"
+            | "fvolcite" | "ftvolcite" | "svolcite" | "Svolcite" | "tvolcite" | "Tvolcite"
+            | "avolcite" | "Avolcite" | "cites" | "Cites" | "parencites" | "Parencites"
+            | "footcites" | "footcitetexts" | "smartcites" | "Smartcites" | "textcites"
+            | "Textcites" | "supercites" | "autocites" | "Autocites" => Some(EntryKind::Citation),
+            _ => None,
+        }
+    }
+}
+
+/// A label, reference or citation key, with the command it appears in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry<'source> {
+    /// Kind of entry.
+    pub kind: EntryKind,
+    /// Key, e.g., `fig:plot`.
+    pub key: &'source str,
+    /// Span of the key.
+    pub key_span: Span,
+    /// Name of the command, without the leading backslash, e.g., `cref`.
+    pub command: &'source str,
+    /// Span of the whole command.
+    pub span: Span,
+    /// Name of the innermost environment enclosing the command, if any.
+    pub environment: Option<&'source str>,
+}
+
+/// Labels, references and citations of a document, see the [module documentation](self).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferenceIndex<'source> {
+    /// Label definitions, in order of appearance.
+    pub labels: Vec<Entry<'source>>,
+    /// References to labels, in order of appearance.
+    pub references: Vec<Entry<'source>>,
+    /// Citations, in order of appearance.
+    pub citations: Vec<Entry<'source>>,
+}
+
+impl<'source> ReferenceIndex<'source> {
+    /// Build the index of a syntax tree.
+    #[must_use]
+    pub fn from_tree(tree: &SyntaxTree<'source>) -> Self {
+        let mut collector = Collector {
+            source: tree.source(),
+            environments: Vec::new(),
+            index: Self::default(),
+        };
+        tree.visit(&mut collector);
+        collector.index
+    }
+
+    /// Return the first definition of a label, if any.
+    #[must_use]
+    pub fn definition(&self, key: &str) -> Option<&Entry<'source>> {
+        self.labels.iter().find(|label| label.key == key)
+    }
+
+    /// Return an iterator over all the definitions of a label.
+    ///
+    /// A label should only be defined once, see [`ReferenceIndex::duplicate_labels`].
+    pub fn definitions<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Entry<'source>> {
+        self.labels.iter().filter(move |label| label.key == key)
+    }
+
+    /// Return an iterator over the references to a label.
+    pub fn references_to<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Entry<'source>> {
+        self.references
+            .iter()
+            .filter(move |reference| reference.key == key)
+    }
+
+    /// Return an iterator over the citations of a bibliography entry.
+    pub fn citations_of<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a Entry<'source>> {
+        self.citations
+            .iter()
+            .filter(move |citation| citation.key == key)
+    }
+
+    /// Return the entry whose key contains a given position, if any,
+    /// e.g., for go-to-definition.
+    #[must_use]
+    pub fn entry_at(&self, position: usize) -> Option<&Entry<'source>> {
+        self.labels
+            .iter()
+            .chain(&self.references)
+            .chain(&self.citations)
+            .find(|entry| entry.key_span.start <= position && position <= entry.key_span.end)
+    }
+
+    /// Return an iterator over the references to labels that are not defined.
+    pub fn undefined_references(&self) -> impl Iterator<Item = &Entry<'source>> {
+        self.references
+            .iter()
+            .filter(move |reference| self.definition(reference.key).is_none())
+    }
+
+    /// Return an iterator over the labels that are never referenced.
+    pub fn unreferenced_labels(&self) -> impl Iterator<Item = &Entry<'source>> {
+        self.labels
+            .iter()
+            .filter(move |label| self.references_to(label.key).next().is_none())
+    }
+
+    /// Return an iterator over the labels that were already defined before.
+    pub fn duplicate_labels(&self) -> impl Iterator<Item = &Entry<'source>> {
+        self.labels
+            .iter()
+            .enumerate()
+            .filter(move |(i, label)| self.labels[..*i].iter().any(|l| l.key == label.key))
+            .map(|(_, label)| label)
+    }
+}
+
+/// Collect the entries of the index.
+struct Collector<'source> {
+    source: &'source str,
+    environments: Vec<&'source str>,
+    index: ReferenceIndex<'source>,
+}
+
+impl<'source> Collector<'source> {
+    /// Return the keys of a required argument, with their spans.
+    fn keys(&self, kind: EntryKind, group: &Group<'source>) -> Vec<(&'source str, Span)> {
+        let keys = match kind {
+            EntryKind::Label => {
+                let span = group.inner_span();
+                let text = &self.source[span.clone()];
+                let start = span.start + text.len() - text.trim_start().len();
+                let key = text.trim();
+                vec![(key, start..start + key.len())]
+            }
+            _ => parse_group(group, self.source)
+                .into_iter()
+                .map(|keyval| (keyval.key, keyval.key_span))
+                .collect(),
+        };
+        keys.into_iter()
+            .filter(|(key, _)| !key.is_empty() && !key.contains('#'))
+            .collect()
+    }
+}
+
+impl<'source> Visit<'source> for Collector<'source> {
+    fn visit_environment(&mut self, environment: &Environment<'source>) {
+        self.environments.push(environment.name);
+        walk_environment(self, environment);
+        self.environments.pop();
+    }
+
+    fn visit_command(&mut self, command: &Command<'source>) {
+        if let Some(kind) = EntryKind::from_command(command.name) {
+            for arg in command.args.iter() {
+                let group = match arg {
+                    Argument::Required(group) => group,
+                    _ => continue,
+                };
+                for (key, key_span) in self.keys(kind, group) {
+                    let entry = Entry {
+                        kind,
+                        key,
+                        key_span,
+                        command: command.name,
+                        span: command.span.clone(),
+                        environment: self.environments.last().copied(),
+                    };
+                    match kind {
+                        EntryKind::Label => self.index.labels.push(entry),
+                        EntryKind::Reference => self.index.references.push(entry),
+                        EntryKind::Citation => self.index.citations.push(entry),
+                    }
+                }
+            }
+        }
+        walk_command(self, command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys<'a>(entries: impl Iterator<Item = &'a Entry<'a>>) -> Vec<&'a str> {
+        entries.map(|entry| entry.key).collect()
+    }
+
+    #[test]
+    fn test_labels() {
+        let source = r"\section{Intro}\label{ sec:intro }
+\begin{figure}\caption{Plot\label{fig:plot}}\end{figure}
+\newcommand{\mylabel}[1]{\label{#1}}
+\label{sec:intro}";
+        let tree = SyntaxTree::parse(source);
+        let index = ReferenceIndex::from_tree(&tree);
+
+        assert_eq!(
+            keys(index.labels.iter()),
+            vec!["sec:intro", "fig:plot", "sec:intro"]
+        );
+        assert_eq!(&source[index.labels[0].key_span.clone()], "sec:intro");
+        assert_eq!(index.labels[0].environment, None);
+        assert_eq!(index.labels[1].environment, Some("figure"));
+        assert_eq!(&source[index.labels[1].span.clone()], r"\label{fig:plot}");
+        assert_eq!(keys(index.duplicate_labels()), vec!["sec:intro"]);
+        assert_eq!(index.definitions("sec:intro").count(), 2);
+    }
+
+    #[test]
+    fn test_references() {
+        let source = r"\label{a}\label{b}\label{c}
+\ref{a} \pageref*{a} \autoref{b} \nameref{b}
+\Cref{a, b,
+  c} \crefrange{a}{d}";
+        let tree = SyntaxTree::parse(source);
+        let index = ReferenceIndex::from_tree(&tree);
+
+        assert_eq!(
+            keys(index.references.iter()),
+            vec!["a", "a", "b", "b", "a", "b", "c", "a", "d"]
+        );
+        assert_eq!(
+            index
+                .references_to("b")
+                .map(|r| r.command)
+                .collect::<Vec<_>>(),
+            vec!["autoref", "nameref", "Cref"]
+        );
+        assert_eq!(&source[index.references[6].key_span.clone()], "c");
+        assert_eq!(keys(index.undefined_references()), vec!["d"]);
+        assert!(index.unreferenced_labels().next().is_none());
+    }
+
+    #[test]
+    fn test_citations() {
+        let source = r"\cite{a,b} \cite[see][p. 3]{c} \textcite{d}
+\begin{itemize}\item \parencites[1]{e}[2]{f, g}\end{itemize} \nocite{*}";
+        let tree = SyntaxTree::parse(source);
+        let index = ReferenceIndex::from_tree(&tree);
+
+        assert_eq!(
+            keys(index.citations.iter()),
+            vec!["a", "b", "c", "d", "e", "f", "g", "*"]
+        );
+        assert_eq!(index.citations[2].command, "cite");
+        assert_eq!(index.citations[5].environment, Some("itemize"));
+        assert_eq!(index.citations_of("c").count(), 1);
+    }
+
+    #[test]
+    fn test_entry_at() {
+        let source = r"\label{a} \ref{a} \cite{b}";
+        let tree = SyntaxTree::parse(source);
+        let index = ReferenceIndex::from_tree(&tree);
+
+        let entry = index.entry_at(15).unwrap();
+        assert_eq!(entry.kind, EntryKind::Reference);
+        assert_eq!(
+            index.definition(entry.key).unwrap().key_span,
+            index.labels[0].key_span
+        );
+        assert_eq!(index.entry_at(24).unwrap().kind, EntryKind::Citation);
+        assert!(index.entry_at(9).is_none());
+    }
+}
@@ -0,0 +1,200 @@
+//! Serialization of spanned tokens, with their location in the source.
+//!
+//! [`Token`] and [`CategoryCode`](crate::tex::category_codes::CategoryCode) derive
+//! [`Serialize`] and [`Deserialize`] when the `serde` feature is enabled.
+//! A [`Span`] is only a byte range, so [`locate`] pairs each [`SpannedToken`]
+//! with a [`Location`], rendered as `{start, end, line, column}`.
+//!
+//! Tokens are rendered as `{"kind": ..., "value": ...}`, where `value` is only
+//! present for tokens that hold data, e.g., the name of an environment.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::serialize::{locate, LocatedToken, Location};
+//! use untex::prelude::*;
+//!
+//! let source = "Hello\n\\begin{document}";
+//! let tokens: Vec<_> = locate(source, Token::lexer(source).spanned()).collect();
+//!
+//! assert_eq!(
+//!     tokens[2],
+//!     LocatedToken {
+//!         token: Token::EnvironmentBegin("document"),
+//!         span: Location {
+//!             start: 6,
+//!             end: 22,
+//!             line: 2,
+//!             column: 1,
+//!         },
+//!     }
+//! );
+//! ```
+
+use crate::latex::lines::{ColumnUnit, LineIndex};
+use crate::latex::token::{Span, SpannedToken, Token};
+use serde::{Deserialize, Serialize};
+
+/// Location of a [`Span`] in a source.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Location {
+    /// Byte offset of the start of the span.
+    pub start: usize,
+    /// Byte offset of the end of the span (exclusive).
+    pub end: usize,
+    /// Line of the start of the span, starting at 1.
+    pub line: usize,
+    /// Column of the start of the span, in characters, starting at 1.
+    pub column: usize,
+}
+
+impl Location {
+    /// Return the location of a span, using a line index of its source.
+    #[must_use]
+    pub fn new(index: &LineIndex<'_>, span: Span) -> Self {
+        let position = index.position(span.start, ColumnUnit::Char).one_based();
+
+        Self {
+            line: position.line,
+            column: position.column,
+            start: span.start,
+            end: span.end,
+        }
+    }
+
+    /// Return the span, i.e., the byte range, of this location.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.start..self.end
+    }
+}
+
+/// A [`SpannedToken`] whose span is rendered as a [`Location`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocatedToken<'source> {
+    /// Token.
+    #[serde(borrow)]
+    pub token: Token<'source>,
+    /// Location of the token.
+    pub span: Location,
+}
+
+impl<'source> From<LocatedToken<'source>> for SpannedToken<'source> {
+    fn from(located: LocatedToken<'source>) -> Self {
+        let span = located.span.span();
+        (located.token, span)
+    }
+}
+
+/// Iterator returned by [`locate`].
+#[derive(Debug)]
+pub struct Locate<'source, I> {
+    index: LineIndex<'source>,
+    iter: I,
+}
+
+impl<'source, I> Iterator for Locate<'source, I>
+where
+    I: Iterator<Item = SpannedToken<'source>>,
+{
+    type Item = LocatedToken<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (token, span) = self.iter.next()?;
+        Some(LocatedToken {
+            token,
+            span: Location::new(&self.index, span),
+        })
+    }
+}
+
+/// Attach a [`Location`] to each spanned token of `source`, see [`LineIndex`].
+pub fn locate<'source, I>(source: &'source str, iter: I) -> Locate<'source, I::IntoIter>
+where
+    I: IntoIterator<Item = SpannedToken<'source>>,
+{
+    Locate {
+        index: LineIndex::new(source),
+        iter: iter.into_iter(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tex::category_codes::CategoryCode;
+    use logos::Logos;
+
+    #[test]
+    fn test_locate() {
+        let source = "é $x$\n\n  \\end{a}";
+        let tokens: Vec<_> = locate(source, Token::lexer(source).spanned()).collect();
+        let positions: Vec<_> = tokens
+            .iter()
+            .map(|located| (located.span.line, located.span.column))
+            .collect();
+
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1),
+                (1, 2),
+                (1, 3),
+                (1, 4),
+                (1, 5),
+                (1, 6),
+                (2, 1),
+                (3, 1),
+                (3, 3)
+            ]
+        );
+        let spanned: Vec<SpannedToken> = tokens.into_iter().map(Into::into).collect();
+        assert_eq!(spanned, Token::lexer(source).spanned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_locate_unordered() {
+        let source = "a\nb";
+        let tokens: Vec<_> =
+            locate(source, vec![(Token::Word, 2..3), (Token::Word, 0..1)]).collect();
+        assert_eq!(tokens[0].span.line, 2);
+        assert_eq!(tokens[1].span.line, 1);
+    }
+
+    #[test]
+    fn test_json() {
+        let source = "\\begin{document}%";
+        let tokens: Vec<_> = locate(source, Token::lexer(source).spanned()).collect();
+        let json = serde_json::to_string(&tokens).unwrap();
+
+        assert_eq!(
+            json,
+            r#"[{"token":{"kind":"EnvironmentBegin","value":"document"},"span":{"start":0,"end":16,"line":1,"column":1}},{"token":{"kind":"Comment"},"span":{"start":16,"end":17,"line":1,"column":17}}]"#
+        );
+        let parsed: Vec<LocatedToken> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, tokens);
+    }
+
+    #[test]
+    fn test_category_code() {
+        let json = serde_json::to_string(&CategoryCode::EscapeChar).unwrap();
+        assert_eq!(json, r#""EscapeChar""#);
+        assert_eq!(
+            serde_json::from_str::<CategoryCode>(&json).unwrap(),
+            CategoryCode::EscapeChar
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "strum")]
+    fn test_token_discriminants() {
+        use crate::latex::token::TokenDiscriminants;
+
+        let json = serde_json::to_string(&TokenDiscriminants::EnvironmentBegin).unwrap();
+        assert_eq!(json, r#""EnvironmentBegin""#);
+        assert_eq!(
+            serde_json::from_str::<TokenDiscriminants>(&json).unwrap(),
+            TokenDiscriminants::EnvironmentBegin
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Argument signatures of commands and environments.
+//!
+//! A [`Token::CommandName`](crate::latex::token::Token::CommandName) carries no
+//! information about the arguments that follow it. This module provides a
+//! [`Registry`] that maps command (and environment) names to their [`Signature`],
+//! written with the `xparse` argument specification syntax, e.g., `"s o m"`
+//! for `\section`.
+//!
+//! Default signatures, see [`Registry::latex2e`], are taken from the
+//! "*LATEX2e: An unofficial reference manual*".
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::signature::{ArgSpec, Registry};
+//!
+//! let mut registry = Registry::latex2e();
+//! registry.define_command("vect", "m").unwrap();
+//!
+//! assert_eq!(registry.command("vect").unwrap().args(), &[ArgSpec::Required]);
+//! assert_eq!(registry.command("frac").unwrap().to_string(), "m m");
+//! ```
+
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Specification of a single argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgSpec {
+    /// An optional star `'*'`, written `s`.
+    Star,
+    /// An optional argument, delimited by brackets, written `o` or `O{default}`.
+    Optional(Option<String>),
+    /// A required argument, delimited by braces, written `m`.
+    Required,
+}
+
+impl fmt::Display for ArgSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgSpec::Star => f.write_str("s"),
+            ArgSpec::Optional(None) => f.write_str("o"),
+            ArgSpec::Optional(Some(default)) => write!(f, "O{{{default}}}"),
+            ArgSpec::Required => f.write_str("m"),
+        }
+    }
+}
+
+/// Ordered list of arguments taken by a command or an environment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signature {
+    args: Vec<ArgSpec>,
+}
+
+impl Signature {
+    /// Create a new signature from a list of argument specifications.
+    #[must_use]
+    pub fn new(args: Vec<ArgSpec>) -> Self {
+        Self { args }
+    }
+
+    /// Create the signature of a `\newcommand`-like definition, i.e.,
+    /// `n` required arguments, the first one being optional if `default` is set.
+    #[must_use]
+    pub fn with_arity(n: usize, default: Option<String>) -> Self {
+        let mut args = vec![ArgSpec::Required; n];
+        if let Some(first) = args.first_mut() {
+            if default.is_some() {
+                *first = ArgSpec::Optional(default);
+            }
+        }
+        Self { args }
+    }
+
+    /// Return the argument specifications.
+    #[must_use]
+    pub fn args(&self) -> &[ArgSpec] {
+        &self.args
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{arg}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Signature {
+    type Err = Error;
+
+    /// Parse an `xparse` argument specification.
+    ///
+    /// Supported types are `m`, `o`, `O{default}`, `s`, and `d[]` and `D[]{default}`
+    /// that are equivalent to `o` and `O{default}`. Prefixes `+` and `!` are ignored.
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || Error::InvalidArgSpec(s.to_string());
+        let mut args = Vec::new();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' | '\t' | '\n' | '+' | '!' => (),
+                'm' => args.push(ArgSpec::Required),
+                'o' => args.push(ArgSpec::Optional(None)),
+                's' => args.push(ArgSpec::Star),
+                'O' => args.push(ArgSpec::Optional(Some(
+                    read_braced(&mut chars).ok_or_else(err)?,
+                ))),
+                'd' | 'D' => {
+                    if chars.next() != Some('[') || chars.next() != Some(']') {
+                        return Err(err());
+                    }
+                    let default = if c == 'D' {
+                        Some(read_braced(&mut chars).ok_or_else(err)?)
+                    } else {
+                        None
+                    };
+                    args.push(ArgSpec::Optional(default));
+                }
+                _ => return Err(err()),
+            }
+        }
+
+        Ok(Self { args })
+    }
+}
+
+/// Read a balanced `{...}` group and return its content.
+fn read_braced<I>(chars: &mut std::iter::Peekable<I>) -> Option<String>
+where
+    I: Iterator<Item = char>,
+{
+    if chars.next()? != '{' {
+        return None;
+    }
+    let mut depth = 1;
+    let mut content = String::new();
+
+    for c in chars.by_ref() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(content);
+                }
+            }
+            _ => (),
+        }
+        content.push(c);
+    }
+    None
+}
+
+/// Commands defined in LaTeX2e, with their argument specification.
+static LATEX2E_COMMANDS: &[(&str, &str)] = &[
+    // Document structure
+    ("documentclass", "o m"),
+    ("usepackage", "o m"),
+    ("RequirePackage", "o m"),
+    ("LoadClass", "o m"),
+    ("part", "s o m"),
+    ("chapter", "s o m"),
+    ("section", "s o m"),
+    ("subsection", "s o m"),
+    ("subsubsection", "s o m"),
+    ("paragraph", "s o m"),
+    ("subparagraph", "s o m"),
+    ("appendix", ""),
+    ("tableofcontents", ""),
+    ("listoffigures", ""),
+    ("listoftables", ""),
+    ("title", "m"),
+    ("author", "o m"),
+    ("date", "m"),
+    ("thanks", "m"),
+    ("maketitle", ""),
+    ("today", ""),
+    ("input", "m"),
+    ("include", "m"),
+    ("includeonly", "m"),
+    // Cross references
+    ("label", "m"),
+    // Starred variants, as with `hyperref`
+    ("ref", "s m"),
+    ("pageref", "s m"),
+    // Two optional arguments, as with `natbib` and `biblatex`
+    ("cite", "o o m"),
+    ("nocite", "m"),
+    ("bibliography", "m"),
+    ("bibliographystyle", "m"),
+    ("bibitem", "o m"),
+    // Definitions
+    ("newcommand", "s m o o m"),
+    ("renewcommand", "s m o o m"),
+    ("providecommand", "s m o o m"),
+    ("newenvironment", "s m o o m m"),
+    ("renewenvironment", "s m o o m m"),
+    ("newtheorem", "m o m o"),
+    ("newcounter", "m o"),
+    ("setcounter", "m m"),
+    ("addtocounter", "m m"),
+    ("stepcounter", "m"),
+    ("refstepcounter", "m"),
+    ("value", "m"),
+    ("newlength", "m"),
+    ("setlength", "m m"),
+    ("addtolength", "m m"),
+    ("settowidth", "m m"),
+    ("settoheight", "m m"),
+    ("settodepth", "m m"),
+    // Fonts
+    ("textrm", "m"),
+    ("textsf", "m"),
+    ("texttt", "m"),
+    ("textmd", "m"),
+    ("textbf", "m"),
+    ("textup", "m"),
+    ("textit", "m"),
+    ("textsl", "m"),
+    ("textsc", "m"),
+    ("textnormal", "m"),
+    ("emph", "m"),
+    ("underline", "m"),
+    ("mathrm", "m"),
+    ("mathsf", "m"),
+    ("mathtt", "m"),
+    ("mathbf", "m"),
+    ("mathit", "m"),
+    ("mathcal", "m"),
+    ("mathnormal", "m"),
+    // Boxes and spaces
+    ("mbox", "m"),
+    ("fbox", "m"),
+    ("makebox", "o o m"),
+    ("framebox", "o o m"),
+    ("parbox", "o o o m m"),
+    ("raisebox", "m o o m"),
+    ("rule", "o m m"),
+    ("hspace", "s m"),
+    ("vspace", "s m"),
+    ("\\", "s o"),
+    ("newline", ""),
+    ("linebreak", "o"),
+    ("nolinebreak", "o"),
+    ("pagebreak", "o"),
+    ("nopagebreak", "o"),
+    ("newpage", ""),
+    ("clearpage", ""),
+    ("cleardoublepage", ""),
+    ("centering", ""),
+    ("item", "o"),
+    // Floats and footnotes
+    ("caption", "o m"),
+    ("footnote", "o m"),
+    ("footnotemark", "o"),
+    ("footnotetext", "o m"),
+    ("marginpar", "o m"),
+    // Tables
+    ("multicolumn", "m m m"),
+    ("cline", "m"),
+    ("hline", ""),
+    // Math
+    ("frac", "m m"),
+    ("sqrt", "o m"),
+    ("overline", "m"),
+    ("underbrace", "m"),
+    ("overbrace", "m"),
+    ("hat", "m"),
+    ("widehat", "m"),
+    ("tilde", "m"),
+    ("widetilde", "m"),
+    ("bar", "m"),
+    ("vec", "m"),
+    ("dot", "m"),
+    ("ddot", "m"),
+    ("stackrel", "m m"),
+    ("left", ""),
+    ("middle", ""),
+    ("right", ""),
+    ("big", ""),
+    ("Big", ""),
+    ("bigg", ""),
+    ("Bigg", ""),
+    ("bigl", ""),
+    ("Bigl", ""),
+    ("biggl", ""),
+    ("Biggl", ""),
+    ("bigr", ""),
+    ("Bigr", ""),
+    ("biggr", ""),
+    ("Biggr", ""),
+];
+
+/// Environments defined in LaTeX2e, with their argument specification.
+static LATEX2E_ENVIRONMENTS: &[(&str, &str)] = &[
+    ("document", ""),
+    ("abstract", ""),
+    ("array", "o m"),
+    ("center", ""),
+    ("description", ""),
+    ("displaymath", ""),
+    ("enumerate", ""),
+    ("eqnarray", ""),
+    ("eqnarray*", ""),
+    ("equation", ""),
+    ("equation*", ""),
+    ("figure", "o"),
+    ("figure*", "o"),
+    ("filecontents", "o m"),
+    ("filecontents*", "o m"),
+    ("flushleft", ""),
+    ("flushright", ""),
+    ("itemize", ""),
+    ("list", "m m"),
+    ("math", ""),
+    ("minipage", "o o o m"),
+    ("picture", ""),
+    ("quotation", ""),
+    ("quote", ""),
+    ("tabbing", ""),
+    ("table", "o"),
+    ("table*", "o"),
+    ("tabular", "o m"),
+    ("tabular*", "m o m"),
+    ("thebibliography", "m"),
+    ("theorem", "o"),
+    ("titlepage", ""),
+    ("verbatim", ""),
+    ("verbatim*", ""),
+    ("verse", ""),
+];
+
+/// Registry of command and environment signatures.
+///
+/// Commands are referred to without their leading backslash.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Registry {
+    commands: HashMap<String, Signature>,
+    environments: HashMap<String, Signature>,
+}
+
+impl Registry {
+    /// Create a new registry with the LaTeX2e commands and environments,
+    /// see [`Registry::latex2e`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self::latex2e()
+    }
+
+    /// Create an empty registry.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self {
+            commands: HashMap::new(),
+            environments: HashMap::new(),
+        }
+    }
+
+    /// Create a registry with the LaTeX2e commands and environments.
+    #[must_use]
+    pub fn latex2e() -> Self {
+        let parse = |(name, spec): &(&str, &str)| {
+            (
+                name.to_string(),
+                spec.parse().expect("built-in signatures should be valid"),
+            )
+        };
+        Self {
+            commands: LATEX2E_COMMANDS.iter().map(parse).collect(),
+            environments: LATEX2E_ENVIRONMENTS.iter().map(parse).collect(),
+        }
+    }
+
+    /// Return the signature of a command, if known.
+    #[must_use]
+    pub fn command(&self, name: &str) -> Option<&Signature> {
+        self.commands.get(name)
+    }
+
+    /// Return the signature of an environment, if known.
+    #[must_use]
+    pub fn environment(&self, name: &str) -> Option<&Signature> {
+        self.environments.get(name)
+    }
+
+    /// Insert (or replace) the signature of a command.
+    pub fn insert_command<S>(&mut self, name: S, signature: Signature) -> Option<Signature>
+    where
+        S: Into<String>,
+    {
+        self.commands.insert(name.into(), signature)
+    }
+
+    /// Insert (or replace) the signature of an environment.
+    pub fn insert_environment<S>(&mut self, name: S, signature: Signature) -> Option<Signature>
+    where
+        S: Into<String>,
+    {
+        self.environments.insert(name.into(), signature)
+    }
+
+    /// Parse an argument specification and insert it as a command signature.
+    pub fn define_command<S>(&mut self, name: S, spec: &str) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.insert_command(name, spec.parse()?);
+        Ok(())
+    }
+
+    /// Parse an argument specification and insert it as an environment signature.
+    pub fn define_environment<S>(&mut self, name: S, spec: &str) -> Result<()>
+    where
+        S: Into<String>,
+    {
+        self.insert_environment(name, spec.parse()?);
+        Ok(())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() {
+        let signature: Signature = "s o O{x y} m".parse().unwrap();
+        assert_eq!(
+            signature.args(),
+            &[
+                ArgSpec::Star,
+                ArgSpec::Optional(None),
+                ArgSpec::Optional(Some("x y".to_string())),
+                ArgSpec::Required,
+            ]
+        );
+        assert_eq!(signature.to_string(), "s o O{x y} m");
+    }
+
+    #[test]
+    fn test_parse_spec_delimited() {
+        let signature: Signature = "+d[] !D[]{{a}b} m".parse().unwrap();
+        assert_eq!(signature.to_string(), "o O{{a}b} m",);
+    }
+
+    #[test]
+    fn test_parse_spec_err() {
+        for spec in ["x", "O{unbalanced", "O", "d()"] {
+            assert!(
+                spec.parse::<Signature>().is_err(),
+                "{spec} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_with_arity() {
+        assert_eq!(Signature::with_arity(2, None).to_string(), "m m");
+        assert_eq!(
+            Signature::with_arity(2, Some("x".to_string())).to_string(),
+            "O{x} m"
+        );
+        assert_eq!(
+            Signature::with_arity(0, Some("x".to_string())).to_string(),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_latex2e() {
+        let registry = Registry::latex2e();
+        assert_eq!(registry.command("section").unwrap().to_string(), "s o m");
+        assert_eq!(registry.command("\\").unwrap().to_string(), "s o");
+        assert_eq!(registry.environment("tabular").unwrap().to_string(), "o m");
+        assert!(registry.command("unknown").is_none());
+    }
+
+    #[test]
+    fn test_constructors() {
+        assert_eq!(Registry::new(), Registry::latex2e());
+        assert_eq!(Registry::default(), Registry::latex2e());
+        assert!(Registry::empty().command("section").is_none());
+        assert!(Registry::empty().environment("tabular").is_none());
+    }
+}
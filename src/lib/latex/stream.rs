@@ -0,0 +1,420 @@
+//! Lexing sources from any [`io::Read`], chunk by chunk, in bounded memory.
+//!
+//! [`StreamLexer`] reads a source, e.g., standard input or a multi-gigabyte file,
+//! and splits it into [`Chunk`]s of about [`DEFAULT_CAPACITY`] bytes.
+//! Chunks end right before a line break, but never right after `\begin` or `\end`,
+//! whose environment name may follow on the next line, so lexing each chunk gives the same tokens
+//! as lexing the whole source, and [`Chunk::tokens`] yields them with absolute byte offsets.
+//!
+//! A line longer than the capacity is split before a space, or at any character boundary
+//! if it has no space, in which case the tokens around the split may differ.
+//!
+//! With [`StreamLexer::with_verbatim`], chunks never end inside a verbatim environment,
+//! so that each chunk can be lexed with a [`VerbatimLexer`].
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::stream::StreamLexer;
+//! use untex::prelude::*;
+//!
+//! let source = "\\section{Data}\n% x y\n1 2\n3 4\n";
+//! let mut spans = Vec::new();
+//!
+//! for chunk in StreamLexer::new(source.as_bytes()).with_capacity(8) {
+//!     spans.extend(chunk.unwrap().tokens().map(|(_, span)| span));
+//! }
+//!
+//! let expected: Vec<_> = Token::lexer(source).spanned().map(|(_, span)| span).collect();
+//! assert_eq!(spans, expected);
+//! ```
+
+use crate::latex::encoding::invalid_utf8;
+use crate::latex::macros::harvest;
+use crate::latex::token::{Span, SpannedToken, Token};
+use crate::latex::verbatim::{VerbatimConfig, VerbatimLexer};
+use logos::Logos;
+use std::io::{self, Read};
+
+/// Default size, in bytes, of the chunks read by a [`StreamLexer`].
+pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+/// Number of bytes read at once from the reader.
+const READ_SIZE: usize = 8 * 1024;
+
+/// A chunk of source, read by a [`StreamLexer`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    offset: usize,
+    text: String,
+}
+
+impl Chunk {
+    /// Return the offset, in bytes, of this chunk in the whole source.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Return the span of this chunk in the whole source.
+    #[must_use]
+    pub fn span(&self) -> Span {
+        self.offset..self.offset + self.text.len()
+    }
+
+    /// Return the text of this chunk.
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Return the text of this chunk, consuming it.
+    #[must_use]
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    /// Return the tokens of this chunk, with spans in the whole source.
+    pub fn tokens(&self) -> impl Iterator<Item = SpannedToken<'_>> + '_ {
+        let offset = self.offset;
+        Token::lexer(&self.text)
+            .spanned()
+            .map(move |(token, span)| (token, span.start + offset..span.end + offset))
+    }
+}
+
+/// Iterator over the [`Chunk`]s of a source read from an [`io::Read`],
+/// see the [module documentation](self).
+#[derive(Debug)]
+pub struct StreamLexer<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    offset: usize,
+    capacity: usize,
+    target: usize,
+    verbatim: Option<VerbatimConfig>,
+    eof: bool,
+}
+
+impl<R> StreamLexer<R>
+where
+    R: Read,
+{
+    /// Create a new lexer over a reader, with the default capacity.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            offset: 0,
+            capacity: DEFAULT_CAPACITY,
+            target: DEFAULT_CAPACITY,
+            verbatim: None,
+            eof: false,
+        }
+    }
+
+    /// Set the size, in bytes, above which a chunk is emitted.
+    #[must_use]
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self.target = self.capacity;
+        self
+    }
+
+    /// Set the verbatim environments that chunks must not split.
+    ///
+    /// A verbatim environment longer than the capacity is kept in a single chunk.
+    /// Environments defined in the source (see [`VerbatimConfig`]'s `Extend` implementation)
+    /// are added as chunks are read.
+    #[must_use]
+    pub fn with_verbatim(mut self, config: VerbatimConfig) -> Self {
+        self.verbatim = Some(config);
+        self
+    }
+
+    /// Return the verbatim environments that chunks must not split, if any,
+    /// including those defined in the chunks read so far.
+    #[must_use]
+    pub fn verbatim(&self) -> Option<&VerbatimConfig> {
+        self.verbatim.as_ref()
+    }
+
+    /// Read more bytes into the buffer.
+    fn fill(&mut self) -> io::Result<()> {
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_SIZE, 0);
+
+        match self.reader.read(&mut self.buffer[len..]) {
+            Ok(read) => {
+                self.buffer.truncate(len + read);
+                self.eof = read == 0;
+                Ok(())
+            }
+            Err(error) => {
+                self.buffer.truncate(len);
+                match error.kind() {
+                    io::ErrorKind::Interrupted => Ok(()),
+                    _ => Err(error),
+                }
+            }
+        }
+    }
+
+    /// Return where the buffer should be split, if anywhere.
+    fn split(&mut self) -> Option<usize> {
+        let buffer = self.buffer.as_slice();
+        let mut end = match line_break(buffer, buffer.len()) {
+            Some(end) => end,
+            // A single line, longer than the capacity
+            None if line_len(buffer) >= self.capacity => boundary(buffer)?,
+            None => return None,
+        };
+
+        if let (Some(config), Ok(text)) = (&mut self.verbatim, std::str::from_utf8(&buffer[..end]))
+        {
+            // Environments defined in the chunk may already be used in it
+            let definitions = harvest(text, VerbatimLexer::new(text, config));
+            config.extend(&definitions);
+
+            if let Some(begin) = open_verbatim(text, config) {
+                end = line_break(buffer, begin)?;
+            }
+        }
+        Some(end)
+    }
+
+    /// Remove the first `end` bytes from the buffer, as a chunk.
+    fn take(&mut self, end: usize) -> io::Result<Chunk> {
+        let bytes: Vec<u8> = self.buffer.drain(..end).collect();
+        let text = String::from_utf8(bytes).map_err(|e| invalid_utf8(e, self.offset))?;
+        let chunk = Chunk {
+            offset: self.offset,
+            text,
+        };
+        self.offset += end;
+        Ok(chunk)
+    }
+}
+
+impl<R> Iterator for StreamLexer<R>
+where
+    R: Read,
+{
+    type Item = io::Result<Chunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.eof {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                return Some(self.take(self.buffer.len()));
+            }
+            if self.buffer.len() >= self.target {
+                if let Some(end) = self.split() {
+                    self.target = self.capacity;
+                    return Some(self.take(end));
+                }
+                // E.g., the buffer ends inside a verbatim environment
+                self.target = self.buffer.len() + self.buffer.len().max(self.capacity);
+            }
+            if let Err(error) = self.fill() {
+                return Some(Err(error));
+            }
+        }
+    }
+}
+
+/// Return whether `bytes` ends with an odd number of backslashes,
+/// i.e., whether the next character is escaped.
+fn is_escaped(bytes: &[u8]) -> bool {
+    bytes.iter().rev().take_while(|b| **b == b'\\').count() % 2 == 1
+}
+
+/// Return the position of the last line break before `before`, if any.
+///
+/// A line break escaped by a backslash, or following `\begin` or `\end`, is skipped.
+fn line_break(buffer: &[u8], before: usize) -> Option<usize> {
+    buffer[..before]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, b)| **b == b'\n')
+        .map(|(i, _)| match i.checked_sub(1) {
+            Some(j) if buffer[j] == b'\r' => j,
+            _ => i,
+        })
+        .find(|i| *i > 0 && !is_escaped(&buffer[..*i]) && !ends_with_environment(&buffer[..*i]))
+}
+
+/// Return whether `bytes` ends with `\begin` or `\end`, and spaces,
+/// whose environment name may follow on the next line.
+fn ends_with_environment(bytes: &[u8]) -> bool {
+    let len = bytes
+        .iter()
+        .rposition(|b| *b != b' ' && *b != b'\t')
+        .map_or(0, |i| i + 1);
+    let bytes = &bytes[..len];
+
+    [&b"\\begin"[..], &b"\\end"[..]]
+        .iter()
+        .any(|command| bytes.ends_with(command) && !is_escaped(&bytes[..len - command.len()]))
+}
+
+/// Return the length of the line in `buffer`, without the leading line break, if any.
+fn line_len(buffer: &[u8]) -> usize {
+    let leading = match buffer {
+        [b'\r', b'\n', ..] => 2,
+        [b'\n', ..] => 1,
+        _ => 0,
+    };
+    buffer.len() - leading
+}
+
+/// Return the last position where a line without line break can be split, if any.
+///
+/// The line is split before its last run of spaces, or before its last character.
+fn boundary(buffer: &[u8]) -> Option<usize> {
+    let is_space = |b: &u8| *b == b' ' || *b == b'\t';
+    let is_char_boundary = |b: &u8| (*b as i8) >= -0x40;
+
+    (1..buffer.len())
+        .rev()
+        .find(|i| is_space(&buffer[*i]) && !is_space(&buffer[*i - 1]) && !is_escaped(&buffer[..*i]))
+        .or_else(|| {
+            (1..buffer.len())
+                .rev()
+                .find(|i| is_char_boundary(&buffer[*i]) && !is_escaped(&buffer[..*i]))
+        })
+}
+
+/// Return the start of the verbatim environment left open at the end of `text`, if any.
+fn open_verbatim(text: &str, config: &VerbatimConfig) -> Option<usize> {
+    let mut open = None;
+
+    for (token, span) in VerbatimLexer::new(text, config) {
+        match token {
+            Token::EnvironmentBegin(name) if config.environment(name).is_some() => {
+                open = Some(span.start);
+            }
+            Token::EnvironmentEnd(_) => open = None,
+            _ => (),
+        }
+    }
+    open
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning one byte at a time.
+    struct OneByte<'a>(&'a [u8]);
+
+    impl<'a> Read for OneByte<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((byte, rest)) if !buf.is_empty() => {
+                    buf[0] = *byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn chunks(source: &str, capacity: usize) -> Vec<Chunk> {
+        StreamLexer::new(OneByte(source.as_bytes()))
+            .with_capacity(capacity)
+            .with_verbatim(VerbatimConfig::default())
+            .collect::<io::Result<_>>()
+            .unwrap()
+    }
+
+    fn texts(source: &str, capacity: usize) -> Vec<String> {
+        chunks(source, capacity)
+            .into_iter()
+            .map(Chunk::into_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_tokens() {
+        let source = "\\begin{document}\r\n$a\\\nb$ % c\n\n\\\\\n\\end{document}";
+
+        for capacity in 17..source.len() + 2 {
+            let chunks = chunks(source, capacity);
+            let text: String = chunks.iter().map(Chunk::as_str).collect();
+            assert_eq!(text, source);
+
+            let tokens: Vec<_> = chunks.iter().flat_map(Chunk::tokens).collect();
+            let expected: Vec<_> = Token::lexer(source).spanned().collect();
+            assert_eq!(tokens, expected, "capacity {capacity}");
+        }
+    }
+
+    #[test]
+    fn test_environment_line_break() {
+        let source =
+            "\\begin{document}\nx\n\\begin\n{itemize}\n\\item a\n\\end \n{itemize}\n\\end{document}\n\\end\nx";
+
+        for capacity in 17..source.len() + 2 {
+            let chunks = chunks(source, capacity);
+            let tokens: Vec<_> = chunks.iter().flat_map(Chunk::tokens).collect();
+            let expected: Vec<_> = Token::lexer(source).spanned().collect();
+            assert_eq!(tokens, expected, "capacity {capacity}");
+        }
+    }
+
+    #[test]
+    fn test_split() {
+        let source = "ab\ncd\r\nef";
+        assert_eq!(texts(source, 3), vec!["ab", "\ncd", "\r\nef"]);
+
+        let spans: Vec<_> = chunks(source, 3).iter().map(Chunk::span).collect();
+        assert_eq!(spans, vec![0..2, 2..5, 5..9]);
+    }
+
+    #[test]
+    fn test_long_line() {
+        assert_eq!(texts("abc  def", 4), vec!["abc", "  d", "ef"]);
+        assert_eq!(texts("ab\\cd", 3), vec!["ab", "\\c", "d"]);
+        assert_eq!(texts("éèà", 2), vec!["é", "è", "à"]);
+    }
+
+    #[test]
+    fn test_verbatim() {
+        let source = "a\n\\begin{verbatim}\n$\n\n%\n\\end{verbatim}\nb\nc";
+        assert_eq!(
+            texts(source, 20),
+            vec![
+                "a",
+                "\n\\begin{verbatim}\n$\n\n%\n\\end{verbatim}\nb",
+                "\nc"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defined_verbatim() {
+        let source = "\\lstnewenvironment{code}{}{}\n\\begin{code}\n$\n%\n\\end{code}\nb";
+        assert_eq!(
+            texts(source, 40),
+            vec![
+                "\\lstnewenvironment{code}{}{}",
+                "\n\\begin{code}\n$\n%\n\\end{code}\nb"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let results: Vec<_> = StreamLexer::new(&b"a\n\xff"[..]).with_capacity(1).collect();
+        assert_eq!(results[0].as_ref().unwrap().as_str(), "a");
+
+        let error = results.last().unwrap().as_ref().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "invalid UTF-8 at byte 2");
+    }
+}
@@ -0,0 +1,291 @@
+//! Parsing tables and alignment environments into rows and cells.
+//!
+//! A [`Table`] is built from a `tabular`-like environment (e.g., `tabular`, `tabularx`,
+//! `longtable` or `array`), or from an alignment environment (e.g., `align` or `pmatrix`).
+//! Its body is split into [`Row`]s and cells with a [`Splitter`], see
+//! [`latex::align`](crate::latex::align).
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::{Node, SyntaxTree};
+//! use untex::latex::table::Table;
+//!
+//! let source = r"\begin{tabular}{l|r}
+//!   \hline
+//!   Name & Value \\
+//!   \multicolumn{2}{c}{Total} \\
+//!   \hline
+//! \end{tabular}";
+//! let tree = SyntaxTree::parse(source);
+//! let table = match &tree.nodes()[0] {
+//!     Node::Environment(environment) => Table::from_environment(environment, source).unwrap(),
+//!     _ => unreachable!(),
+//! };
+//!
+//! assert_eq!(&source[table.spec.clone().unwrap()], "l|r");
+//! assert_eq!(table.rows.len(), 2);
+//!
+//! let cells: Vec<_> = table.rows[0].cells.iter().map(|cell| &source[cell.clone()]).collect();
+//! assert_eq!(cells, vec!["Name", "Value"]);
+//! assert_eq!(table.rows[0].rules.len(), 1);
+//! assert_eq!(table.rows[1].multicolumns[0].columns, Some(2));
+//! assert_eq!(table.end_rules.len(), 1);
+//! ```
+
+use crate::latex::align::{self, Splitter};
+use crate::latex::parse::{Argument, Environment, SyntaxTree};
+use crate::latex::token::Span;
+use crate::latex::visit::{walk_environment, Visit};
+
+pub use crate::latex::align::Multicolumn;
+
+/// Environments whose last required argument is a column specification.
+static TABULAR_ENVIRONMENTS: &[&str] = &[
+    "tabular",
+    "tabular*",
+    "tabularx",
+    "tabulary",
+    "longtable",
+    "longtable*",
+    "array",
+    "supertabular",
+    "xtabular",
+];
+
+/// Alignment environments, without column specification.
+static ALIGNMENT_ENVIRONMENTS: &[&str] = &[
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "aligned",
+    "alignedat",
+    "flalign",
+    "flalign*",
+    "eqnarray",
+    "eqnarray*",
+    "split",
+    "cases",
+    "matrix",
+    "pmatrix",
+    "bmatrix",
+    "Bmatrix",
+    "vmatrix",
+    "Vmatrix",
+    "smallmatrix",
+];
+
+/// Return whether an environment is supported by [`Table::from_environment`].
+#[must_use]
+pub fn is_table(name: &str) -> bool {
+    TABULAR_ENVIRONMENTS.contains(&name) || ALIGNMENT_ENVIRONMENTS.contains(&name)
+}
+
+/// A row of a [`Table`], whose cells are spans trimmed from whitespaces, comments and rules.
+///
+/// Empty cells have an empty span, located where the cell starts.
+pub type Row = align::Row<Span>;
+
+/// A table, or an alignment environment, split into rows and cells.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table<'source> {
+    /// Environment name.
+    pub name: &'source str,
+    /// Span of the column specification, if any, e.g., `l|r` in `\begin{tabular}{l|r}`.
+    pub spec: Option<Span>,
+    /// Rows of the table.
+    pub rows: Vec<Row>,
+    /// Spans of the rules following the last row.
+    pub end_rules: Vec<Span>,
+    /// Span of the whole environment.
+    pub span: Span,
+}
+
+impl<'source> Table<'source> {
+    /// Build a table from an environment.
+    ///
+    /// Return [`None`] if the environment is not a table, see [`is_table`].
+    #[must_use]
+    pub fn from_environment(
+        environment: &Environment<'source>,
+        source: &'source str,
+    ) -> Option<Self> {
+        let name = environment.name;
+
+        let (spec, splitter) = if TABULAR_ENVIRONMENTS.contains(&name) {
+            let spec = environment.args.iter().rev().find_map(|arg| match arg {
+                Argument::Required(group) => Some(group.inner_span()),
+                _ => None,
+            });
+            (spec, Splitter::new(source, environment.inner_span().start))
+        } else if ALIGNMENT_ENVIRONMENTS.contains(&name) {
+            // Groups wrongly taken as arguments are the content of the first cell,
+            // except for the number of columns of `alignat`-like environments.
+            let skip = usize::from(name.starts_with("alignat") || name == "alignedat");
+            let start = match skip {
+                1 => environment
+                    .args
+                    .first()
+                    .map_or(environment.begin.end, |arg| arg.span().end),
+                _ => environment.begin.end,
+            };
+            let mut splitter = Splitter::new(source, start);
+            for arg in environment.args.iter().skip(skip) {
+                splitter.extend(arg.span());
+            }
+            (None, splitter)
+        } else {
+            return None;
+        };
+
+        let (rows, end_rules) = splitter.split(
+            &environment.children,
+            environment.inner_span().end,
+            |_, span, content| content.unwrap_or(span.start..span.start),
+        );
+
+        Some(Self {
+            name,
+            spec,
+            rows,
+            end_rules,
+            span: environment.span.clone(),
+        })
+    }
+}
+
+/// Return all tables in a syntax tree, including nested ones, in document order.
+#[must_use]
+pub fn tables<'source>(tree: &SyntaxTree<'source>) -> Vec<Table<'source>> {
+    let mut collector = Collector {
+        source: tree.source(),
+        tables: Vec::new(),
+    };
+    tree.visit(&mut collector);
+    collector.tables
+}
+
+struct Collector<'source> {
+    source: &'source str,
+    tables: Vec<Table<'source>>,
+}
+
+impl<'source> Visit<'source> for Collector<'source> {
+    fn visit_environment(&mut self, environment: &Environment<'source>) {
+        if let Some(table) = Table::from_environment(environment, self.source) {
+            self.tables.push(table);
+        }
+        walk_environment(self, environment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells<'source>(table: &Table<'_>, source: &'source str) -> Vec<Vec<&'source str>> {
+        table
+            .rows
+            .iter()
+            .map(|row| row.cells.iter().map(|cell| &source[cell.clone()]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_tabular() {
+        let source = r"\begin{tabular}[t]{|l|c|}
+  \toprule
+  a & {b & c} \\ % comment & d
+  \midrule
+  \& & $x & y$ \\[2pt]
+  \cmidrule(lr){1-2}
+  & \begin{tabular}{c} e \\ f \end{tabular}
+  \\ \bottomrule
+\end{tabular}";
+        let tables = tables(&SyntaxTree::parse(source));
+        assert_eq!(tables.len(), 2);
+
+        let table = &tables[0];
+        assert_eq!(table.name, "tabular");
+        assert_eq!(&source[table.spec.clone().unwrap()], "|l|c|");
+        assert_eq!(
+            cells(table, source),
+            vec![
+                vec!["a", "{b & c}"],
+                vec![r"\&", "$x & y$"],
+                vec!["", r"\begin{tabular}{c} e \\ f \end{tabular}"],
+            ]
+        );
+        assert_eq!(
+            &source[table.rows[1].separator.clone().unwrap()],
+            r"\\[2pt]"
+        );
+        assert_eq!(
+            &source[table.rows[2].rules[0].clone()],
+            r"\cmidrule(lr){1-2}"
+        );
+        assert_eq!(&source[table.end_rules[0].clone()], r"\bottomrule");
+
+        assert_eq!(cells(&tables[1], source), vec![vec!["e"], vec!["f"]]);
+    }
+
+    #[test]
+    fn test_multicolumn() {
+        let source = r"\begin{longtable}{lll}
+a & \multicolumn{2}{r}{bc} \\
+\multicolumn{x}{l}{d} & e & f
+\end{longtable}";
+        let tables = tables(&SyntaxTree::parse(source));
+        let table = &tables[0];
+
+        assert_eq!(
+            cells(table, source)[1],
+            vec![r"\multicolumn{x}{l}{d}", "e", "f"]
+        );
+        let multicolumn = &table.rows[0].multicolumns[0];
+        assert_eq!(multicolumn.cell, 1);
+        assert_eq!(multicolumn.columns, Some(2));
+        assert_eq!(&source[multicolumn.spec.clone().unwrap()], "r");
+        assert_eq!(&source[multicolumn.content.clone().unwrap()], "bc");
+        assert_eq!(table.rows[1].multicolumns[0].columns, None);
+        assert!(table.rows[1].separator.is_none());
+    }
+
+    #[test]
+    fn test_tabularx() {
+        let source = r"\begin{tabularx}{\linewidth}{lX}a&b\end{tabularx}";
+        let tables = tables(&SyntaxTree::parse(source));
+
+        assert_eq!(&source[tables[0].spec.clone().unwrap()], "lX");
+        assert_eq!(cells(&tables[0], source), vec![vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn test_alignment() {
+        let source = "$\\begin{pmatrix}{a} & b \\\\ c & d \\end{pmatrix}$
+\\begin{alignat}{2} x &= y & z &= w \\end{alignat}
+\\begin{align}\\end{align}";
+        let tables = tables(&SyntaxTree::parse(source));
+
+        assert_eq!(tables.len(), 3);
+        assert!(tables.iter().all(|table| table.spec.is_none()));
+        assert_eq!(
+            cells(&tables[0], source),
+            vec![vec!["{a}", "b"], vec!["c", "d"]]
+        );
+        assert_eq!(
+            cells(&tables[1], source),
+            vec![vec!["x", "= y", "z", "= w"]]
+        );
+        assert_eq!(cells(&tables[2], source), vec![vec![""]]);
+    }
+
+    #[test]
+    fn test_not_a_table() {
+        let source = r"\begin{itemize}a & b\end{itemize}";
+        assert!(tables(&SyntaxTree::parse(source)).is_empty());
+        assert!(is_table("tabular*"));
+        assert!(!is_table("figure"));
+    }
+}
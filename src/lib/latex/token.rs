@@ -11,16 +11,32 @@ use logos::{Lexer, Logos};
 #[cfg(feature = "strum")]
 use strum_macros::EnumDiscriminants;
 
+/// Return the environment name between the braces of `slice`, without surrounding whitespace.
+fn environment_name(slice: &str) -> &str {
+    let start = slice.find('{').map_or(0, |i| i + 1);
+    slice[start..slice.len() - 1].trim()
+}
+
 /// Callback for [`Token::EnvironmentBegin`] that returns the environment name.
 fn parse_environment_begin<'source>(lex: &mut Lexer<'source, Token<'source>>) -> &'source str {
-    let slice = lex.slice();
-    &slice[7..slice.len() - 1]
+    environment_name(lex.slice())
 }
 
 /// Callback for [`Token::EnvironmentEnd`] that returns the environment name.
 fn parse_environment_end<'source>(lex: &mut Lexer<'source, Token<'source>>) -> &'source str {
-    let slice = lex.slice();
-    &slice[5..slice.len() - 1]
+    environment_name(lex.slice())
+}
+
+/// Callback for [`Token::InvalidCommand`] that extends the token
+/// to the end of the escaped character, which may span several bytes.
+fn complete_invalid_command<'source>(lex: &mut Lexer<'source, Token<'source>>) {
+    let end = lex.span().end;
+    let source = lex.source();
+    let len = (end..=source.len())
+        .find(|&i| source.is_char_boundary(i))
+        .unwrap_or(end)
+        - end;
+    lex.bump(len);
 }
 
 /// Enumerates all meaningful tokens that can
@@ -28,7 +44,18 @@ fn parse_environment_end<'source>(lex: &mut Lexer<'source, Token<'source>>) -> &
 #[derive(Clone, Debug, Logos, PartialEq, Eq)]
 #[cfg_attr(feature = "strum", derive(EnumDiscriminants))]
 #[cfg_attr(feature = "cli", strum_discriminants(derive(clap::ValueEnum)))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", content = "value"))]
+#[cfg_attr(
+    all(feature = "serde", feature = "strum"),
+    strum_discriminants(derive(serde::Serialize, serde::Deserialize))
+)]
 pub enum Token<'source> {
+    /// An accented or special character, e.g., `\'e` or `\ss`, with its Unicode character.
+    ///
+    /// Never produced by [`Token::lexer`], see [`latex::accent`](crate::latex::accent).
+    Accented(char),
+
     /// And `'&'`, or "ampersand", character.
     #[token("&")]
     And,
@@ -67,7 +94,12 @@ pub enum Token<'source> {
 
     /// A valid command name, including leading backslash `\`,
     /// matching regex `r"\\[a-zA-Z]+"`.
+    ///
+    /// `\begin` and `\end` followed by spaces, and at most one line break,
+    /// but not by an environment name, also include that whitespace,
+    /// e.g., `"\\end  "` in plain TeX.
     #[regex(r"\\[a-zA-Z]+")]
+    #[regex(r"\\(begin|end)[ \t]*(\r?\n[ \t]*)?")]
     CommandName,
 
     /// A comment, matching anyway after a non-escaped percent-sign `'%'` is encountered.
@@ -103,13 +135,23 @@ pub enum Token<'source> {
     DoubleDollarSign,
 
     /// A valid environment begin, including leading backslash `\`
-    /// and environment name, matching regex `r"\\begin\{[a-zA-Z]+\*?\}"`.
-    #[regex(r"\\begin\{[a-zA-Z]+\*?\}", parse_environment_begin)]
+    /// and environment name, matching regex
+    /// `r"\\begin[ \t]*(\r?\n[ \t]*)?\{[^{}\\%\r\n]+\}"`.
+    ///
+    /// The name is stored without surrounding whitespace, e.g., `"figure"` for `\begin { figure }`.
+    /// A single line break, but not a blank line, may come before the opening brace.
+    #[regex(
+        r"\\begin[ \t]*(\r?\n[ \t]*)?\{[^{}\\%\r\n]+\}",
+        parse_environment_begin
+    )]
     EnvironmentBegin(&'source str),
 
     /// A valid environment end, including leading backslash `\`
-    /// and environment name, matching regex `r"\\end\{[a-zA-Z]+\*?\}"`.
-    #[regex(r"\\end\{[a-zA-Z]+\*?\}", parse_environment_end)]
+    /// and environment name, matching regex
+    /// `r"\\end[ \t]*(\r?\n[ \t]*)?\{[^{}\\%\r\n]+\}"`.
+    ///
+    /// The name is stored without surrounding whitespace, as for [`Token::EnvironmentBegin`].
+    #[regex(r"\\end[ \t]*(\r?\n[ \t]*)?\{[^{}\\%\r\n]+\}", parse_environment_end)]
     EnvironmentEnd(&'source str),
 
     /// Equal sign `'='` character.
@@ -160,7 +202,9 @@ pub enum Token<'source> {
 
     /// Indicates an invalid command name, that should match everything
     /// escaped sequence that has invalid syntax.
-    #[regex(r"\\[^a-zA-Z]")]
+    ///
+    /// The escaped character is always complete, even if not ASCII, e.g., `"\\é"`.
+    #[regex(r"\\[^a-zA-Z]", complete_invalid_command)]
     InvalidCommand,
 
     /// Indicates a newline, either with `'\n'` or `"\r\n"`.
@@ -176,9 +220,6 @@ pub enum Token<'source> {
     #[error]
     Other,
 
-    /// Variant to be allocated later by the user.
-    OwnedString(String),
-
     /// Right paresentheses `')'` character.
     #[token(")")]
     ParenClose,
@@ -212,9 +253,16 @@ pub enum Token<'source> {
     #[token("_")]
     Underscore,
 
-    /// Indicates an ASCII-letters only word
-    /// matching regex `"[a-zA-Z]+"`.
-    #[regex("[a-zA-Z]+")]
+    /// Verbatim content, e.g., the body of a `verbatim` environment.
+    ///
+    /// Never produced by [`Token::lexer`], see [`latex::verbatim`](crate::latex::verbatim).
+    Verbatim,
+
+    /// Indicates a word, made of Unicode letters and combining marks,
+    /// matching regex `r"[\p{L}\p{M}]+"`.
+    ///
+    /// Command names are still restricted to ASCII letters, see [`Token::CommandName`].
+    #[regex(r"[\p{L}\p{M}]+")]
     Word,
 }
 
@@ -342,6 +390,7 @@ mod tests {
             Token::DisplayMathClose,
             13..15,
         );
+        assert_token_positions!("Caf\\é ok", Token::InvalidCommand, 3..6);
     }
 
     #[test]
@@ -400,6 +449,51 @@ mod tests {
         assert_token_positions!(r"\end{equation}", Token::EnvironmentEnd("equation"), 0..14,);
     }
 
+    #[test]
+    fn token_environment_names() {
+        let source = "\\begin {figure}\\begin{ pgfonlayer2 }\\end{frame:main}\\begin{my-env*}\\end\t{@tempenv}";
+        let names: Vec<_> = Token::lexer(source)
+            .filter_map(|token| match token {
+                Token::EnvironmentBegin(name) | Token::EnvironmentEnd(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            names,
+            vec!["figure", "pgfonlayer2", "frame:main", "my-env*", "@tempenv"]
+        );
+        assert_token_positions!("\\end \t\\bye", Token::CommandName, 0..6, 6..10);
+        assert_token_positions!("\\end\r\n x", Token::CommandName, 0..7);
+    }
+
+    #[test]
+    fn token_environment_any_names() {
+        let source = "\\begin{théorème}\\end{a+b}\\begin{ my env }\\begin\n{figure}\\end \r\n {figure}\\end{\\envname}";
+        let names: Vec<_> = Token::lexer(source)
+            .filter_map(|token| match token {
+                Token::EnvironmentBegin(name) | Token::EnvironmentEnd(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(names, vec!["théorème", "a+b", "my env", "figure", "figure"]);
+        assert_token_positions!(
+            "\\begin\n{figure}\\begin\n\n{figure}",
+            Token::EnvironmentBegin("figure"),
+            0..15,
+        );
+        assert_token_positions!(
+            "\\begin\n{figure}\\begin\n\n{figure}",
+            Token::CommandName,
+            15..22
+        );
+        assert!(
+            Token::lexer("\\end{itemize\n\\item a % c\n\\item b}\\end{a%b}")
+                .all(|token| !matches!(token, Token::EnvironmentEnd(_)))
+        );
+    }
+
     #[test]
     fn token_equal_sign() {
         assert_token_positions!(r"Should match =, but not \=", Token::EqualSign, 13..14,);
@@ -538,6 +632,15 @@ mod tests {
         assert_token_positions!(r"Should match words", Token::Word, 0..6, 7..12, 13..18,);
     }
 
+    #[test]
+    fn token_word_unicode() {
+        assert_token_positions!("Jérome Straße", Token::Word, 0..7, 8..15,);
+        // "e" followed by a combining acute accent
+        assert_token_positions!("Cafe\u{301}!", Token::Word, 0..6,);
+        assert_token_positions!(r"\café", Token::Word, 4..6,);
+        assert_token_positions!(r"\café", Token::CommandName, 0..4,);
+    }
+
     #[test]
     fn test_document() {
         let source = r#"
@@ -0,0 +1,412 @@
+//! Verbatim-aware lexing, for `\verb`, `verbatim`, `lstlisting`, `minted`, etc.
+//!
+//! [`Token::lexer`] is context-free, so the content of verbatim regions is lexed
+//! as regular LaTeX: a `%` or a `$` in a code listing is seen as a comment or a math delimiter.
+//! [`VerbatimLexer`] wraps the lexer and emits the content of such regions
+//! as a single, opaque, [`Token::Verbatim`]:
+//!
+//! - for environments, `\begin{name}`, its arguments and `\end{name}` are lexed as usual,
+//!   and the token spans the content in between;
+//! - for commands, e.g., `\verb|...|`, the command name is lexed as usual,
+//!   and the token spans the rest, delimiters (and star) included.
+//!
+//! Verbatim environments and commands are configured with a [`VerbatimConfig`].
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::verbatim::{VerbatimConfig, VerbatimLexer};
+//! use untex::prelude::*;
+//!
+//! let source = r"\begin{minted}{rust}
+//! let cost = 5; // $5 or 50%
+//! \end{minted} and \verb|$|.";
+//! let config = VerbatimConfig::default();
+//! let tokens: Vec<_> = VerbatimLexer::new(source, &config).collect();
+//!
+//! assert!(tokens.iter().all(|(token, _)| *token != Token::DollarSign));
+//! assert!(tokens.iter().all(|(token, _)| *token != Token::Comment));
+//!
+//! let verbatims: Vec<_> = tokens
+//!     .iter()
+//!     .filter(|(token, _)| *token == Token::Verbatim)
+//!     .map(|(_, span)| &source[span.clone()])
+//!     .collect();
+//! assert_eq!(verbatims, vec!["\nlet cost = 5; // $5 or 50%\n", "|$|"]);
+//! ```
+
+use crate::latex::macros::{MacroDefinition, MacroKind};
+use crate::latex::signature::{ArgSpec, Signature};
+use crate::latex::token::{SpannedToken, Token};
+use logos::{Lexer, Logos};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Verbatim environments, with the signature of the arguments that precede their content.
+static VERBATIM_ENVIRONMENTS: &[(&str, &str)] = &[
+    ("verbatim", ""),
+    ("verbatim*", ""),
+    ("lstlisting", "o"),
+    ("minted", "o m"),
+    ("comment", ""),
+];
+
+/// Verbatim commands.
+static VERBATIM_COMMANDS: &[&str] = &["verb"];
+
+/// Set of verbatim environments and commands.
+///
+/// The default configuration contains the `verbatim`, `verbatim*`, `lstlisting`,
+/// `minted` and `comment` environments, and the `\verb` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerbatimConfig {
+    environments: HashMap<String, Signature>,
+    commands: HashSet<String>,
+}
+
+impl VerbatimConfig {
+    /// Create a new empty configuration.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            environments: HashMap::new(),
+            commands: HashSet::new(),
+        }
+    }
+
+    /// Return the signature of a verbatim environment, if any.
+    #[must_use]
+    pub fn environment(&self, name: &str) -> Option<&Signature> {
+        self.environments.get(name)
+    }
+
+    /// Return whether a command, without the leading backslash, is a verbatim command.
+    #[must_use]
+    pub fn is_command(&self, name: &str) -> bool {
+        self.commands.contains(name)
+    }
+
+    /// Insert a verbatim environment, with the signature of the arguments that
+    /// precede its content, e.g., `"o m"` for `minted`.
+    ///
+    /// Return the previous signature, if any.
+    pub fn insert_environment<S>(&mut self, name: S, signature: Signature) -> Option<Signature>
+    where
+        S: Into<String>,
+    {
+        self.environments.insert(name.into(), signature)
+    }
+
+    /// Remove a verbatim environment, and return its signature, if any.
+    pub fn remove_environment(&mut self, name: &str) -> Option<Signature> {
+        self.environments.remove(name)
+    }
+
+    /// Insert a verbatim command, without the leading backslash, e.g., `"lstinline"`.
+    ///
+    /// Return whether the command was not already present.
+    pub fn insert_command<S>(&mut self, name: S) -> bool
+    where
+        S: Into<String>,
+    {
+        self.commands.insert(name.into())
+    }
+
+    /// Remove a verbatim command, and return whether it was present.
+    pub fn remove_command(&mut self, name: &str) -> bool {
+        self.commands.remove(name)
+    }
+}
+
+impl Default for VerbatimConfig {
+    fn default() -> Self {
+        let mut config = Self::new();
+
+        for (name, spec) in VERBATIM_ENVIRONMENTS.iter() {
+            config.insert_environment(*name, spec.parse().expect("valid argument specification"));
+        }
+        for name in VERBATIM_COMMANDS.iter() {
+            config.insert_command(*name);
+        }
+        config
+    }
+}
+
+impl<'a, 'source> Extend<&'a MacroDefinition<'source>> for VerbatimConfig {
+    /// Insert every environment defined with `\lstnewenvironment`, ignoring other definitions.
+    ///
+    /// The arguments of such environments precede their content.
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = &'a MacroDefinition<'source>>,
+    {
+        for definition in iter {
+            if definition.kind == MacroKind::Environment
+                && definition.definer == "lstnewenvironment"
+            {
+                let signature = definition.signature.clone().unwrap_or_default();
+                self.insert_environment(definition.name, signature);
+            }
+        }
+    }
+}
+
+/// Lexer emitting [`Token::Verbatim`] for the content of verbatim regions,
+/// see the [module documentation](self).
+#[derive(Debug)]
+pub struct VerbatimLexer<'source, 'config> {
+    lexer: Lexer<'source, Token<'source>>,
+    config: &'config VerbatimConfig,
+    pending: VecDeque<SpannedToken<'source>>,
+}
+
+impl<'source, 'config> VerbatimLexer<'source, 'config> {
+    /// Create a new lexer over a source.
+    #[must_use]
+    pub fn new(source: &'source str, config: &'config VerbatimConfig) -> Self {
+        Self {
+            lexer: Token::lexer(source),
+            config,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Lex `len` bytes, starting from the end of the current token, as regular tokens,
+    /// and skip them.
+    fn lex_ahead(&mut self, len: usize) {
+        let start = self.lexer.span().end;
+        let slice = &self.lexer.remainder()[..len];
+        self.pending.extend(
+            Token::lexer(slice)
+                .spanned()
+                .map(|(token, span)| (token, span.start + start..span.end + start)),
+        );
+        self.lexer.bump(len);
+    }
+
+    /// Skip `len` bytes, starting from the end of the current token, as a verbatim token.
+    fn verbatim(&mut self, len: usize) {
+        let start = self.lexer.span().end;
+        if len > 0 {
+            self.pending
+                .push_back((Token::Verbatim, start..start + len));
+            self.lexer.bump(len);
+        }
+    }
+
+    /// Handle the content of a verbatim environment, after `\begin{name}`.
+    fn environment(&mut self, name: &str, signature: &Signature) {
+        let rest = self.lexer.remainder();
+        let args = arguments_len(rest, signature);
+        self.lex_ahead(args);
+
+        let rest = self.lexer.remainder();
+        let end = format!("\\end{{{name}}}");
+        match rest.find(&end) {
+            Some(i) => {
+                self.verbatim(i);
+                self.lex_ahead(end.len());
+            }
+            None => self.verbatim(rest.len()),
+        }
+    }
+
+    /// Handle the content of a verbatim command, e.g., `|...|` after `\verb`.
+    fn command(&mut self) {
+        let rest = self.lexer.remainder();
+        let mut chars = rest.char_indices();
+        let mut len = 0;
+
+        if rest.starts_with('*') {
+            chars.next();
+            len = 1;
+        }
+        if let Some((i, delimiter)) = chars.next() {
+            len = i + delimiter.len_utf8();
+            if !matches!(delimiter, '\n' | '\r') {
+                // An unterminated command ends at the end of the line.
+                for (i, c) in chars {
+                    if c == '\n' || c == '\r' {
+                        break;
+                    }
+                    len = i + c.len_utf8();
+                    if c == delimiter {
+                        break;
+                    }
+                }
+            } else {
+                len -= delimiter.len_utf8();
+            }
+        }
+        self.verbatim(len);
+    }
+}
+
+/// Return the length of the arguments at the start of `rest`, as specified by a signature.
+///
+/// Spaces are allowed before each argument, and braces are balanced within arguments.
+fn arguments_len(rest: &str, signature: &Signature) -> usize {
+    let mut len = 0;
+
+    for arg in signature.args() {
+        let start = rest.len() - rest[len..].trim_start_matches([' ', '\t']).len();
+        let slice = &rest[start..];
+
+        let open = match arg {
+            ArgSpec::Star => '*',
+            ArgSpec::Optional(_) => '[',
+            ArgSpec::Required => '{',
+        };
+        if !slice.starts_with(open) {
+            continue;
+        }
+        if open == '*' {
+            len = start + 1;
+            continue;
+        }
+
+        let mut depth = 0usize;
+        for (i, c) in slice.char_indices() {
+            let closed = match c {
+                '{' => {
+                    depth += 1;
+                    false
+                }
+                '}' => {
+                    depth = depth.saturating_sub(1);
+                    open == '{' && depth == 0
+                }
+                ']' => open == '[' && depth == 0,
+                _ => false,
+            };
+            if closed {
+                len = start + i + 1;
+                break;
+            }
+        }
+    }
+    len
+}
+
+impl<'source, 'config> Iterator for VerbatimLexer<'source, 'config> {
+    type Item = SpannedToken<'source>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+        let token = self.lexer.next()?;
+        let span = self.lexer.span();
+
+        match token {
+            Token::EnvironmentBegin(name) => {
+                if let Some(signature) = self.config.environment(name) {
+                    self.environment(name, signature);
+                }
+            }
+            Token::CommandName if self.config.is_command(&self.lexer.slice()[1..]) => {
+                self.command();
+            }
+            _ => (),
+        }
+        Some((token, span))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::latex::macros::harvest;
+
+    fn lex<'source>(
+        source: &'source str,
+        config: &VerbatimConfig,
+    ) -> Vec<(Token<'source>, &'source str)> {
+        VerbatimLexer::new(source, config)
+            .map(|(token, span)| (token, &source[span]))
+            .collect()
+    }
+
+    #[test]
+    fn test_environment() {
+        let source = "\\begin{verbatim}% $x$\n\\begin{a}\\end{verbatim}%";
+        assert_eq!(
+            lex(source, &VerbatimConfig::default()),
+            vec![
+                (Token::EnvironmentBegin("verbatim"), "\\begin{verbatim}"),
+                (Token::Verbatim, "% $x$\n\\begin{a}"),
+                (Token::EnvironmentEnd("verbatim"), "\\end{verbatim}"),
+                (Token::Comment, "%"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_environment_arguments() {
+        let source = "\\begin{lstlisting}[caption={a, [b]}]$\\end{lstlisting}\\begin{minted} {c}\\end{minted}";
+        let tokens = lex(source, &VerbatimConfig::default());
+
+        assert_eq!(tokens[1], (Token::BracketOpen, "["));
+        assert!(tokens.contains(&(Token::Verbatim, "$")));
+        assert_eq!(
+            &tokens[tokens.len() - 6..],
+            &[
+                (Token::EnvironmentBegin("minted"), "\\begin{minted}"),
+                (Token::TabsOrSpaces, " "),
+                (Token::BraceOpen, "{"),
+                (Token::Word, "c"),
+                (Token::BraceClose, "}"),
+                (Token::EnvironmentEnd("minted"), "\\end{minted}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_environment() {
+        let source = "\\begin{comment}\n$\n";
+        assert_eq!(
+            lex(source, &VerbatimConfig::default())[1],
+            (Token::Verbatim, "\n$\n")
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let source = "\\verb|%$| \\verb*+a b+ \\verb|open\n\\verb";
+        let tokens = lex(source, &VerbatimConfig::default());
+        let verbatims: Vec<_> = tokens
+            .iter()
+            .filter(|(token, _)| token == &Token::Verbatim)
+            .map(|(_, slice)| *slice)
+            .collect();
+
+        assert_eq!(verbatims, vec!["|%$|", "*+a b+", "|open"]);
+        assert_eq!(tokens.last(), Some(&(Token::CommandName, "\\verb")));
+    }
+
+    #[test]
+    fn test_config() {
+        let source = r"\lstnewenvironment{code}[1][]{}{}
+\begin{code}[x]$\end{code}
+\begin{Verbatim}$\end{Verbatim}
+\begin{verbatim}$\end{verbatim}";
+        let mut config = VerbatimConfig::default();
+        config.extend(&harvest(source, Token::lexer(source).spanned()));
+        config.insert_environment("Verbatim", Signature::default());
+        config.remove_environment("verbatim");
+
+        let tokens = lex(source, &config);
+        let dollars = tokens
+            .iter()
+            .filter(|(token, _)| token == &Token::DollarSign)
+            .count();
+        assert_eq!(dollars, 1);
+        assert!(tokens.contains(&(Token::BracketOpen, "[")));
+    }
+
+    #[test]
+    fn test_plain() {
+        let source = include_str!("../../../tests/data/minimal.tex");
+        let tokens: Vec<_> = VerbatimLexer::new(source, &VerbatimConfig::default()).collect();
+        assert_eq!(tokens, Token::lexer(source).spanned().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,377 @@
+//! Visiting and rewriting [`SyntaxTree`]s.
+//!
+//! The [`Visit`] and [`VisitMut`] traits provide one hook per kind of node,
+//! each defaulting to a `walk_*` function that visits the children of the node.
+//! Overriding a hook allows to act on specific nodes only, and calling the
+//! corresponding `walk_*` function from the hook continues the traversal.
+//!
+//! With [`VisitMut`], nodes can be modified in place and replaced with text
+//! (see [`Node::replace_with`]). Writing the tree afterward (see [`SyntaxTree::write`])
+//! keeps the original bytes of every untouched node.
+//!
+//! # Example
+//!
+//! ```
+//! use untex::latex::parse::{Node, SyntaxTree};
+//! use untex::latex::visit::{walk_node_mut, VisitMut};
+//!
+//! /// Replace `\bf` with `\bfseries`, and remove all comments.
+//! struct Rewrite;
+//!
+//! impl<'source> VisitMut<'source> for Rewrite {
+//!     fn visit_node(&mut self, node: &mut Node<'source>) {
+//!         match node {
+//!             Node::Command(command) if command.name == "bf" => command.name = "bfseries",
+//!             Node::Comment(_) => node.replace_with(""),
+//!             _ => walk_node_mut(self, node),
+//!         }
+//!     }
+//! }
+//!
+//! let mut tree = SyntaxTree::parse(r"{\bf bold}  % comment");
+//! tree.visit_mut(&mut Rewrite);
+//! assert_eq!(tree.to_string(), r"{\bfseries bold}  ");
+//! ```
+
+use crate::latex::parse::{Argument, Command, Environment, Group, Math, Node, SyntaxTree};
+use crate::latex::token::Span;
+
+/// Trait for visiting a syntax tree by reference.
+pub trait Visit<'source> {
+    /// Visit any node.
+    fn visit_node(&mut self, node: &Node<'source>) {
+        walk_node(self, node)
+    }
+
+    /// Visit a command.
+    fn visit_command(&mut self, command: &Command<'source>) {
+        walk_command(self, command)
+    }
+
+    /// Visit an argument of a command or an environment.
+    fn visit_argument(&mut self, argument: &Argument<'source>) {
+        walk_argument(self, argument)
+    }
+
+    /// Visit a group, either a standalone one or an argument.
+    fn visit_group(&mut self, group: &Group<'source>) {
+        walk_group(self, group)
+    }
+
+    /// Visit an environment.
+    fn visit_environment(&mut self, environment: &Environment<'source>) {
+        walk_environment(self, environment)
+    }
+
+    /// Visit a math region.
+    fn visit_math(&mut self, math: &Math<'source>) {
+        walk_math(self, math)
+    }
+
+    /// Visit a comment.
+    fn visit_comment(&mut self, _span: &Span) {}
+
+    /// Visit text.
+    fn visit_text(&mut self, _span: &Span) {}
+
+    /// Visit a replacement.
+    fn visit_replacement(&mut self, _span: &Span, _text: &str) {}
+}
+
+/// Visit the content of a node.
+pub fn walk_node<'source, V>(visitor: &mut V, node: &Node<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    match node {
+        Node::Command(command) => visitor.visit_command(command),
+        Node::Group(group) => visitor.visit_group(group),
+        Node::Environment(environment) => visitor.visit_environment(environment),
+        Node::Math(math) => visitor.visit_math(math),
+        Node::Comment(span) => visitor.visit_comment(span),
+        Node::Text(span) => visitor.visit_text(span),
+        Node::Replacement(span, text) => visitor.visit_replacement(span, text),
+    }
+}
+
+/// Visit the arguments of a command.
+pub fn walk_command<'source, V>(visitor: &mut V, command: &Command<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    for argument in command.args.iter() {
+        visitor.visit_argument(argument);
+    }
+}
+
+/// Visit the group of an argument, if any.
+pub fn walk_argument<'source, V>(visitor: &mut V, argument: &Argument<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    if let Some(group) = argument.group() {
+        visitor.visit_group(group);
+    }
+}
+
+/// Visit the children of a group.
+pub fn walk_group<'source, V>(visitor: &mut V, group: &Group<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    for node in group.children.iter() {
+        visitor.visit_node(node);
+    }
+}
+
+/// Visit the arguments, then the children, of an environment.
+pub fn walk_environment<'source, V>(visitor: &mut V, environment: &Environment<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    for argument in environment.args.iter() {
+        visitor.visit_argument(argument);
+    }
+    for node in environment.children.iter() {
+        visitor.visit_node(node);
+    }
+}
+
+/// Visit the children of a math region.
+pub fn walk_math<'source, V>(visitor: &mut V, math: &Math<'source>)
+where
+    V: Visit<'source> + ?Sized,
+{
+    for node in math.children.iter() {
+        visitor.visit_node(node);
+    }
+}
+
+/// Trait for visiting a syntax tree by mutable reference.
+pub trait VisitMut<'source> {
+    /// Visit any node.
+    fn visit_node(&mut self, node: &mut Node<'source>) {
+        walk_node_mut(self, node)
+    }
+
+    /// Visit a command.
+    fn visit_command(&mut self, command: &mut Command<'source>) {
+        walk_command_mut(self, command)
+    }
+
+    /// Visit an argument of a command or an environment.
+    fn visit_argument(&mut self, argument: &mut Argument<'source>) {
+        walk_argument_mut(self, argument)
+    }
+
+    /// Visit a group, either a standalone one or an argument.
+    fn visit_group(&mut self, group: &mut Group<'source>) {
+        walk_group_mut(self, group)
+    }
+
+    /// Visit an environment.
+    fn visit_environment(&mut self, environment: &mut Environment<'source>) {
+        walk_environment_mut(self, environment)
+    }
+
+    /// Visit a math region.
+    fn visit_math(&mut self, math: &mut Math<'source>) {
+        walk_math_mut(self, math)
+    }
+
+    /// Visit a comment.
+    fn visit_comment(&mut self, _span: &mut Span) {}
+
+    /// Visit text.
+    fn visit_text(&mut self, _span: &mut Span) {}
+
+    /// Visit a replacement.
+    fn visit_replacement(&mut self, _span: &mut Span, _text: &mut String) {}
+}
+
+/// Visit the content of a node, by mutable reference.
+pub fn walk_node_mut<'source, V>(visitor: &mut V, node: &mut Node<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    match node {
+        Node::Command(command) => visitor.visit_command(command),
+        Node::Group(group) => visitor.visit_group(group),
+        Node::Environment(environment) => visitor.visit_environment(environment),
+        Node::Math(math) => visitor.visit_math(math),
+        Node::Comment(span) => visitor.visit_comment(span),
+        Node::Text(span) => visitor.visit_text(span),
+        Node::Replacement(span, text) => visitor.visit_replacement(span, text),
+    }
+}
+
+/// Visit the arguments of a command, by mutable reference.
+pub fn walk_command_mut<'source, V>(visitor: &mut V, command: &mut Command<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    for argument in command.args.iter_mut() {
+        visitor.visit_argument(argument);
+    }
+}
+
+/// Visit the group of an argument, if any, by mutable reference.
+pub fn walk_argument_mut<'source, V>(visitor: &mut V, argument: &mut Argument<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    match argument {
+        Argument::Star(_) => (),
+        Argument::Optional(group) | Argument::Required(group) => visitor.visit_group(group),
+    }
+}
+
+/// Visit the children of a group, by mutable reference.
+pub fn walk_group_mut<'source, V>(visitor: &mut V, group: &mut Group<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    for node in group.children.iter_mut() {
+        visitor.visit_node(node);
+    }
+}
+
+/// Visit the arguments, then the children, of an environment, by mutable reference.
+pub fn walk_environment_mut<'source, V>(visitor: &mut V, environment: &mut Environment<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    for argument in environment.args.iter_mut() {
+        visitor.visit_argument(argument);
+    }
+    for node in environment.children.iter_mut() {
+        visitor.visit_node(node);
+    }
+}
+
+/// Visit the children of a math region, by mutable reference.
+pub fn walk_math_mut<'source, V>(visitor: &mut V, math: &mut Math<'source>)
+where
+    V: VisitMut<'source> + ?Sized,
+{
+    for node in math.children.iter_mut() {
+        visitor.visit_node(node);
+    }
+}
+
+impl<'source> SyntaxTree<'source> {
+    /// Visit every top-level node with a visitor.
+    pub fn visit<V>(&self, visitor: &mut V)
+    where
+        V: Visit<'source> + ?Sized,
+    {
+        for node in self.nodes().iter() {
+            visitor.visit_node(node);
+        }
+    }
+
+    /// Visit every top-level node with a mutable visitor.
+    pub fn visit_mut<V>(&mut self, visitor: &mut V)
+    where
+        V: VisitMut<'source> + ?Sized,
+    {
+        for node in self.nodes_mut().iter_mut() {
+            visitor.visit_node(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter<'source> {
+        commands: Vec<&'source str>,
+        environments: Vec<&'source str>,
+        maths: usize,
+    }
+
+    impl<'source> Visit<'source> for Counter<'source> {
+        fn visit_command(&mut self, command: &Command<'source>) {
+            self.commands.push(command.name);
+            walk_command(self, command);
+        }
+
+        fn visit_environment(&mut self, environment: &Environment<'source>) {
+            self.environments.push(environment.name);
+            walk_environment(self, environment);
+        }
+
+        fn visit_math(&mut self, math: &Math<'source>) {
+            self.maths += 1;
+            walk_math(self, math);
+        }
+    }
+
+    #[test]
+    fn test_visit() {
+        let tree = SyntaxTree::parse(include_str!("../../../tests/data/minimal.tex"));
+        let mut counter = Counter::default();
+        tree.visit(&mut counter);
+
+        assert_eq!(
+            counter.commands,
+            vec![
+                "documentclass",
+                "usepackage",
+                "title",
+                "author",
+                "date",
+                "maketitle",
+                "pi",
+                "pi",
+                "LaTeX",
+                "section",
+                "pi"
+            ]
+        );
+        assert_eq!(counter.environments, vec!["document", "equation"]);
+        assert_eq!(counter.maths, 2);
+    }
+
+    struct Rename;
+
+    impl<'source> VisitMut<'source> for Rename {
+        fn visit_environment(&mut self, environment: &mut Environment<'source>) {
+            if environment.name == "equation" {
+                environment.name = "equation*";
+            }
+            walk_environment_mut(self, environment);
+        }
+
+        fn visit_math(&mut self, math: &mut Math<'source>) {
+            for node in math.children.iter_mut() {
+                if matches!(node, Node::Command(command) if command.name == "pi") {
+                    node.replace_with(r"\uppi");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let source = "\\begin{equation}[x]\n  $\\pi$ \\pi\n\\end{equation} % \\pi";
+        let mut tree = SyntaxTree::parse(source);
+        tree.visit_mut(&mut Rename);
+
+        assert_eq!(
+            tree.to_string(),
+            "\\begin{equation*}[x]\n  $\\uppi$ \\pi\n\\end{equation*} % \\pi"
+        );
+    }
+
+    #[test]
+    fn test_visit_mut_untouched() {
+        let source = include_str!("../../../tests/data/pgfplots_table.tex");
+        let mut tree = SyntaxTree::parse(source);
+        tree.visit_mut(&mut Rename);
+
+        assert_eq!(tree.to_string(), source);
+    }
+}
@@ -9,16 +9,17 @@ use crate::error::Error;
 use logos::Logos;
 
 #[derive(Debug, PartialEq, Logos)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Category codes, as defined in TeX by Topic (section 2.3).
 ///
 /// > Each of the 256 character codes (0–255) has an associated category code, though not necessarily
-/// always the same one. There are 16 categories, numbered 0–15. When scanning the input, TEX thus
-/// forms character-code–category-code pairs. The input processor sees only these pairs; from them
-/// are formed character tokens, control sequence tokens, and parameter tokens. These tokens are then passed to TEX’s expansion and execution processes.
+/// > always the same one. There are 16 categories, numbered 0–15. When scanning the input, TEX thus
+/// > forms character-code–category-code pairs. The input processor sees only these pairs; from them
+/// > are formed character tokens, control sequence tokens, and parameter tokens. These tokens are then passed to TEX’s expansion and execution processes.
 /// >
 /// > A character token is a character-code–category-code pair that is passed unchanged.
-/// A control sequence token consists of one or more characters preceded by an escape character;
-/// see below. Parameter tokens are also explained below.
+/// > A control sequence token consists of one or more characters preceded by an escape character;
+/// > see below. Parameter tokens are also explained below.
 ///
 /// The documentation of each enum variant is simple a copy / paste
 /// from aforementionned book.
@@ -259,7 +260,7 @@ mod tests {
 
     #[test]
     fn catcode_letter() {
-        for s in 'A'..'Z' {
+        for s in 'A'..='Z' {
             let u = s.to_string();
             let l = s.to_ascii_lowercase().to_string();
             assert_catcode_positions!(&u, CategoryCode::Letter, 0..1);
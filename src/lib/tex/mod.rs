@@ -3,6 +3,6 @@
 //! This crate relates to the TeX processor.
 //! Most of the content of this takes source from
 //! > "*TeX by Topic, a TeXnician's reference*",
-//! written by *Victor Eijkhout*,
-//! available here <https://texdoc.org/serve/TeXbyTopic.pdf/0>.
+//! > written by *Victor Eijkhout*,
+//! > available here <https://texdoc.org/serve/TeXbyTopic.pdf/0>.
 pub mod category_codes;